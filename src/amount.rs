@@ -0,0 +1,375 @@
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use stellar_base::amount::{Amount, Stroops};
+
+/// Exact Pi amount
+///
+/// Stored as stroops (1 Pi = 10 000 000 stroops), so there is no floating point rounding in the payment amounts.
+/// Parsing from and formatting to decimal strings is exact, the arithmetic is checked.
+/// On the Pi API's JSON it's represented as a number, which is only exact below `PiAmount::MAX_JSON` in absolute value,
+/// the larger amounts can't be serialized. It can be converted losslessly to `stellar_base::amount::Amount`.
+///
+/// ```
+/// use pi_rust::amount::PiAmount;
+///
+/// let amount: PiAmount = "0.1".parse().unwrap();
+/// let sum = amount.checked_add("0.2".parse().unwrap()).unwrap();
+/// assert_eq!(sum, "0.3".parse().unwrap());
+/// assert_eq!(sum.to_string(), "0.3000000");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PiAmount(i64);
+
+impl PiAmount {
+    /// Number of decimals the Pi blockchain supports
+    pub const DECIMALS: u32 = 7;
+    /// Stroops in 1 Pi
+    pub const STROOPS_PER_PI: i64 = 10_000_000;
+    pub const ZERO: PiAmount = PiAmount(0);
+    /// Largest amount written exactly as a JSON number, a float has 15 significant digits for sure
+    pub const MAX_JSON: PiAmount = PiAmount(999_999_999_999_999);
+
+    pub fn from_stroops(stroops: i64) -> Self {
+        PiAmount(stroops)
+    }
+
+    /// Creating the amount from whole Pi, `None` if it's overflowing
+    pub fn from_pi(pi: i64) -> Option<Self> {
+        pi.checked_mul(Self::STROOPS_PER_PI).map(PiAmount)
+    }
+
+    pub fn stroops(&self) -> i64 {
+        self.0
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.0 > 0
+    }
+
+    pub fn checked_add(self, other: PiAmount) -> Option<PiAmount> {
+        self.0.checked_add(other.0).map(PiAmount)
+    }
+
+    pub fn checked_sub(self, other: PiAmount) -> Option<PiAmount> {
+        self.0.checked_sub(other.0).map(PiAmount)
+    }
+
+    pub fn checked_mul(self, factor: i64) -> Option<PiAmount> {
+        self.0.checked_mul(factor).map(PiAmount)
+    }
+
+    /// Parsing a decimal string and rounding it half away from zero to the nearest stroop.
+    /// Only used for JSON numbers, which are floats on the wire and can't carry more precision anyway.
+    fn parse_rounded(s: &str) -> Result<Self, PiAmountError> {
+        let (negative, digits) = split_sign(s);
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if fraction.len() <= Self::DECIMALS as usize {
+            return s.parse();
+        }
+
+        let (kept, rest) = fraction.split_at(Self::DECIMALS as usize);
+        let truncated: PiAmount =
+            format!("{}{}.{}", if negative { "-" } else { "" }, whole, kept).parse()?;
+        if !rest.chars().all(|c| c.is_ascii_digit()) {
+            return Err(PiAmountError::InvalidFormat(s.to_string()));
+        }
+        if rest.starts_with(['5', '6', '7', '8', '9']) {
+            let one = if negative { -1 } else { 1 };
+            truncated
+                .checked_add(PiAmount(one))
+                .ok_or_else(|| PiAmountError::Overflow(s.to_string()))
+        } else {
+            Ok(truncated)
+        }
+    }
+}
+
+fn split_sign(s: &str) -> (bool, &str) {
+    match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    }
+}
+
+impl FromStr for PiAmount {
+    type Err = PiAmountError;
+
+    /// Exact parsing, errors if there are more than 7 decimals instead of rounding
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = split_sign(s.trim());
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+        if (whole.is_empty() && fraction.is_empty())
+            || !whole.chars().all(|c| c.is_ascii_digit())
+            || !fraction.chars().all(|c| c.is_ascii_digit())
+        {
+            return Err(PiAmountError::InvalidFormat(s.to_string()));
+        }
+        if fraction.len() > Self::DECIMALS as usize {
+            return Err(PiAmountError::TooManyDecimals(s.to_string()));
+        }
+
+        let whole_stroops = if whole.is_empty() {
+            0
+        } else {
+            whole
+                .parse::<i64>()
+                .ok()
+                .and_then(|w| w.checked_mul(Self::STROOPS_PER_PI))
+                .ok_or_else(|| PiAmountError::Overflow(s.to_string()))?
+        };
+        let fraction_stroops = if fraction.is_empty() {
+            0
+        } else {
+            // The length checked above, so it's at most 7 digits and can't overflow
+            fraction.parse::<i64>().unwrap_or(0)
+                * 10_i64.pow(Self::DECIMALS - fraction.len() as u32)
+        };
+
+        let stroops = whole_stroops
+            .checked_add(fraction_stroops)
+            .ok_or_else(|| PiAmountError::Overflow(s.to_string()))?;
+
+        Ok(PiAmount(if negative { -stroops } else { stroops }))
+    }
+}
+
+impl fmt::Display for PiAmount {
+    /// Formatting like Horizon does, always with 7 decimals
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        let per_pi = Self::STROOPS_PER_PI as u64;
+        write!(f, "{}{}.{:07}", sign, abs / per_pi, abs % per_pi)
+    }
+}
+
+impl Serialize for PiAmount {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // The division is correctly rounded, so the float is the nearest one to the exact decimal value.
+        // With at most 15 significant digits that float is unique to the decimal, and serde_json writes it back
+        // with the shortest representation, which is the decimal itself. Above that it would be rounded.
+        if self.0.unsigned_abs() > Self::MAX_JSON.0 as u64 {
+            return Err(ser::Error::custom(PiAmountError::Overflow(
+                self.to_string(),
+            )));
+        }
+        serializer.serialize_f64(self.0 as f64 / Self::STROOPS_PER_PI as f64)
+    }
+}
+
+impl<'de> Deserialize<'de> for PiAmount {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PiAmountVisitor;
+
+        impl<'de> de::Visitor<'de> for PiAmountVisitor {
+            type Value = PiAmount;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a Pi amount as a number or a decimal string")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<PiAmount, E> {
+                PiAmount::from_pi(v)
+                    .ok_or_else(|| E::custom(PiAmountError::Overflow(v.to_string())))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<PiAmount, E> {
+                i64::try_from(v)
+                    .ok()
+                    .and_then(PiAmount::from_pi)
+                    .ok_or_else(|| E::custom(PiAmountError::Overflow(v.to_string())))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<PiAmount, E> {
+                if !v.is_finite() {
+                    return Err(E::custom(PiAmountError::InvalidFormat(v.to_string())));
+                }
+                // Display of f64 is the shortest decimal which reads back to the same float, never in exponent form
+                PiAmount::parse_rounded(&v.to_string()).map_err(E::custom)
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<PiAmount, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(PiAmountVisitor)
+    }
+}
+
+impl From<PiAmount> for Stroops {
+    fn from(amount: PiAmount) -> Self {
+        Stroops::new(amount.0)
+    }
+}
+
+impl From<Stroops> for PiAmount {
+    fn from(stroops: Stroops) -> Self {
+        PiAmount(stroops.to_i64())
+    }
+}
+
+impl TryFrom<PiAmount> for Amount {
+    type Error = stellar_base::error::Error;
+
+    fn try_from(amount: PiAmount) -> Result<Self, Self::Error> {
+        Amount::from_stroops(&amount.into())
+    }
+}
+
+impl TryFrom<Amount> for PiAmount {
+    type Error = stellar_base::error::Error;
+
+    fn try_from(amount: Amount) -> Result<Self, Self::Error> {
+        Ok(amount.to_stroops()?.into())
+    }
+}
+
+/// Errors of the `PiAmount` parsing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PiAmountError {
+    InvalidFormat(String),
+    TooManyDecimals(String),
+    Overflow(String),
+}
+
+impl fmt::Display for PiAmountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PiAmountError::InvalidFormat(s) => write!(f, "Invalid Pi amount: {:?}", s),
+            PiAmountError::TooManyDecimals(s) => write!(
+                f,
+                "Pi amount can have at most {} decimals: {:?}",
+                PiAmount::DECIMALS,
+                s
+            ),
+            PiAmountError::Overflow(s) => write!(f, "Pi amount is out of range: {:?}", s),
+        }
+    }
+}
+
+impl std::error::Error for PiAmountError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_and_format() {
+        let amount: PiAmount = "3.14".parse().unwrap();
+        assert_eq!(amount.stroops(), 31_400_000);
+        assert_eq!(amount.to_string(), "3.1400000");
+
+        let amount: PiAmount = "0.0000001".parse().unwrap();
+        assert_eq!(amount.stroops(), 1);
+
+        let amount: PiAmount = "-12".parse().unwrap();
+        assert_eq!(amount.stroops(), -120_000_000);
+        assert_eq!(amount.to_string(), "-12.0000000");
+
+        assert_eq!(".5".parse::<PiAmount>().unwrap().stroops(), 5_000_000);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            "0.00000001".parse::<PiAmount>(),
+            Err(PiAmountError::TooManyDecimals(_))
+        ));
+        assert!(matches!(
+            "1e-7".parse::<PiAmount>(),
+            Err(PiAmountError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            "".parse::<PiAmount>(),
+            Err(PiAmountError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            "922337203686".parse::<PiAmount>(),
+            Err(PiAmountError::Overflow(_))
+        ));
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let a: PiAmount = "0.1".parse().unwrap();
+        let b: PiAmount = "0.2".parse().unwrap();
+        assert_eq!(a.checked_add(b), Some("0.3".parse().unwrap()));
+        assert_eq!(b.checked_sub(a), Some(a));
+        assert_eq!(a.checked_mul(3), Some("0.3".parse().unwrap()));
+        assert_eq!(PiAmount::from_stroops(i64::MAX).checked_add(a), None);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let amount: PiAmount = "1.2345678".parse().unwrap();
+        let value = serde_json::to_value(amount).unwrap();
+        assert_eq!(value, json!(1.2345678));
+        assert_eq!(serde_json::from_value::<PiAmount>(value).unwrap(), amount);
+
+        assert_eq!(
+            serde_json::from_value::<PiAmount>(json!(2)).unwrap(),
+            PiAmount::from_pi(2).unwrap()
+        );
+        assert_eq!(
+            serde_json::from_value::<PiAmount>(json!("0.1000000")).unwrap(),
+            "0.1".parse().unwrap()
+        );
+        // Floats which can't be represented in stroops are rounded to the nearest one
+        assert_eq!(
+            serde_json::from_value::<PiAmount>(json!(0.1 + 0.2)).unwrap(),
+            "0.3".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_json_range() {
+        for stroops in [
+            PiAmount::MAX_JSON.stroops(),
+            PiAmount::MAX_JSON.stroops() - 1,
+            -PiAmount::MAX_JSON.stroops(),
+            123_456_789_012_345,
+        ] {
+            let amount = PiAmount::from_stroops(stroops);
+            let json = serde_json::to_string(&amount).unwrap();
+            assert_eq!(json, amount.to_string().trim_end_matches('0'));
+            assert_eq!(serde_json::from_str::<PiAmount>(&json).unwrap(), amount);
+        }
+
+        // Near i64::MAX a float can't hold the amount, it's an error instead of a rounded number
+        for stroops in [
+            i64::MAX,
+            i64::MAX - 1,
+            i64::MIN + 1,
+            PiAmount::MAX_JSON.stroops() + 1,
+        ] {
+            assert!(serde_json::to_string(&PiAmount::from_stroops(stroops)).is_err());
+            assert_eq!(
+                serde_json::from_value::<PiAmount>(json!(
+                    PiAmount::from_stroops(stroops).to_string()
+                ))
+                .unwrap()
+                .stroops(),
+                stroops
+            );
+        }
+    }
+
+    #[test]
+    fn test_stellar_amount_conversion() {
+        let amount: PiAmount = "10.5".parse().unwrap();
+        let stellar_amount = Amount::try_from(amount).unwrap();
+        assert_eq!(stellar_amount, Amount::from_str("10.5").unwrap());
+        assert_eq!(PiAmount::try_from(stellar_amount).unwrap(), amount);
+    }
+}
@@ -0,0 +1,204 @@
+//! The blocking `PiNetwork`, enabled by the `blocking` cargo feature
+//!
+//! For synchronous applications, e.g. batch jobs and CLI tools, which don't run a tokio runtime.
+//! Every method of the async `PiNetwork` is here with the same arguments, it runs the async one on a runtime of its own
+//! and blocks the thread until it's done, so the requests, errors and DTOs are the same.
+//!
+//! ```ignore
+//! use pi_rust::blocking::PiNetwork;
+//!
+//! let pi = PiNetwork::new(api_key, wallet_private_seed, None, None)?;
+//! let payment: PaymentDTO = pi.get_payment(payment_id)?;
+//! ```
+//!
+//! Like `reqwest::blocking`, it must not be used inside an async runtime, the methods panic there.
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::channels::{ChannelFunding, ChannelPool};
+use crate::resolution::{IncompletePaymentPolicy, IncompletePaymentResolution};
+use crate::sequence::SequenceManager;
+use crate::signer::{IntoTransactionSigner, TransactionSigner};
+use crate::types::*;
+use crate::verification::U2APaymentVerification;
+
+/// The `PiNetwork` blocking the thread, see at the async `PiNetwork` for the methods
+///
+/// A clone shares the runtime and the connections of the original.
+///
+/// - inner: crate::PiNetwork - the async `PiNetwork` the methods are run with
+/// - runtime: `Arc<Runtime>` - the current-thread runtime the async methods are run on
+
+#[derive(Debug, Clone)]
+pub struct PiNetwork {
+    inner: crate::PiNetwork,
+    runtime: Arc<Runtime>,
+}
+
+impl PiNetwork {
+    pub fn new(
+        api_key: String,
+        wallet: impl IntoTransactionSigner,
+        network_passphrase: Option<NetworkPassphrase>,
+        options: Option<ReqwestClientOptions>,
+    ) -> Result<Self, PiError> {
+        Self::from_async(crate::PiNetwork::new(
+            api_key,
+            wallet,
+            network_passphrase,
+            options,
+        )?)
+    }
+
+    pub fn with_profile(
+        api_key: String,
+        wallet: impl IntoTransactionSigner,
+        profile: NetworkProfile,
+        options: Option<ReqwestClientOptions>,
+    ) -> Result<Self, PiError> {
+        Self::from_async(crate::PiNetwork::with_profile(
+            api_key, wallet, profile, options,
+        )?)
+    }
+
+    /// Wrapping an async `PiNetwork`, it shouldn't have been used on another runtime, its connections belong to that one
+    pub fn from_async(inner: crate::PiNetwork) -> Result<Self, PiError> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| PiError::Message(format!("Can't start the runtime! {}", e)))?;
+        Ok(PiNetwork {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// The async `PiNetwork` the methods are run with
+    pub fn as_async(&self) -> &crate::PiNetwork {
+        &self.inner
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    pub fn payment_args(&self) -> PaymentArgsBuilder {
+        self.inner.payment_args()
+    }
+
+    pub fn create_payment<M: Serialize>(
+        &self,
+        payment_data: PaymentArgs<M>,
+    ) -> Result<String, PiError> {
+        self.block_on(self.inner.create_payment(payment_data))
+    }
+
+    pub fn submit_payment(&self, payment_id: String) -> Result<String, PiError> {
+        self.block_on(self.inner.submit_payment(payment_id))
+    }
+
+    pub fn resubmit_payment(
+        &self,
+        payment_id: String,
+        envelope_xdr: &str,
+    ) -> Result<String, PiError> {
+        self.block_on(self.inner.resubmit_payment(payment_id, envelope_xdr))
+    }
+
+    pub fn complete_payment(
+        &self,
+        payment_id: String,
+        tx_id: String,
+    ) -> Result<PaymentDTO, PiError> {
+        self.block_on(self.inner.complete_payment(payment_id, tx_id))
+    }
+
+    pub fn get_payment<M: DeserializeOwned>(
+        &self,
+        payment_id: String,
+    ) -> Result<PaymentDTO<M>, PiError> {
+        self.block_on(self.inner.get_payment(payment_id))
+    }
+
+    pub fn authenticate_user(&self, access_token: String) -> Result<UserDTO, PiError> {
+        self.block_on(self.inner.authenticate_user(access_token))
+    }
+
+    pub fn approve_payment(&self, payment_id: String) -> Result<PaymentDTO, PiError> {
+        self.block_on(self.inner.approve_payment(payment_id))
+    }
+
+    pub fn verify_u2a_payment(
+        &self,
+        payment_id: String,
+        txid: String,
+    ) -> Result<U2APaymentVerification, PiError> {
+        self.block_on(self.inner.verify_u2a_payment(payment_id, txid))
+    }
+
+    pub fn cancel_payment(&self, payment_id: String) -> Result<PaymentDTO, PiError> {
+        self.block_on(self.inner.cancel_payment(payment_id))
+    }
+
+    pub fn get_incomplete_server_payments(&self) -> Result<Vec<PaymentDTO>, PiError> {
+        self.block_on(self.inner.get_incomplete_server_payments())
+    }
+
+    pub fn resolve_incomplete_payments(
+        &self,
+        policy: IncompletePaymentPolicy,
+    ) -> Result<Vec<IncompletePaymentResolution>, PiError> {
+        self.block_on(self.inner.resolve_incomplete_payments(policy))
+    }
+
+    pub fn create_channel_accounts(
+        &self,
+        pool: &ChannelPool,
+        starting_balance: PiAmount,
+    ) -> Result<Vec<String>, PiError> {
+        self.block_on(self.inner.create_channel_accounts(pool, starting_balance))
+    }
+
+    pub fn fund_channel_accounts(&self, amount: PiAmount) -> Result<ChannelFunding, PiError> {
+        self.block_on(self.inner.fund_channel_accounts(amount))
+    }
+
+    pub fn fund_channels(
+        &self,
+        public_keys: &[String],
+        amount: PiAmount,
+    ) -> Result<ChannelFunding, PiError> {
+        self.block_on(self.inner.fund_channels(public_keys, amount))
+    }
+
+    pub fn retire_channel_accounts(&self) -> Result<Vec<String>, PiError> {
+        self.block_on(self.inner.retire_channel_accounts())
+    }
+
+    pub fn set_channel_pool(&self, pool: ChannelPool) {
+        self.inner.set_channel_pool(pool);
+    }
+
+    pub fn channel_pool(&self) -> Result<Arc<ChannelPool>, PiError> {
+        self.inner.channel_pool()
+    }
+
+    pub fn sequence_manager(&self) -> &SequenceManager {
+        self.inner.sequence_manager()
+    }
+
+    pub fn profile(&self) -> &NetworkProfile {
+        self.inner.profile()
+    }
+
+    pub fn signer(&self) -> &dyn TransactionSigner {
+        self.inner.signer()
+    }
+
+    pub fn validate_seed_format(seed: &str) -> Result<(), PiError> {
+        crate::PiNetwork::validate_seed_format(seed)
+    }
+}
@@ -0,0 +1,513 @@
+use serde::Serialize;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use stellar_base::{
+    amount::{Amount, Stroops},
+    asset::Asset,
+    crypto::{MuxedAccount, SodiumKeyPair},
+    operations::Operation,
+    transaction::Transaction,
+    Network,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use zeroize::Zeroizing;
+
+use crate::amount::PiAmount;
+use crate::stellar_sdk::keypair::Redacted;
+use crate::stellar_sdk::lib::Keypair;
+use crate::types::PiError;
+use crate::PiNetwork;
+
+/// Maximum number of operations in one transaction
+const MAX_OPERATIONS: usize = 100;
+
+/// Pool of channel accounts for submitting A2U payments in parallel
+///
+/// Every account has one sequence number, so the transactions of the app wallet can only be submitted one by one.
+/// With a pool the transactions are made by a channel account: the channel is the source of the transaction,
+/// it pays the fee and gives the sequence number, while the payment operation's source stays the app wallet,
+/// so the payment is still made from the app wallet. Every channel carries one transaction at a time,
+/// so as many payments can be in flight as many channels are in the pool.
+///
+/// The seeds of new channels are made by `ChannelPool::generate`, then the accounts are created, funded and retired by
+/// `PiNetwork::create_channel_accounts`, `fund_channel_accounts` and `retire_channel_accounts`.
+/// Keep their seeds (`secret_seeds`) before the accounts are created, to make the same pool again with `ChannelPool::new`.
+///
+/// ```ignore
+/// let pool = ChannelPool::generate(5)?;
+/// save_seeds(pool.secret_seeds());
+/// pi.create_channel_accounts(&pool, "2".parse()?).await?;
+/// pi.set_channel_pool(pool);
+/// ```
+///
+/// The seeds are zeroed when the pool is dropped and they are left out of `Debug`.
+pub struct ChannelPool {
+    // The channels which are not carrying a transaction right now
+    free: Arc<Mutex<Vec<Keypair>>>,
+    available: Arc<Semaphore>,
+    public_keys: Vec<String>,
+    secret_seeds: Vec<Zeroizing<String>>,
+}
+
+impl fmt::Debug for ChannelPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChannelPool")
+            .field("free", &self.free)
+            .field("available", &self.available)
+            .field("public_keys", &self.public_keys)
+            .field("secret_seeds", &Redacted)
+            .finish()
+    }
+}
+
+/// A channel account taken from the pool, it's given back when the lease is dropped
+#[derive(Debug)]
+pub struct ChannelLease {
+    free: Arc<Mutex<Vec<Keypair>>>,
+    keypair: Option<Keypair>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl ChannelPool {
+    /// Making the pool from the secret seeds of existing channel accounts
+    pub fn new(secret_seeds: Vec<String>) -> Result<Self, PiError> {
+        let keypairs = secret_seeds
+            .iter()
+            .map(|seed| {
+                PiNetwork::validate_seed_format(seed)?;
+                Keypair::from_secret_key(seed).map_err(|e| PiError::Message(format!("{:?}", e)))
+            })
+            .collect::<Result<Vec<_>, PiError>>()?;
+        let secret_seeds = secret_seeds.into_iter().map(Zeroizing::new).collect();
+        Ok(Self::from_keypairs(keypairs, secret_seeds))
+    }
+
+    /// Making a pool of new random channels, their accounts are created by `PiNetwork::create_channel_accounts`
+    pub fn generate(count: usize) -> Result<Self, PiError> {
+        let mut keypairs = (0..count)
+            .map(|_| Keypair::random())
+            .collect::<Result<Vec<_>, _>>()?;
+        let secret_seeds = keypairs
+            .iter_mut()
+            .map(Keypair::secret_key)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_keypairs(keypairs, secret_seeds))
+    }
+
+    fn from_keypairs(keypairs: Vec<Keypair>, secret_seeds: Vec<Zeroizing<String>>) -> Self {
+        ChannelPool {
+            available: Arc::new(Semaphore::new(keypairs.len())),
+            public_keys: keypairs.iter().map(Keypair::public_key).collect(),
+            free: Arc::new(Mutex::new(keypairs)),
+            secret_seeds,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.public_keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.public_keys.is_empty()
+    }
+
+    /// Account IDs of the channels
+    pub fn public_keys(&self) -> &[String] {
+        &self.public_keys
+    }
+
+    /// Secret seeds of the channels, store them safely, the funds of the channels can only be recovered with them
+    pub fn secret_seeds(&self) -> &[Zeroizing<String>] {
+        &self.secret_seeds
+    }
+
+    /// Number of channels not carrying a transaction right now
+    pub fn available(&self) -> usize {
+        self.available.available_permits()
+    }
+
+    /// Taking a free channel, waiting until one is given back if all of them are in use
+    pub async fn acquire(&self) -> Result<ChannelLease, PiError> {
+        let permit = self
+            .available
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| PiError::Message("The channel pool is closed".to_string()))?;
+        let keypair = self
+            .free
+            .lock()
+            .map_err(|_| PiError::Message("The channel pool is poisoned".to_string()))?
+            .pop();
+        Ok(ChannelLease {
+            free: self.free.clone(),
+            keypair,
+            _permit: permit,
+        })
+    }
+
+    /// Waiting until every channel is given back and keeping them, `close` has to follow so the waiting ones give up
+    async fn drain(&self) -> Result<Vec<Keypair>, PiError> {
+        let permits = self
+            .available
+            .acquire_many(self.len() as u32)
+            .await
+            .map_err(|_| PiError::Message("The channel pool is closed".to_string()))?;
+        permits.forget();
+        let keypairs = self
+            .free
+            .lock()
+            .map_err(|_| PiError::Message("The channel pool is poisoned".to_string()))?
+            .clone();
+        Ok(keypairs)
+    }
+
+    /// Closing the pool so no more channel can be taken
+    fn close(&self) {
+        self.available.close();
+    }
+
+    /// True once the pool is retired, no channel can be taken from it anymore
+    pub fn is_closed(&self) -> bool {
+        self.available.is_closed()
+    }
+
+    /// A new pool of the given channels of this one, e.g. the ones still left after a `drain`
+    fn retain(&self, keypairs: Vec<Keypair>) -> Self {
+        let secret_seeds = keypairs
+            .iter()
+            .filter_map(|keypair| {
+                let index = self
+                    .public_keys
+                    .iter()
+                    .position(|public_key| *public_key == keypair.public_key())?;
+                Some(self.secret_seeds[index].clone())
+            })
+            .collect();
+        Self::from_keypairs(keypairs, secret_seeds)
+    }
+}
+
+impl ChannelLease {
+    pub fn keypair(&self) -> &Keypair {
+        // It's only taken in drop
+        self.keypair
+            .as_ref()
+            .expect("Channel lease without keypair")
+    }
+
+    pub fn public_key(&self) -> String {
+        self.keypair().public_key()
+    }
+}
+
+impl Drop for ChannelLease {
+    fn drop(&mut self) {
+        if let (Some(keypair), Ok(mut free)) = (self.keypair.take(), self.free.lock()) {
+            free.push(keypair);
+        }
+    }
+}
+
+impl PiNetwork {
+    /// Creating the accounts of the channels, funded from the app wallet with the starting balance each
+    ///
+    /// The starting balance has to cover the minimum balance of an account and the fees the channel will pay.
+    /// The channels whose account exists already are skipped, so after a failure it can be called again with the same pool.
+    /// Store the seeds of the pool before calling it, the Pi sent to the channels can only be recovered with them.
+    pub async fn create_channel_accounts(
+        &self,
+        pool: &ChannelPool,
+        starting_balance: PiAmount,
+    ) -> Result<Vec<String>, PiError> {
+        let minimum_balance = self.profile().minimum_balance();
+        if starting_balance < minimum_balance {
+            return Err(PiError::Message(format!(
+                "A channel account needs at least {} as starting balance",
+                minimum_balance
+            )));
+        }
+        let amount = to_amount(starting_balance)?;
+
+        let pi_horizon = self.horizon_client()?;
+        let mut missing = Vec::new();
+        for public_key in pool.public_keys() {
+            match pi_horizon
+                .load_account(public_key)
+                .await
+                .map_err(PiError::from)
+            {
+                Ok(_) => {}
+                Err(PiError::Horizon(err)) if err.status == 404 => missing.push(public_key),
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut txids = Vec::new();
+        for chunk in missing.chunks(MAX_OPERATIONS) {
+            let operations = chunk
+                .iter()
+                .map(|public_key| {
+                    let destination = Keypair::from_public_key(public_key)?;
+                    Ok(Operation::new_create_account()
+                        .with_destination(raw_public_key(&destination)?)
+                        .with_starting_balance(amount.clone())?
+                        .build()?)
+                })
+                .collect::<Result<Vec<_>, anyhow::Error>>()
+                .map_err(|e| PiError::Message(format!("Can't make the operation! {:?}", e)))?;
+            txids.push(self.submit_wallet_operations(operations, &[]).await?);
+        }
+        Ok(txids)
+    }
+
+    /// Sending the amount from the app wallet to every channel of the pool, so they can keep paying the fees
+    ///
+    /// The channels are paid by one transaction for every 100 channels. If one of them fails the rest isn't sent,
+    /// the channels already paid are reported, so only the `unfunded` ones are paid again with `fund_channels`.
+    pub async fn fund_channel_accounts(&self, amount: PiAmount) -> Result<ChannelFunding, PiError> {
+        let pool = self.channel_pool()?;
+        self.fund_channels(pool.public_keys(), amount).await
+    }
+
+    /// Sending the amount from the app wallet to the given channels of the pool, see at `fund_channel_accounts`
+    pub async fn fund_channels(
+        &self,
+        public_keys: &[String],
+        amount: PiAmount,
+    ) -> Result<ChannelFunding, PiError> {
+        let pool = self.channel_pool()?;
+        if let Some(unknown) = public_keys
+            .iter()
+            .find(|public_key| !pool.public_keys().contains(public_key))
+        {
+            return Err(PiError::Message(format!(
+                "{} is not a channel of the pool",
+                unknown
+            )));
+        }
+        let amount = to_amount(amount)?;
+
+        // Every operation is made before the first transaction is sent, so nothing is left half done by a bad one
+        let chunks = public_keys
+            .chunks(MAX_OPERATIONS)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|public_key| {
+                        let destination = Keypair::from_public_key(public_key)?;
+                        Ok(Operation::new_payment()
+                            .with_destination(raw_public_key(&destination)?)
+                            .with_amount(amount.clone())?
+                            .with_asset(Asset::new_native())
+                            .build()?)
+                    })
+                    .collect::<Result<Vec<_>, anyhow::Error>>()
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| PiError::Message(format!("Can't make the operation! {:?}", e)))?;
+
+        let mut funding = ChannelFunding::default();
+        for (index, operations) in chunks.into_iter().enumerate() {
+            let chunk = &public_keys[index * MAX_OPERATIONS..];
+            match self.submit_wallet_operations(operations, &[]).await {
+                Ok(txid) => {
+                    funding.txids.push(txid);
+                    funding
+                        .funded
+                        .extend(chunk.iter().take(MAX_OPERATIONS).cloned());
+                }
+                Err(err) => {
+                    funding.unfunded = chunk.to_vec();
+                    funding.error = Some(err);
+                    break;
+                }
+            }
+        }
+        Ok(funding)
+    }
+
+    /// Merging every channel account of the pool back into the app wallet and removing the pool
+    ///
+    /// Waits until the payments in flight give back their channels, the payments started meanwhile are made from the
+    /// app wallet. Every clone of the `PiNetwork` stops using the pool. If it fails the channels not merged yet stay in use.
+    pub async fn retire_channel_accounts(&self) -> Result<Vec<String>, PiError> {
+        let pool = self.channel_pool()?;
+        let keypairs = pool.drain().await?;
+        {
+            // Closed and removed at once, the payments waiting for a channel find the pool gone when they are woken
+            let mut channels = self.lock_channels()?;
+            pool.close();
+            if channels
+                .as_ref()
+                .is_some_and(|current| Arc::ptr_eq(current, &pool))
+            {
+                *channels = None;
+            }
+        }
+        let app_wallet: MuxedAccount = self.app_public_key()?.into();
+
+        let mut txids = Vec::new();
+        for (index, chunk) in keypairs.chunks(MAX_OPERATIONS).enumerate() {
+            let operations = chunk
+                .iter()
+                .map(|keypair| {
+                    Operation::new_account_merge()
+                        .with_source_account(SodiumKeyPair::from(keypair.clone()).public_key())
+                        .with_destination(app_wallet.clone())
+                        .build()
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| PiError::Message(format!("Can't make the operation! {:?}", e)))?;
+            let signers: Vec<&Keypair> = chunk.iter().collect();
+            match self.submit_wallet_operations(operations, &signers).await {
+                Ok(txid) => txids.push(txid),
+                Err(err) => {
+                    // The channels of the earlier chunks are gone, but it's still better to keep the rest
+                    let remaining = keypairs[index * MAX_OPERATIONS..].to_vec();
+                    self.lock_channels()?
+                        .get_or_insert_with(|| Arc::new(pool.retain(remaining)));
+                    return Err(err);
+                }
+            }
+        }
+        Ok(txids)
+    }
+
+    /// Using the pool for the A2U payments from now on, by every clone of the `PiNetwork`
+    pub fn set_channel_pool(&self, pool: ChannelPool) {
+        if let Ok(mut channels) = self.lock_channels() {
+            *channels = Some(Arc::new(pool));
+        }
+    }
+
+    pub fn channel_pool(&self) -> Result<Arc<ChannelPool>, PiError> {
+        self.channels
+            .read()
+            .map_err(|_| PiError::Message("The channel pool is poisoned".to_string()))?
+            .clone()
+            .ok_or_else(|| PiError::Message("No channel pool is set".to_string()))
+    }
+
+    /// Taking a channel for an A2U transaction, `None` if there is no pool
+    pub(crate) async fn acquire_channel(&self) -> Result<Option<ChannelLease>, PiError> {
+        loop {
+            let Ok(pool) = self.channel_pool() else {
+                return Ok(None);
+            };
+            match pool.acquire().await {
+                Ok(lease) => return Ok(Some(lease)),
+                // The pool was retired meanwhile, taking the one which replaced it, if there is one
+                Err(err) => match self.channel_pool() {
+                    Ok(current) if Arc::ptr_eq(&current, &pool) => return Err(err),
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    fn lock_channels(
+        &self,
+    ) -> Result<std::sync::RwLockWriteGuard<'_, Option<Arc<ChannelPool>>>, PiError> {
+        self.channels
+            .write()
+            .map_err(|_| PiError::Message("The channel pool is poisoned".to_string()))
+    }
+
+    /// Submitting a transaction of the app wallet with the operations, signed by the app wallet and the signers
+    ///
+    /// It's made with the time bounds of the A2U transactions, so if its submission gets no answer it's waited for
+    /// until it got into a ledger or expired, like in `submit_payment`. It's never submitted again.
+    async fn submit_wallet_operations(
+        &self,
+        operations: Vec<Operation>,
+        signers: &[&Keypair],
+    ) -> Result<String, PiError> {
+        let pi_horizon = self.horizon_client()?;
+        let source = self.signer().public_key();
+        let sequence = self.sequences.next(&pi_horizon, &source).await?;
+        let fee = self.fee_policy().strategy.fee(&pi_horizon).await?;
+        let fee = Stroops::new(fee * operations.len() as i64);
+
+        let mut builder = Transaction::builder(self.app_public_key()?, sequence, fee);
+        for operation in operations {
+            builder = builder.add_operation(operation);
+        }
+        if let Some(time_bounds) = self.time_bounds(&pi_horizon).await? {
+            builder = builder.with_time_bounds(time_bounds);
+        }
+        let mut transaction = builder
+            .into_transaction()
+            .map_err(|e| PiError::Message(format!("Can't build the transaction! {:?}", e)))?;
+
+        let network = Network::new(self.profile().passphrase.clone());
+        crate::signer::sign_transaction(self.signer(), &mut transaction, &network)
+            .await
+            .inspect_err(|_| self.sequences.invalidate(&source))?;
+        for signer in signers {
+            transaction
+                .sign(SodiumKeyPair::from((*signer).clone()).as_ref(), &network)
+                .map_err(|e| PiError::Message(format!("Can't sign the transaction! {:?}", e)))?;
+        }
+
+        let signed = self.encode_transaction(&transaction)?;
+        match pi_horizon
+            .submit_transaction_envelope(&signed.envelope_xdr)
+            .await
+            .map_err(PiError::from)
+        {
+            Ok(response) => Ok(response.id),
+            Err(err) if err.is_unknown_submission_outcome() => {
+                match self.submission_outcome(&pi_horizon, &signed, err).await? {
+                    Some(txid) => Ok(txid),
+                    // Its sequence number wasn't used
+                    None => {
+                        self.sequences.invalidate(&source);
+                        Err(PiError::Message(format!(
+                            "Transaction {} expired without getting into a ledger",
+                            signed.txid
+                        )))
+                    }
+                }
+            }
+            Err(err) => {
+                self.sequences.handle_error(&source, &err);
+                Err(err)
+            }
+        }
+    }
+}
+
+/// The transactions made by `PiNetwork::fund_channel_accounts`, one for every 100 channels
+///
+/// - txids: `Vec<String>` - the transactions which got into a ledger
+/// - funded: `Vec<String>` - the channel accounts paid by those transactions
+/// - unfunded: `Vec<String>` - the channel accounts which weren't paid, they can be paid with `PiNetwork::fund_channels`
+/// - error: `Option<PiError>` - why the unfunded channels weren't paid. Without a `transaction_validity` a transaction
+///   whose submission got no answer could still get in, then it's an unknown submission outcome
+///   (see `PiError::is_unknown_submission_outcome`), and the first 100 unfunded channels may be paid after all.
+
+#[derive(Debug, Default, Serialize)]
+pub struct ChannelFunding {
+    pub txids: Vec<String>,
+    pub funded: Vec<String>,
+    pub unfunded: Vec<String>,
+    pub error: Option<PiError>,
+}
+
+impl ChannelFunding {
+    /// True if every channel was paid
+    pub fn is_complete(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+fn to_amount(amount: PiAmount) -> Result<Amount, PiError> {
+    Amount::try_from(amount).map_err(|e| PiError::Message(format!("Invalid amount! {:?}", e)))
+}
+
+fn raw_public_key(keypair: &Keypair) -> Result<stellar_base::PublicKey, anyhow::Error> {
+    Ok(stellar_base::PublicKey::from_slice(
+        keypair.raw_public_key(),
+    )?)
+}
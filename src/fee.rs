@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+
+use crate::stellar_sdk::lib::Server;
+use crate::stellar_sdk::types::{FeeDistribution, FeeStats};
+use crate::types::PiError;
+
+/// How the fee of the transactions is chosen, every fee is in stroops per operation
+///
+/// The network only charges as much as it needs, so the fee is the most the app is willing to pay.
+/// While the ledgers are full (surge pricing) the transactions bidding less than the others are rejected
+/// with `tx_insufficient_fee`, and the base fee of the last ledger is often too little for that.
+///
+/// - Fixed(i64) - always the same fee
+/// - LastLedger - the base fee of the last ledger, the default
+/// - Percentile(u8) - the percentile of the fees charged in the last ledgers, e.g. 90 outbids 90% of the recent transactions
+/// - Capped { max } - the highest fee charged in the last ledgers, but never more than `max`
+///
+/// Apart from `Fixed` and the cap of `Capped`, the fee is never less than the base fee of the last ledger.
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeeStrategy {
+    Fixed(i64),
+    #[default]
+    LastLedger,
+    Percentile(u8),
+    Capped {
+        max: i64,
+    },
+}
+
+/// Fee policy of the transactions, part of `ReqwestClientOptions`
+///
+/// - strategy: FeeStrategy - how the fee of a new transaction is chosen, see at `FeeStrategy`
+/// - max_retry_fee: `Option<i64>` - when a transaction is rejected with `tx_insufficient_fee` it's signed again with
+///   a doubled fee, until the fee reaches this many stroops per operation, `None` means it's not signed again
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeePolicy {
+    pub strategy: FeeStrategy,
+    pub max_retry_fee: Option<i64>,
+}
+
+impl FeeStrategy {
+    /// The fee per operation from the fee stats of Horizon
+    pub fn fee_from_stats(&self, fee_stats: &FeeStats) -> Result<i64, PiError> {
+        let base_fee = parse_fee(&fee_stats.last_ledger_base_fee)?;
+        let fee = match self {
+            FeeStrategy::Fixed(fee) => return Ok(*fee),
+            FeeStrategy::LastLedger => base_fee,
+            FeeStrategy::Percentile(percentile) => {
+                percentile_fee(&fee_stats.fee_charged, *percentile)?
+            }
+            FeeStrategy::Capped { max } => {
+                return Ok(parse_fee(&fee_stats.fee_charged.max)?
+                    .max(base_fee)
+                    .min(*max))
+            }
+        };
+        Ok(fee.max(base_fee))
+    }
+
+    /// The fee per operation, the fee stats are only loaded from Horizon if the strategy needs them
+    pub async fn fee(&self, pi_horizon: &Server) -> Result<i64, PiError> {
+        match self {
+            FeeStrategy::Fixed(fee) => Ok(*fee),
+            _ => self.fee_from_stats(&pi_horizon.fee_stats().await?),
+        }
+    }
+}
+
+impl FeePolicy {
+    /// The fee per operation to sign the transaction again with after `tx_insufficient_fee`, `None` if it can't be raised
+    pub fn raised_fee(&self, fee: i64) -> Option<i64> {
+        let max = self.max_retry_fee?;
+        if fee >= max {
+            return None;
+        }
+        Some(fee.saturating_mul(2).min(max))
+    }
+}
+
+/// The smallest percentile of the distribution that is at least the asked one, Horizon only gives some of them
+fn percentile_fee(distribution: &FeeDistribution, percentile: u8) -> Result<i64, PiError> {
+    let fee = match percentile {
+        0 => &distribution.min,
+        1..=10 => &distribution.p10,
+        11..=20 => &distribution.p20,
+        21..=30 => &distribution.p30,
+        31..=40 => &distribution.p40,
+        41..=50 => &distribution.p50,
+        51..=60 => &distribution.p60,
+        61..=70 => &distribution.p70,
+        71..=80 => &distribution.p80,
+        81..=90 => &distribution.p90,
+        91..=95 => &distribution.p95,
+        96..=99 => &distribution.p99,
+        _ => &distribution.max,
+    };
+    parse_fee(fee)
+}
+
+fn parse_fee(fee: &str) -> Result<i64, PiError> {
+    fee.parse()
+        .map_err(|_| PiError::Message(format!("Invalid fee in the fee stats: {:?}", fee)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fee_stats(base_fee: &str) -> FeeStats {
+        let distribution = |max: &str| {
+            json!({
+                "max": max, "min": "100", "mode": "100",
+                "p10": "100", "p20": "100", "p30": "100", "p40": "100", "p50": "150",
+                "p60": "200", "p70": "250", "p80": "300", "p90": "400", "p95": "800", "p99": "1500",
+            })
+        };
+        serde_json::from_value(json!({
+            "last_ledger": "1000",
+            "last_ledger_base_fee": base_fee,
+            "ledger_capacity_usage": "0.97",
+            "fee_charged": distribution("2000"),
+            "max_fee": distribution("100000"),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_fee_from_stats() {
+        let stats = fee_stats("100");
+        assert_eq!(FeeStrategy::Fixed(50).fee_from_stats(&stats).unwrap(), 50);
+        assert_eq!(FeeStrategy::LastLedger.fee_from_stats(&stats).unwrap(), 100);
+        assert_eq!(
+            FeeStrategy::Percentile(50).fee_from_stats(&stats).unwrap(),
+            150
+        );
+        assert_eq!(
+            FeeStrategy::Percentile(85).fee_from_stats(&stats).unwrap(),
+            400
+        );
+        assert_eq!(
+            FeeStrategy::Percentile(100).fee_from_stats(&stats).unwrap(),
+            2000
+        );
+        assert_eq!(
+            FeeStrategy::Capped { max: 1000 }
+                .fee_from_stats(&stats)
+                .unwrap(),
+            1000
+        );
+        assert_eq!(
+            FeeStrategy::Capped { max: 5000 }
+                .fee_from_stats(&stats)
+                .unwrap(),
+            2000
+        );
+
+        // Never less than the base fee of the last ledger
+        let stats = fee_stats("500");
+        assert_eq!(
+            FeeStrategy::Percentile(10).fee_from_stats(&stats).unwrap(),
+            500
+        );
+        assert!(FeeStrategy::LastLedger
+            .fee_from_stats(&fee_stats("none"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_raised_fee() {
+        assert_eq!(FeePolicy::default().raised_fee(100), None);
+
+        let policy = FeePolicy {
+            max_retry_fee: Some(300),
+            ..FeePolicy::default()
+        };
+        assert_eq!(policy.raised_fee(100), Some(200));
+        assert_eq!(policy.raised_fee(200), Some(300));
+        assert_eq!(policy.raised_fee(300), None);
+    }
+}
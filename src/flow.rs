@@ -0,0 +1,391 @@
+//! Resumable A2U (App-to-User) payment flow
+//!
+//! The `PaymentFlow` drives a payment through create → sign → submit → confirm → complete and saves the state
+//! to a `PaymentStore` after every transition. If the process crashes at any point, `resume` continues
+//! the payment from its last saved state after checking the Pi API and Horizon.
+//!
+//! The signed transaction envelope is saved before it's submitted, and only that envelope is ever resubmitted,
+//! so a payment can't be paid twice: the same envelope can only be applied once by the blockchain.
+//! A new transaction is only built when the previous one can never be applied anymore.
+//!
+//! ```ignore
+//! use pi_rust::flow::{PaymentFlow, PaymentFlowState};
+//! use pi_rust::store::MemoryPaymentStore;
+//!
+//! let flow = PaymentFlow::new(pi, MemoryPaymentStore::default());
+//! let record = flow.start(payment_data).await?;
+//! assert_eq!(record.state, PaymentFlowState::Completed);
+//!
+//! // After a restart
+//! for result in flow.resume_unfinished().await? {
+//!     println!("{:?}", result);
+//! }
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::channels::ChannelLease;
+use crate::stellar_sdk::lib::Server;
+use crate::store::{PaymentRecord, PaymentStore};
+use crate::types::{
+    PaymentArgs, PaymentDTO, PiApiErrorKind, PiError, SignedTransaction, TransactionData,
+};
+use crate::PiNetwork;
+
+/// States of the A2U payment flow
+///
+/// - Created: the payment is created on the Pi API, no transaction is signed yet
+/// - Signed: the transaction is built and signed, its hash and envelope are saved, but it's not submitted yet
+/// - Submitted: the transaction was sent to Horizon, but the outcome is not known yet
+/// - Confirmed: the transaction is successful on the blockchain
+/// - Completed: the payment is completed on the Pi API, final state
+/// - Cancelled: the payment is cancelled by the developer, the user or Pi Network, final state
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PaymentFlowState {
+    Created,
+    Signed,
+    Submitted,
+    Confirmed,
+    Completed,
+    Cancelled,
+}
+
+impl PaymentFlowState {
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            PaymentFlowState::Completed | PaymentFlowState::Cancelled
+        )
+    }
+}
+
+/// Driver of the A2U payment flow, see the module documentation
+pub struct PaymentFlow<S: PaymentStore> {
+    pi: PiNetwork,
+    store: S,
+}
+
+impl<S: PaymentStore> PaymentFlow<S> {
+    pub fn new(pi: PiNetwork, store: S) -> Self {
+        PaymentFlow { pi, store }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Creating a new A2U payment and driving it until it's completed
+    /// If any step fails the error is returned, and the flow can be continued with `resume`
+    /// The payment is stored as soon as it's created, so it can be found in the store by its user even if the error
+    /// came before its ID was returned.
+    pub async fn start(&self, payment_data: PaymentArgs) -> Result<PaymentRecord, PiError> {
+        let payment_id = self.pi.create_payment(payment_data.clone()).await?;
+        // Nothing else may fail before the payment is stored, the store must know every payment created on the Pi API
+        self.store
+            .insert(&PaymentRecord::from_args(payment_id.clone(), &payment_data))?;
+
+        let payment = self.pi.get_payment(payment_id).await?;
+        let record = PaymentRecord::from_payment(&payment);
+        self.store.update(&record)?;
+        self.drive_payment(record, &payment).await
+    }
+
+    /// Continuing the flow of a payment from its last saved state
+    /// If the payment is unknown to the store, it's stored and handled as a freshly created payment
+    pub async fn resume(&self, payment_id: &str) -> Result<PaymentRecord, PiError> {
+        match self.store.get(payment_id)? {
+            Some(record) => self.drive(record).await,
+            None => {
+                let payment = self.pi.get_payment(payment_id.to_string()).await?;
+                let record = PaymentRecord::from_payment(&payment);
+                self.store.insert(&record)?;
+                self.drive_payment(record, &payment).await
+            }
+        }
+    }
+
+    /// Resuming every unfinished flow of the store, one result per flow
+    pub async fn resume_unfinished(&self) -> Result<Vec<Result<PaymentRecord, PiError>>, PiError> {
+        let mut results = Vec::new();
+        for record in self.store.unfinished()? {
+            results.push(self.drive(record).await);
+        }
+        Ok(results)
+    }
+
+    /// Cancelling a payment which has no signed transaction yet
+    pub async fn cancel(&self, payment_id: &str) -> Result<PaymentRecord, PiError> {
+        let mut record = match self.store.get(payment_id)? {
+            Some(record) => record,
+            None => {
+                let payment = self.pi.get_payment(payment_id.to_string()).await?;
+                let record = PaymentRecord::from_payment(&payment);
+                self.store.insert(&record)?;
+                record
+            }
+        };
+        if record.state != PaymentFlowState::Created {
+            return Err(PiError::Message(format!(
+                "Payment {} can't be cancelled in the {:?} state",
+                payment_id, record.state
+            )));
+        }
+
+        self.pi.cancel_payment(payment_id.to_string()).await?;
+        record.state = PaymentFlowState::Cancelled;
+        self.store.update(&record)?;
+        Ok(record)
+    }
+
+    /// Making steps until the flow reaches a final state, saving every transition
+    async fn drive(&self, record: PaymentRecord) -> Result<PaymentRecord, PiError> {
+        if record.state.is_final() {
+            return Ok(record);
+        }
+
+        let payment = self.pi.get_payment(record.payment_id.clone()).await?;
+        self.drive_payment(record, &payment).await
+    }
+
+    async fn drive_payment(
+        &self,
+        mut record: PaymentRecord,
+        payment: &PaymentDTO,
+    ) -> Result<PaymentRecord, PiError> {
+        self.pi.check_network(payment)?;
+        let pi_horizon = self.pi.horizon_client()?;
+        let mut signing = Signing::default();
+
+        while !record.state.is_final() {
+            record = self
+                .step(record, payment, &pi_horizon, &mut signing)
+                .await?;
+            self.store.update(&record)?;
+        }
+        Ok(record)
+    }
+
+    async fn step(
+        &self,
+        mut record: PaymentRecord,
+        payment: &PaymentDTO,
+        pi_horizon: &Server,
+        signing: &mut Signing,
+    ) -> Result<PaymentRecord, PiError> {
+        match record.state {
+            PaymentFlowState::Created => {
+                if payment.status.developer_completed {
+                    record.state = PaymentFlowState::Completed;
+                } else if payment.status.cancelled || payment.status.user_cancelled {
+                    record.state = PaymentFlowState::Cancelled;
+                } else if let Some(transaction) = &payment.transaction {
+                    // The Pi API already knows a transaction, checking that instead of making a new one
+                    record.txid = Some(transaction.txid.clone());
+                    record.state = PaymentFlowState::Submitted;
+                } else if let Some(transaction) = self
+                    .pi
+                    .find_transaction_by_memo(pi_horizon, &record.payment_id)
+                    .await?
+                {
+                    // Paid by an earlier run which didn't save its state
+                    record.txid = Some(transaction.hash);
+                    record.state = PaymentFlowState::Confirmed;
+                } else {
+                    self.sign(&mut record, payment, pi_horizon, signing).await?;
+                }
+            }
+            PaymentFlowState::Signed | PaymentFlowState::Submitted => {
+                self.submit(&mut record, pi_horizon, signing).await?;
+            }
+            PaymentFlowState::Confirmed => {
+                let txid = record.txid.clone().ok_or_else(|| {
+                    PiError::Message("Confirmed payment flow without txid".to_string())
+                })?;
+                match self
+                    .pi
+                    .complete_payment(record.payment_id.clone(), txid)
+                    .await
+                {
+                    Ok(_) => record.state = PaymentFlowState::Completed,
+                    Err(err) if err.api_error_kind() == Some(PiApiErrorKind::AlreadyCompleted) => {
+                        record.state = PaymentFlowState::Completed
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            PaymentFlowState::Completed | PaymentFlowState::Cancelled => {}
+        }
+        Ok(record)
+    }
+
+    /// Building and signing the transaction, it's saved in the Signed state before it's submitted
+    async fn sign(
+        &self,
+        record: &mut PaymentRecord,
+        payment: &PaymentDTO,
+        pi_horizon: &Server,
+        signing: &mut Signing,
+    ) -> Result<(), PiError> {
+        let transaction_data = TransactionData {
+            amount: payment.amount,
+            payment_identifier: payment.identifier.clone(),
+            from_address: payment.from_address.clone(),
+            to_address: payment.to_address.clone(),
+        };
+        if signing.channel.is_none() {
+            signing.channel = self.pi.acquire_channel().await?;
+        }
+        let transaction = self
+            .pi
+            .build_a2u_transaction(
+                pi_horizon.clone(),
+                transaction_data,
+                signing.channel.as_ref().map(ChannelLease::keypair),
+                signing.min_fee,
+            )
+            .await?;
+        let signed = self.pi.encode_transaction(&transaction)?;
+
+        record.txid = Some(signed.txid);
+        record.envelope_xdr = Some(signed.envelope_xdr);
+        record.state = PaymentFlowState::Signed;
+        Ok(())
+    }
+
+    /// Checking the signed transaction on Horizon and submitting its envelope if it's not there yet
+    async fn submit(
+        &self,
+        record: &mut PaymentRecord,
+        pi_horizon: &Server,
+        signing: &mut Signing,
+    ) -> Result<(), PiError> {
+        let txid = record
+            .txid
+            .clone()
+            .ok_or_else(|| PiError::Message("Signed payment flow without txid".to_string()))?;
+
+        match PiNetwork::find_transaction(pi_horizon, &txid).await? {
+            Some(transaction) if transaction.successful => {
+                record.state = PaymentFlowState::Confirmed;
+                return Ok(());
+            }
+            // A failed transaction didn't pay anything, a new one can be made if it was ours
+            Some(_) if record.envelope_xdr.is_some() => {
+                self.restart(record);
+                return Ok(());
+            }
+            Some(_) => {
+                return Err(PiError::Message(format!(
+                    "Transaction {} linked to payment {} failed on the blockchain",
+                    txid, record.payment_id
+                )))
+            }
+            None => {}
+        }
+
+        let envelope_xdr = match &record.envelope_xdr {
+            Some(envelope_xdr) => envelope_xdr.clone(),
+            // The transaction came from the Pi API and it's not on the blockchain, nothing to resubmit
+            None => {
+                return Err(PiError::Message(format!(
+                    "Transaction {} of payment {} is not found on the blockchain",
+                    txid, record.payment_id
+                )))
+            }
+        };
+
+        if record.state == PaymentFlowState::Signed {
+            record.state = PaymentFlowState::Submitted;
+            self.store.update(record)?;
+        }
+
+        match pi_horizon.submit_transaction_envelope(&envelope_xdr).await {
+            Ok(response) if response.successful => {
+                record.state = PaymentFlowState::Confirmed;
+                Ok(())
+            }
+            Ok(_) => {
+                self.restart(record);
+                Ok(())
+            }
+            Err(err) => {
+                let err = PiError::from(err);
+                // Keeping the cached sequence numbers right, the source is the app wallet or a channel account
+                let signed =
+                    SignedTransaction::from_envelope(&envelope_xdr, &self.pi.profile().passphrase)
+                        .ok();
+                if let Some(signed) = &signed {
+                    self.pi
+                        .sequence_manager()
+                        .handle_error(&signed.source_account, &err);
+                }
+                match err {
+                    // The sequence number is used or the envelope expired, so it can never be applied anymore
+                    // If it was applied by an earlier submission it's found by the hash or by the memo
+                    err if matches!(
+                        err.transaction_result_code(),
+                        Some("tx_bad_seq" | "tx_too_late")
+                    ) =>
+                    {
+                        if let Some(transaction) = PiNetwork::find_transaction(pi_horizon, &txid)
+                            .await?
+                            .or(self
+                                .pi
+                                .find_transaction_by_memo(pi_horizon, &record.payment_id)
+                                .await?)
+                        {
+                            record.txid = Some(transaction.hash);
+                            record.state = PaymentFlowState::Confirmed;
+                        } else {
+                            self.restart(record);
+                        }
+                        Ok(())
+                    }
+                    // Not applied either, it's signed again with a higher fee if the fee policy allows it
+                    err if err.transaction_result_code() == Some("tx_insufficient_fee") => {
+                        let fee = signed
+                            .and_then(|signed| self.pi.fee_policy().raised_fee(signed.base_fee));
+                        match fee {
+                            Some(fee) => {
+                                signing.min_fee = Some(fee);
+                                self.restart(record);
+                                Ok(())
+                            }
+                            None => Err(err),
+                        }
+                    }
+                    // Anything else leaves the flow in Submitted, the outcome is checked again on resume
+                    err => Err(err),
+                }
+            }
+        }
+    }
+
+    fn restart(&self, record: &mut PaymentRecord) {
+        record.txid = None;
+        record.envelope_xdr = None;
+        record.state = PaymentFlowState::Created;
+    }
+}
+
+/// What the next transaction of the flow is signed with
+/// The channel account is kept until the flow is over, the fee is raised after `tx_insufficient_fee`
+#[derive(Default)]
+struct Signing {
+    channel: Option<ChannelLease>,
+    min_fee: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_final_states() {
+        assert!(PaymentFlowState::Completed.is_final());
+        assert!(PaymentFlowState::Cancelled.is_final());
+        assert!(!PaymentFlowState::Signed.is_final());
+        assert!(!PaymentFlowState::Submitted.is_final());
+    }
+}
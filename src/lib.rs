@@ -11,10 +11,26 @@
 //! // If you need mainnet do this
 //! // use pi_rust::{types::NetworkPassphrase, PiNetwork};
 //! // let pi = PiNetwork::new(pi_api_key, wallet_private_seed, Some(NetworkPassphrase::PiNetwork), None).unwrap();
-//! // ReqwestClientOptions can be set also like Some(ReqwestClientOptions{base_url:"String".to_string()})
+//! // ReqwestClientOptions can be set also like Some(ReqwestClientOptions{base_url:"String".to_string(), ..Default::default()})
+//! // Transient failures (5xx, 429, connection errors) are retried with backoff, see `retry_policy: RetryPolicy` in the options
+//! // Timeouts, proxy, root certificates, user agent and pool size are also in the options, or your own reqwest::Client can be given in `client`
 //! let pi = PiNetwork::new(pi_api_key, wallet_private_seed, None, None).unwrap();
 //! ```
 //!
+//! The passphrase, Horizon URL, Pi API URL, native asset code, base reserve and payment limits of the network come from a `NetworkProfile`.
+//! `PiNetwork::new` uses the `NetworkProfile::mainnet()` or `NetworkProfile::testnet()` preset, any other network, e.g. a local stand-in, can be given with `with_profile`.
+//! The payments whose `network` isn't the one of the profile are rejected instead of being signed for the wrong network.
+//!
+//! ```ignore
+//! use pi_rust::types::NetworkProfile;
+//!
+//! let profile = NetworkProfile::testnet()
+//!     .with_passphrase("Local Stand-in")
+//!     .with_horizon_url("http://localhost:8000")
+//!     .with_pi_api_url("http://localhost:8001");
+//! let pi = PiNetwork::with_profile(pi_api_key, wallet_private_seed, profile, None).unwrap();
+//! ```
+//!
 //! 2. Create an A2U payment
 //!
 //! Make sure to store your payment data in your database. Here's an example of how you could keep track of the data.
@@ -28,7 +44,7 @@
 //! //  Get the user_uid from the Frontend
 //! let user_uid = "user_uid_of_your_app".to_string();
 //! let payment_data = PaymentArgs {
-//!   amount: 1.0,
+//!   amount: "1.0".parse().unwrap(),
 //!   memo: "Refund for apple pie".to_string(), // this is just an example
 //!   metadata: json!({productId: "apple-pie-1"}),
 //!   uid: user_uid
@@ -65,6 +81,72 @@
 //! let completed_payment = pi.complete_payment(payment_id, txid).await;
 //! ```
 //!
+//! ### Keeping track of the payments with a `PaymentStore`
+//!
+//! Instead of maintaining the table by hand, the `PaymentFlow` can do every step above and write the payment to a `PaymentStore` after each of them.
+//! The crate ships an in-memory store, an append-only JSON-lines file store and a SQLite store behind the `sqlite` cargo feature,
+//! or you can implement the `PaymentStore` trait over your own database.
+//!
+//! ```ignore
+//! use pi_rust::{flow::PaymentFlow, store::{FilePaymentStore, PaymentStore}};
+//!
+//! let flow = PaymentFlow::new(pi, FilePaymentStore::open("payments.jsonl")?);
+//! let record = flow.start(payment_data).await?;
+//!
+//! // Later, e.g. when the user asks for their payments
+//! let payments = flow.store().find_by_uid("user_uid_of_your_app")?;
+//! let apple_pies = flow.store().find_by_metadata("productId", json!("apple-pie-1"))?;
+//! ```
+//!
+//! ### Sending many payments at once with channel accounts
+//!
+//! Every transaction of the app wallet uses the next sequence number of the wallet, so its payments can only be submitted one by one.
+//! With a pool of channel accounts each payment is submitted by a free channel: the channel pays the fee and gives the sequence number,
+//! while the Pi is still sent from the app wallet. The pool hands out every channel to one payment at a time, so `submit_payment` and `PaymentFlow` can run concurrently.
+//!
+//! ```ignore
+//! // Once: creating 5 channels with 2 Pi each from the app wallet, the seeds are kept safe before the accounts are created
+//! let pool = ChannelPool::generate(5)?;
+//! save_seeds(pool.secret_seeds());
+//! pi.create_channel_accounts(&pool, "2".parse()?).await?;
+//!
+//! // At startup
+//! pi.set_channel_pool(ChannelPool::new(load_seeds())?);
+//!
+//! // When the channels are running out of Pi for the fees, the ones paid before a failure aren't paid again
+//! let funding = pi.fund_channel_accounts("1".parse()?).await?;
+//! if !funding.is_complete() {
+//!     pi.fund_channels(&funding.unfunded, "1".parse()?).await?;
+//! }
+//!
+//! // Merging the channels back into the app wallet when they are not needed anymore
+//! pi.retire_channel_accounts().await?;
+//! ```
+//!
+//! ### Keeping the wallet seed in a separate process
+//!
+//! `PiNetwork::new` takes the private seed of the app wallet, or any `TransactionSigner` instead. The `PiNetwork` only asks the signer
+//! for the public key of the wallet and for the signatures of the transaction hashes, so the seed can be held by a separate signing process.
+//! `RemoteSigner` asks it over HTTP or a local Unix socket with a JSON request `{"public_key", "network_passphrase", "transaction_hash"}`,
+//! the hash in hex, and expects `{"signature"}` (hex) or `{"error"}` back. The signature is verified before it's used.
+//!
+//! ```ignore
+//! use pi_rust::signer::RemoteSigner;
+//!
+//! let signer = RemoteSigner::unix("/run/pi-signer.sock", &app_wallet_address)?;
+//! let pi = PiNetwork::new(pi_api_key, signer, None, None)?;
+//! ```
+//!
+//! ### Synchronous applications
+//!
+//! With the `blocking` cargo feature `blocking::PiNetwork` has every method of `PiNetwork` without `async`, like `reqwest::blocking`.
+//! It runs the same code on a runtime of its own, so batch jobs and CLI tools don't need to start one. It must not be used inside an async runtime.
+//!
+//! ```ignore
+//! let pi = pi_rust::blocking::PiNetwork::new(api_key, wallet_private_seed, None, None)?;
+//! let payment: PaymentDTO = pi.get_payment(payment_id)?;
+//! ```
+//!
 //! ## Overall flow for A2U (App-to-User) payment
 //!
 //! To create an A2U payment using the Pi Rust SDK, here's an overall flow you need to follow:
@@ -99,23 +181,71 @@
 //! You need to provide 4 different data and pass them as a single object to this method.
 //! ```ignore
 //! #[derive(Debug, Clone, Serialize, Deserialize)]
-//! pub struct PaymentArgs {
-//!     pub amount: f64, // the amount of Pi you're paying to your user, always decimal so if 1 Pi then 1.0 should be entered
+//! pub struct PaymentArgs<M = Value> {
+//!     pub amount: PiAmount, // the amount of Pi you're paying to your user, stored exactly in stroops, for example "1.5".parse().unwrap()
 //!     pub memo: String, // a short memo that describes what the payment is about
-//!     pub metadata: Value, // an arbitrary object that you can attach to this payment. This is for your own use. You should use this object as a way to link this payment with your internal business logic.
+//!     pub metadata: M, // an arbitrary object that you can attach to this payment. This is for your own use. You should use this object as a way to link this payment with your internal business logic. serde_json::Value or your own struct
 //!     pub uid: String, // a user uid of your app. You should have access to this value if a user has authenticated on your app.
 //! }
 //! ```
 //!
+//! `pi.payment_args()` checks the arguments against the `PaymentLimits` of the network profile before anything is sent, `create_payment` checks them too.
+//! The amount must be positive and at least the `min_amount` of the limits, the network presets leave it at one stroop so the app sets its own minimum with `NetworkProfile::with_payment_limits`, the memo can be at most 28 bytes, the uid can't be empty and the metadata must be a JSON object.
+//! The error is a `PaymentArgsError`, its `field()` tells which argument is wrong:
+//!
+//! ```ignore
+//! let payment_data = pi
+//!     .payment_args()
+//!     .amount("1.5".parse().unwrap())
+//!     .memo("Refund for apple pie")
+//!     .metadata(json!({"productId": "apple-pie-1"}))
+//!     .uid(user_uid)
+//!     .build()?;
+//! ```
+//!
 //! - Return value: `a payment identifier (payment_id: String)`
 //!
 //! ### `submit_payment`
 //!
 //! This method creates a payment transaction and submits it to the Pi Blockchain.
+//! The sequence number of the wallet is cached by the `SequenceManager` (`pi.sequence_manager()`), so the wallet is only loaded from Horizon for the first transaction.
+//! If the transaction is rejected with `tx_bad_seq`, because the wallet was used somewhere else too, the sequence number is loaded again and the transaction is built again.
+//!
+//! The fee is chosen by the `fee_policy` of `ReqwestClientOptions`, the base fee of the last ledger by default.
+//! During surge pricing a percentile of the recent fees gets the payment in, and the transaction can be signed again with a doubled fee after `tx_insufficient_fee`:
+//!
+//! ```ignore
+//! let options = ReqwestClientOptions {
+//!     fee_policy: FeePolicy {
+//!         strategy: FeeStrategy::Percentile(90),
+//!         max_retry_fee: Some(10_000), // stroops per operation
+//!     },
+//!     ..ReqwestClientOptions::default()
+//! };
+//! ```
+//!
+//! The hash of the transaction is computed locally before it is submitted (`SignedTransaction`).
+//! If Horizon gives no clear answer, e.g. the request timed out or it responded with 504, the method waits for the transaction by its hash
+//! (`Server::wait_for_transaction`) until its max time. If it got into a ledger its txid is returned, if it expired without getting in
+//! a new transaction is built, so a network failure never pays twice.
+//!
+//! Every Horizon call is async, made with `reqwest`, so waiting for Horizon never blocks a thread of the runtime.
+//! The bundled `stellar_sdk::Server` is async too, the blocking one made with `ureq` is `stellar_sdk::blocking::Server` behind the `ureq` cargo feature.
 //!
 //! - Required parameter: `payment_id`
 //! - Return value: `a transaction identifier (txid: String)`
 //!
+//! ### `resubmit_payment`
+//!
+//! Every A2U transaction is only valid for the `transaction_validity` of `ReqwestClientOptions` (3 minutes by default), by the time of Horizon.
+//! If a payment was left without knowing whether its transaction got to the blockchain, e.g. the app stopped during the submission,
+//! this method waits until the transaction expired, checks that it wasn't applied, and only then builds a new one.
+//! If the earlier transaction was applied after all, its txid is returned, so the payment is never paid twice.
+//! `PaymentFlow` does the same on its own with the envelopes it stored.
+//!
+//! - Required parameter: `payment_id, envelope_xdr: &str` (the signed envelope of the earlier transaction)
+//! - Return value: `a transaction identifier (txid: String)`
+//!
 //! ### `complete_payment`
 //!
 //! This method completes the payment in the Pi server.
@@ -127,20 +257,20 @@
 //!
 //! ```ignore
 //! #[derive(Debug, Clone, Serialize, Deserialize)]
-//! pub struct PaymentDTO {
+//! pub struct PaymentDTO<M = Value> {
 //!   // Payment data:
 //!     pub identifier: String, // payment identifier
 //!     pub user_uid: String, // user's app-specific ID
-//!     pub amount: f64, // payment amount
+//!     pub amount: PiAmount, // payment amount
 //!     pub memo: String, // a String provided by the developer, shown to the user
-//!     pub metadata: Value, // an object provided by the developer for their own usage
+//!     pub metadata: M, // an object provided by the developer for their own usage, read it as your own struct with get_payment::<M>
 //!     pub from_address: String, // sender address of the blockchain transaction
 //!     pub to_address: String, // recipient address of the blockchain transaction
 //!     pub direction: Direction, // direction of the payment ("user_to_app" | "app_to_user")
 //!     pub status: PaymentDTOStatus, // Status flags representing the current state of this payment
 //!     pub transaction: Option<PaymentDTOTransaction>, // Blockchain transaction data. This is None if no transaction has been made yet
-//!     pub created_at: String, // payment's creation timestamp
-//!     pub network: NetworkPassphrase, // a network of the payment ("Pi Network" | "Pi Testnet")
+//!     pub created_at: DateTime<Utc>, // payment's creation timestamp
+//!     pub network: NetworkPassphrase, // a network of the payment ("Pi Network" | "Pi Testnet" | the name of a custom network)
 //! }
 //!
 //! #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -160,12 +290,74 @@
 //! }
 //! ```
 //!
+//! `payment.state()` combines the flags and the transaction into a `PaymentState`: `AwaitingApproval`, `Approved`, `TransactionSubmitted`,
+//! `TransactionVerified`, `Completed`, `CancelledByDeveloper` or `CancelledByUser`. Flags which contradict each other, e.g. completed without
+//! a transaction, are returned as an `InvalidPaymentState` error. `payment.age()` and `payment.is_older_than(max_age)` help to find the stale payments:
+//!
+//! ```ignore
+//! let stale = pi
+//!     .get_incomplete_server_payments()
+//!     .await?
+//!     .into_iter()
+//!     .filter(|payment| payment.state().is_ok_and(|state| !state.is_final()))
+//!     .filter(|payment| payment.is_older_than(Duration::from_secs(3600)));
+//! ```
+//!
 //! ### `get_payment`
 //!
 //! This method returns a payment object if it exists.
+//! The metadata is deserialized to the type you ask for, `serde_json::Value` or your own struct given to `create_payment`.
+//! If the stored metadata doesn't fit the type, `PiError::MetadataMismatch` is returned.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct OrderRef {
+//!     order_id: u64,
+//! }
+//!
+//! let payment = pi.get_payment::<OrderRef>(payment_id).await?;
+//! println!("{}", payment.metadata.order_id);
+//! ```
 //!
 //! - Required parameter: `payment_id`
-//! - Return value: `a payment object (payment: PaymentDTO)`
+//! - Return value: `a payment object (payment: PaymentDTO<M>)`
+//!
+//! ### `authenticate_user`
+//!
+//! This method verifies the user's access token, obtained on the frontend with `Pi.authenticate`, with the Pi API.
+//!
+//! - Required parameter: `access_token`
+//! - Return value: `a user object (user: UserDTO)`
+//!
+//! If the access token is expired or invalid the method returns `PiError::InvalidAccessToken`.
+//!
+//! ```ignore
+//! #[derive(Debug, Clone, Serialize, Deserialize)]
+//! pub struct UserDTO {
+//!     pub uid: String, // user's app-specific ID
+//!     pub username: Option<String>, // only present if the "username" scope was requested
+//!     pub credentials: UserCredentials, // scopes granted by the user and the validity of the token
+//! }
+//! ```
+//!
+//! ### `verify_u2a_payment`
+//!
+//! This method verifies a U2A (User-to-App) payment on the Pi Blockchain, before you complete it with the txid received from the frontend.
+//! It checks that the transaction is successful, its memo is the payment ID, and it pays the payment's amount in Pi from the user's wallet to your app wallet on the configured network.
+//!
+//! - Required parameter: `payment_id, txid`
+//! - Return value: `a verification report (report: U2APaymentVerification)`
+//!
+//! ```ignore
+//! let report = pi.verify_u2a_payment(payment_id.clone(), txid.clone()).await?;
+//! if report.is_valid() {
+//!     pi.complete_payment(payment_id, txid).await?;
+//! } else {
+//!     for check in report.failed_checks() {
+//!         println!("{:?}: expected {}, got {}", check.kind, check.expected, check.actual);
+//!     }
+//! }
+//! ```
 //!
 //! ### `cancel_payment`
 //!
@@ -191,18 +383,115 @@
 //!
 //! If you do not know what this payment maps to in your business logic, you may use its `metadata` property to retrieve which business logic item it relates to. Remember that `metadata` is a required argument when creating a payment, and should be used as a way to link this payment to an item of your business logic.
 //!
+//! ### `resolve_incomplete_payments`
+//!
+//! This method resolves every payment returned by `get_incomplete_server_payments` automatically, so it's a good idea to call it at startup.
+//! Each payment is classified by its linked transaction and status, and Horizon is checked for a transaction with the payment ID as memo.
+//! If a successful transaction is on the blockchain the payment is completed with it, otherwise the policy decides whether it's submitted and completed, or cancelled.
+//!
+//! - Required parameter: `policy: IncompletePaymentPolicy` (`Submit` or `Cancel`)
+//! - Return value: `a report for every payment (resolutions: Vec<IncompletePaymentResolution>)`
+//!
+//! ```ignore
+//! use pi_rust::resolution::IncompletePaymentPolicy;
+//!
+//! for resolution in pi.resolve_incomplete_payments(IncompletePaymentPolicy::Cancel).await? {
+//!     if !resolution.is_resolved() {
+//!         println!("{} is still incomplete: {:?}", resolution.payment_id, resolution.error);
+//!     }
+//! }
+//! ```
+//!
+//! ## Errors
+//!
+//! Every method returns `PiError` on failure. When the Pi API rejects a call it's `PiError::Api`, carrying the HTTP status,
+//! the error code and message of the API, and a `PiApiErrorKind` for the well-known cases, so there is no need to compare strings.
+//! When Horizon rejects a transaction it's `PiError::Horizon` with the transaction and operation result codes.
+//! When the `TransactionSigner` of the app wallet fails it's `PiError::Signer`, the transaction wasn't submitted then.
+//!
+//! ```ignore
+//! match pi.create_payment(payment_data).await {
+//!     Ok(payment_id) => { /* store it */ }
+//!     Err(PiError::Api(err)) if err.kind == PiApiErrorKind::OngoingPaymentExists => { /* see get_incomplete_server_payments */ }
+//!     Err(PiError::Horizon(err)) => println!("{:?}", err.transaction_result_code()),
+//!     Err(err) => println!("{}", err),
+//! }
+//! ```
+//!
+//! ## Testing offline
+//!
+//! With the `mock` cargo feature the crate has local stand-ins of the Pi API and Horizon, `MockPiApi` and `MockHorizon`, so the payment flows of your app can be tested without the network.
+//! `MockPiApi` keeps the payments in memory with the same state transitions and error responses as the real API.
+//! `MockHorizon` keeps the native balances and sequence numbers in an in-memory ledger, checks the signatures and sequence numbers of the submitted transactions and applies their payments.
+//! `MockSigner` is a signing process holding the app wallet's keypair, for testing a `RemoteSigner` over HTTP or a Unix socket.
+//! On both of them errors can be injected to test the failure handling.
+//!
+//! ```ignore
+//! use pi_rust::mock::{MockError, MockHorizon, MockPiApi};
+//!
+//! let mock = MockPiApi::start("api_key", &app_wallet_address)?;
+//! let access_token = mock.add_user("user_uid", Some("username"), &user_wallet_address);
+//! let horizon = MockHorizon::start("Pi Testnet")?;
+//! horizon.create_account(&app_wallet_address, "100")?;
+//! horizon.create_account(&user_wallet_address, "1")?;
+//!
+//! let options = ReqwestClientOptions { base_url: mock.url(), horizon_url: Some(horizon.url()), ..Default::default() };
+//! let pi = PiNetwork::new("api_key".to_string(), app_wallet_seed, None, Some(options))?;
+//!
+//! // The whole A2U flow runs locally
+//! let payment_id = pi.create_payment(payment_data.clone()).await?;
+//! let txid = pi.submit_payment(payment_id.clone()).await?;
+//! pi.complete_payment(payment_id, txid).await?;
+//!
+//! // U2A payments are created like the frontend does
+//! let payment = mock.create_user_payment(payment_data)?;
+//! pi.approve_payment(payment.identifier.clone()).await?;
+//!
+//! // The next request matching the pattern fails
+//! mock.inject_error(MockError::new("/complete", 503, "unavailable", "Try again later"));
+//! ```
+//!
 //! ## Troubleshooting
 //!
 //! ### Error when creating a payment: "You need to complete the ongoing payment first to create a new one."
 //!
-//! See documentation for the `get_incomplete_server_payments` above.
+//! See documentation for the `get_incomplete_server_payments` above, or let `resolve_incomplete_payments` handle it.
 //////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
+pub mod amount;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod channels;
+pub mod fee;
+pub mod flow;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod network;
+pub mod payment_args;
+pub mod payment_state;
+pub mod resolution;
+pub mod retry;
+pub mod sequence;
+pub mod signer;
 pub mod stellar_sdk;
+pub mod store;
 pub mod types;
-use reqwest::{header, Client, StatusCode};
+pub mod verification;
+use channels::{ChannelLease, ChannelPool};
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::{header, Client, Method, RequestBuilder, Response, StatusCode};
+use resolution::{
+    IncompletePaymentAction, IncompletePaymentKind, IncompletePaymentPolicy,
+    IncompletePaymentResolution,
+};
+use retry::{parse_retry_after, RetryPolicy};
+use sequence::SequenceManager;
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::{json, Value};
-use std::str::FromStr;
+use signer::{IntoTransactionSigner, TransactionSigner};
+use std::convert::TryFrom;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use stellar_base::{
     amount::{Amount, Stroops},
     asset::Asset,
@@ -210,217 +499,423 @@ use stellar_base::{
     crypto::SodiumKeyPair,
     memo::Memo,
     operations::Operation,
+    time_bounds::TimeBounds,
     transaction::Transaction,
     Network, PublicKey,
 };
 use stellar_sdk::{
-    lib::{Keypair, Server},
-    types::Account,
+    endpoints::ServerOptions,
+    keypair::Redacted,
+    lib::{CallBuilder, Keypair, Server},
+    types::Transaction as HorizonTransaction,
+    utils::{Direction as HorizonDirection, Endpoint},
 };
 use types::*;
+use verification::U2APaymentVerification;
+use zeroize::Zeroizing;
+
+/// How many times an A2U transaction is built again after it was rejected with a wrong sequence number
+const MAX_BAD_SEQUENCE_RETRIES: u32 = 2;
+
+/// How long after the max time of a transaction a ledger closing after it is waited for, the clocks may differ
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Account ID of the source account of the transaction
+pub(crate) fn source_account_id(transaction: &Transaction) -> String {
+    match transaction.source_account() {
+        MuxedAccount::Ed25519(public_key) => public_key.account_id(),
+        MuxedAccount::MuxedEd25519(muxed) => muxed.public_key().account_id(),
+    }
+}
+
+/// Parsing the error body of a failed Pi API call into `PiError::Api`
+async fn api_error(response: Response) -> PiError {
+    let status = response.status().as_u16();
+    match response.text().await {
+        Ok(body) => PiError::Api(PiApiError::new(status, &body)),
+        Err(e) => PiError::Reqwest(e),
+    }
+}
+
+/// Creating the reqwest client from the options, or using the client given in the options
+/// The client is made once for the `PiNetwork`, so the connections are pooled between the calls
+fn build_reqwest_client(options: &ReqwestClientOptions) -> Result<Client, PiError> {
+    if let Some(client) = &options.client {
+        return Ok(client.clone());
+    }
 
-/// Creating the reqwest client for the api call with the necessary header authentication
-fn get_reqwest_client(api_key: String) -> Client {
-    let mut headers = header::HeaderMap::new();
-    let mut auth_value =
-        header::HeaderValue::from_str(format!("Key {}", api_key).as_str()).unwrap();
-    auth_value.set_sensitive(true);
-    headers.insert(header::AUTHORIZATION, auth_value);
-    headers.insert(
-        header::CONTENT_TYPE,
-        header::HeaderValue::from_static("application/json"),
-    );
-
-    let client = Client::builder()
-        .default_headers(headers)
-        .timeout(std::time::Duration::from_secs(20))
-        .build()
-        .unwrap();
-
-    client
+    let mut builder = Client::builder();
+    if let Some(timeout) = options.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(connect_timeout) = options.connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+    if let Some(proxy) = &options.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    for pem in &options.root_certificates {
+        builder = builder.add_root_certificate(reqwest::Certificate::from_pem(pem)?);
+    }
+    if let Some(user_agent) = &options.user_agent {
+        builder = builder.user_agent(user_agent);
+    }
+    if let Some(pool_max_idle_per_host) = options.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(pool_max_idle_per_host);
+    }
+
+    Ok(builder.build()?)
 }
 
 /// The `PiNetwork` struct containing every iformation for API authorization and the wallet handling
 /// Have to initialized once and we can call all the methods on this struct.
-/// If we want to handle more payments concurrently, we have to initialize one struct for every case.
-/// The struct can store one payment flow and focus on that.
+/// The struct doesn't keep any payment state, every method works from the payment ID it gets,
+/// so one instance (or an `Arc<PiNetwork>`, or a clone of it) can serve any number of concurrent payment flows.
+/// The API key and the wallet seed are zeroed on drop and printed as `[REDACTED]` by `Debug`.
+/// The transactions of the app wallet are signed by its `TransactionSigner`, the seed can be held by another process.
+#[derive(Clone)]
 pub struct PiNetwork {
-    pub api_key: String,
-    pub my_key_pair: Keypair,
-    pub network_passphrase: Option<NetworkPassphrase>,
-    pub current_payment: Option<PaymentDTO>,
+    api_key: Zeroizing<String>,
+    signer: Arc<dyn TransactionSigner>,
     pub reqwest_options: Option<ReqwestClientOptions>,
+    client: Client,
+    authorization: header::HeaderValue,
+    // Shared by the clones, so a pool set or retired on one of them applies to all
+    channels: Arc<RwLock<Option<Arc<ChannelPool>>>>,
+    sequences: Arc<SequenceManager>,
+    profile: NetworkProfile,
+}
+
+impl std::fmt::Debug for PiNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PiNetwork")
+            .field("api_key", &Redacted)
+            .field("signer", &self.signer)
+            .field("reqwest_options", &self.reqwest_options)
+            .field("client", &self.client)
+            .field("authorization", &self.authorization)
+            .field("channels", &self.channels)
+            .field("sequences", &self.sequences)
+            .field("profile", &self.profile)
+            .finish()
+    }
 }
 
 impl PiNetwork {
     // Creating new PiNetwork struct for the crate's user
+    // The wallet is its private seed, or any `TransactionSigner` if the seed is held elsewhere, see at `signer`
+    // The network is the preset profile of the passphrase, testnet by default, with the URLs of the options if they are given
     pub fn new(
         api_key: String,
-        wallet_private_seed: String,
+        wallet: impl IntoTransactionSigner,
         network_passphrase: Option<NetworkPassphrase>,
         options: Option<ReqwestClientOptions>,
     ) -> Result<Self, PiError> {
-        // Validating the seed format for common errors if erroring out returning error for the new function otherwise we continue the code
-        Self::validate_seed_format(&wallet_private_seed)?;
-        let my_key_pair = Keypair::from_secret_key(&wallet_private_seed);
-
-        // Matching if everything went ok then we are returning struct if the key pair generation errored then we returning that error
-        match my_key_pair {
-            Ok(my_key_pair) => {
-                return Ok(PiNetwork {
-                    api_key,
-                    my_key_pair,
-                    network_passphrase,
-                    current_payment: None,
-                    reqwest_options: options,
-                })
+        let mut profile =
+            NetworkProfile::from(network_passphrase.unwrap_or(NetworkPassphrase::PiTestnet));
+        if let Some(options) = &options {
+            profile.pi_api_url = options.base_url.clone();
+            if let Some(horizon_url) = &options.horizon_url {
+                profile.horizon_url = horizon_url.clone();
             }
-            Err(e) => return Err(PiError::Message(format!("{:?}", e))),
         }
+        Self::with_profile(api_key, wallet, profile, options)
+    }
+
+    /// Creating the PiNetwork for any network, e.g. a local stand-in, the URLs of the profile are used instead of the ones of the options
+    pub fn with_profile(
+        api_key: String,
+        wallet: impl IntoTransactionSigner,
+        profile: NetworkProfile,
+        options: Option<ReqwestClientOptions>,
+    ) -> Result<Self, PiError> {
+        let api_key = Zeroizing::new(api_key);
+        // A seed is validated for the common format errors before it's used
+        let signer = wallet.into_signer()?;
+
+        let mut authorization =
+            header::HeaderValue::from_str(&Zeroizing::new(format!("Key {}", *api_key))).map_err(
+                |_| PiError::Message("The API key contains invalid characters".to_string()),
+            )?;
+        authorization.set_sensitive(true);
+        let client = build_reqwest_client(&options.clone().unwrap_or_default())?;
+
+        Ok(PiNetwork {
+            api_key,
+            signer,
+            reqwest_options: options,
+            client,
+            authorization,
+            channels: Arc::new(RwLock::new(None)),
+            sequences: Arc::new(SequenceManager::new()),
+            profile,
+        })
+    }
+
+    /// Builder of the payment arguments, checking them against the payment limits of the network profile
+    pub fn payment_args(&self) -> PaymentArgsBuilder {
+        self.profile.payment_args()
     }
 
     /// You can create an A2U payment using create_payment method. This method returns a payment identifier (payment id).
-    pub async fn create_payment(&mut self, payment_data: PaymentArgs) -> Result<String, PiError> {
-        let client = get_reqwest_client(self.api_key.clone());
+    /// The arguments are checked against the `PaymentLimits` of the network profile first, see at `payment_args`.
+    /// The metadata can be any type serialized to an object, read it back with `get_payment::<M>`.
+    pub async fn create_payment<M: Serialize>(
+        &self,
+        payment_data: PaymentArgs<M>,
+    ) -> Result<String, PiError> {
+        payment_data.validate(&self.profile.payment_limits)?;
         let body = json!({ "payment": payment_data });
-        let url = match &self.reqwest_options {
-            Some(options) => options.base_url.clone(),
-            None => "https://api.minepi.com".to_string(),
-        };
+        let url = self.base_url();
 
-        let response = client
-            .post(format!("{url}/v2/payments"))
-            .json(&body)
-            .send()
+        let response = self
+            .send(
+                self.request(Method::POST, format!("{url}/v2/payments"))
+                    .json(&body),
+                false,
+            )
             .await?;
 
         if response.status() == StatusCode::OK {
             let response_data: Value = response.json().await?;
-            let payment_dto: PaymentDTO = serde_json::from_value(response_data.clone())?;
-            self.current_payment = Some(payment_dto.clone());
+            let payment_dto: PaymentDTO = serde_json::from_value(response_data)?;
 
             Ok(payment_dto.identifier)
         } else {
-            Err(PiError::Message(format!(
-                "Error, message from API: {:?}",
-                response.text().await
-            )))
+            Err(api_error(response).await)
         }
     }
 
     /// You can submit the payment to the Pi Blockchain using submit_payment method. This method builds a payment transaction and submits it to the Pi Blockchain for you. Once submitted, the method returns a transaction identifier (txid).
-    pub async fn submit_payment(&mut self, payment_id: String) -> Result<String, PiError> {
-        if let Some(current_payment) = self.current_payment.clone() {
-            if current_payment.identifier != payment_id {
-                self.current_payment = Some(self.get_payment(payment_id.clone()).await?);
-                if let Some(transaction) = current_payment.transaction {
-                    let tx_id = transaction.txid;
-                    return Err(PiError::Message(format!(
-                        "This payment already has a linked txid: Payment ID: {}, TX ID: {}",
-                        payment_id, tx_id
-                    )));
-                }
-            }
+    pub async fn submit_payment(&self, payment_id: String) -> Result<String, PiError> {
+        // Always working from the payment stored by the Pi API, so concurrent flows can't mix up each other's data
+        let payment = self.get_payment(payment_id.clone()).await?;
+        if let Some(transaction) = payment.transaction {
+            return Err(PiError::Message(format!(
+                "This payment already has a linked txid: Payment ID: {}, TX ID: {}",
+                payment_id, transaction.txid
+            )));
+        }
+        self.check_network(&payment)?;
 
-            let payment = self.current_payment.clone().unwrap();
-            let amount = payment.amount.clone();
-            let payment_identifier = payment.identifier.clone();
-            let from_address = payment.from_address.clone();
-            let to_address = payment.to_address.clone();
-            let network = payment.network.clone();
+        let pi_horizon = self.horizon_client()?;
 
-            let pi_horizon = PiNetwork::get_horizon_client(network).await;
+        let transaction_data = TransactionData {
+            amount: payment.amount,
+            payment_identifier: payment.identifier,
+            from_address: payment.from_address,
+            to_address: payment.to_address,
+        };
 
-            let transaction_data = TransactionData {
-                amount,
-                payment_identifier,
-                from_address,
-                to_address,
-            };
+        // The channel is kept until the transaction is submitted, so no other payment takes its sequence number
+        let channel = self.acquire_channel().await?;
+        let source = channel
+            .as_ref()
+            .map_or_else(|| self.signer.public_key(), ChannelLease::public_key);
 
+        let fee_policy = self.fee_policy();
+        let mut attempt = 0;
+        let mut min_fee = None;
+        loop {
             let transaction = self
-                .build_a2u_transaction(pi_horizon.clone(), transaction_data)
+                .build_a2u_transaction(
+                    pi_horizon.clone(),
+                    transaction_data.clone(),
+                    channel.as_ref().map(ChannelLease::keypair),
+                    min_fee,
+                )
                 .await?;
+            let fee = transaction.fee().to_i64();
+            let signed = self.encode_transaction(&transaction)?;
 
-            let txid = PiNetwork::submit_transaction(pi_horizon.clone(), transaction).await?;
+            match pi_horizon
+                .submit_transaction_envelope(&signed.envelope_xdr)
+                .await
+                .map_err(PiError::from)
+            {
+                Ok(response) => return Ok(response.id),
+                // No answer from Horizon, waiting for the transaction by its hash before doing anything else
+                Err(err) if err.is_unknown_submission_outcome() => {
+                    match self.submission_outcome(&pi_horizon, &signed, err).await? {
+                        Some(txid) => return Ok(txid),
+                        // It expired without getting into a ledger, its sequence number wasn't used
+                        None if attempt < MAX_BAD_SEQUENCE_RETRIES => {
+                            self.sequences.invalidate(&source);
+                            attempt += 1;
+                        }
+                        None => {
+                            return Err(PiError::Message(format!(
+                            "Transaction {} of payment {} expired without getting into a ledger",
+                            signed.txid, payment_id
+                        )))
+                        }
+                    }
+                }
+                // The transaction wasn't applied with a wrong sequence number, it's safe to build it again
+                Err(err)
+                    if self.sequences.handle_error(&source, &err)
+                        && attempt < MAX_BAD_SEQUENCE_RETRIES =>
+                {
+                    attempt += 1;
+                }
+                // Not applied either, signing it again with a higher fee if the policy allows it
+                Err(err) if err.transaction_result_code() == Some("tx_insufficient_fee") => {
+                    match fee_policy.raised_fee(fee) {
+                        Some(raised_fee) => min_fee = Some(raised_fee),
+                        None => return Err(err),
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
 
-            self.current_payment = None;
-            Ok(txid)
-        } else {
-            Err(PiError::Message("No current payment available".to_string()))
+    /// Replacing the transaction of a payment if it was lost, e.g. the submission timed out and Horizon doesn't know about it
+    ///
+    /// The earlier transaction is given by its signed envelope, which must have a max time (see `transaction_validity`).
+    /// It waits until the transaction expired, then if it's found by its hash, or a successful transaction with
+    /// the payment ID as memo is found, that txid is returned. Only otherwise a new transaction is built and submitted.
+    pub async fn resubmit_payment(
+        &self,
+        payment_id: String,
+        envelope_xdr: &str,
+    ) -> Result<String, PiError> {
+        let signed = SignedTransaction::from_envelope(envelope_xdr, &self.profile.passphrase)?;
+        let max_time = signed.max_time.ok_or_else(|| {
+            PiError::Message(
+                "The transaction has no max time, it can't be replaced safely".to_string(),
+            )
+        })?;
+
+        let payment = self.get_payment(payment_id.clone()).await?;
+        self.check_network(&payment)?;
+        let pi_horizon = self.horizon_client()?;
+        PiNetwork::wait_until_expired(&pi_horizon, max_time).await?;
+
+        if let Some(found) = PiNetwork::find_transaction(&pi_horizon, &signed.txid).await? {
+            if found.successful {
+                return Ok(signed.txid);
+            }
         }
+        if let Some(found) = self
+            .find_transaction_by_memo(&pi_horizon, &payment_id)
+            .await?
+        {
+            return Ok(found.hash);
+        }
+        // The sequence number of the expired transaction wasn't used, it's taken by the new one
+        self.sequences.invalidate(&signed.source_account);
+        self.submit_payment(payment_id).await
     }
 
     /// This method completes the payment in the Pi server.
     pub async fn complete_payment(
-        &mut self,
+        &self,
         payment_id: String,
         tx_id: String,
     ) -> Result<PaymentDTO, PiError> {
-        let client = get_reqwest_client(self.api_key.clone());
-        let url = match &self.reqwest_options {
-            Some(options) => options.base_url.clone(),
-            None => "https://api.minepi.com".to_string(),
-        };
+        let url = self.base_url();
 
         let body = json!({ "txid": tx_id });
 
-        let response = client
-            .post(format!("{url}/v2/payments/{payment_id}/complete"))
-            .json(&body)
-            .send()
+        let (response, attempts) = self
+            .send_counted(
+                self.request(
+                    Method::POST,
+                    format!("{url}/v2/payments/{payment_id}/complete"),
+                )
+                .json(&body),
+                true,
+            )
             .await?;
 
         if response.status() == StatusCode::OK {
             let response_data: Value = response.json().await?;
-            let payment_dto: PaymentDTO = serde_json::from_value(response_data.clone())?;
-            self.current_payment = None;
+            let payment_dto: PaymentDTO = serde_json::from_value(response_data)?;
 
             Ok(payment_dto)
         } else {
-            Err(PiError::Message(format!(
-                "Error, message from API: {:?}",
-                response.text().await
-            )))
+            let err = api_error(response).await;
+            // The response of an earlier attempt was lost, but that attempt may have completed the payment
+            if attempts > 1 && err.api_error_kind() == Some(PiApiErrorKind::AlreadyCompleted) {
+                let payment: PaymentDTO = self.get_payment(payment_id).await?;
+                if payment
+                    .transaction
+                    .as_ref()
+                    .is_some_and(|transaction| transaction.txid == tx_id)
+                {
+                    return Ok(payment);
+                }
+            }
+            Err(err)
         }
     }
 
     /// This method returns a payment object based on the payment ID if it exists.
-    pub async fn get_payment(&mut self, payment_id: String) -> Result<PaymentDTO, PiError> {
-        let client = get_reqwest_client(self.api_key.clone());
-        let url = match &self.reqwest_options {
-            Some(options) => options.base_url.clone(),
-            None => "https://api.minepi.com".to_string(),
-        };
-        let response = client
-            .get(format!("{url}/v2/payments/{payment_id}"))
-            .send()
+    /// The metadata is deserialized to `M`, e.g. `get_payment::<OrderRef>(payment_id)`, or `get_payment::<Value>` for the plain JSON.
+    /// If the stored metadata doesn't fit `M`, `PiError::MetadataMismatch` is returned.
+    pub async fn get_payment<M: DeserializeOwned>(
+        &self,
+        payment_id: String,
+    ) -> Result<PaymentDTO<M>, PiError> {
+        let url = self.base_url();
+        let response = self
+            .send(
+                self.request(Method::GET, format!("{url}/v2/payments/{payment_id}")),
+                true,
+            )
             .await?;
 
         if response.status() == StatusCode::OK {
             let response_data: Value = response.json().await?;
             let payment: PaymentDTO = serde_json::from_value(response_data)?;
 
-            Ok(payment)
+            payment.deserialize_metadata()
         } else {
-            Err(PiError::Message(format!(
-                "Error, message from API: {:?}",
-                response.text().await
-            )))
+            Err(api_error(response).await)
+        }
+    }
+
+    /// This method verifies the access token of a user, obtained on the frontend with `Pi.authenticate`, and returns the user's data.
+    /// Never trust the user data sent by the frontend, always verify the access token with this method on the backend.
+    /// If the token is expired or invalid `PiError::InvalidAccessToken` is returned.
+    pub async fn authenticate_user(&self, access_token: String) -> Result<UserDTO, PiError> {
+        let url = self.base_url();
+        // The /me endpoint authorized with the user's token instead of the app's API key
+        let response = self
+            .send(
+                self.client
+                    .get(format!("{url}/v2/me"))
+                    .bearer_auth(access_token),
+                true,
+            )
+            .await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let user: UserDTO = response.json().await?;
+                Ok(user)
+            }
+            StatusCode::UNAUTHORIZED => Err(PiError::InvalidAccessToken(format!(
+                "The user access token is expired or invalid: {}",
+                response.text().await.unwrap_or_default()
+            ))),
+            _ => Err(api_error(response).await),
         }
     }
 
     /// This method required to approve the user payment created on the frontend, after the backend is approved the user can pay
-    pub async fn approve_payment(&mut self, payment_id: String) -> Result<PaymentDTO, PiError> {
-        let client = get_reqwest_client(self.api_key.clone());
-        let url = match &self.reqwest_options {
-            Some(options) => options.base_url.clone(),
-            None => "https://api.minepi.com".to_string(),
-        };
-        let response = client
-            .post(format!("{url}/v2/payments/{payment_id}/approve"))
-            .body("{}")
-            .send()
+    pub async fn approve_payment(&self, payment_id: String) -> Result<PaymentDTO, PiError> {
+        let url = self.base_url();
+        let response = self
+            .send(
+                self.request(
+                    Method::POST,
+                    format!("{url}/v2/payments/{payment_id}/approve"),
+                )
+                .body("{}"),
+                false,
+            )
             .await?;
 
         if response.status() == StatusCode::OK {
@@ -429,35 +924,60 @@ impl PiNetwork {
 
             Ok(payment)
         } else {
-            Err(PiError::Message(format!(
-                "Error, message from API: {:?}",
-                response.text().await
-            )))
+            Err(api_error(response).await)
         }
     }
 
+    /// This method verifies a U2A (User-to-App) payment against the Pi Blockchain before you complete it.
+    /// The txid is coming from the frontend, so it shouldn't be trusted: the transaction is loaded from Horizon
+    /// and its memo, source, destination, amount, asset, success and network are compared with the payment.
+    /// Only call `complete_payment` if the returned report's `is_valid` is true.
+    pub async fn verify_u2a_payment(
+        &self,
+        payment_id: String,
+        txid: String,
+    ) -> Result<U2APaymentVerification, PiError> {
+        let payment = self.get_payment(payment_id).await?;
+        let pi_horizon = self.horizon_client()?;
+
+        let transaction = pi_horizon.load_transaction(&txid).await?;
+        let operations = pi_horizon
+            .payments()
+            .for_endpoint(Endpoint::Transactions(format!("/{}", txid)))
+            .limit(200)
+            .call()
+            .await?
+            ._embedded
+            .records;
+
+        Ok(U2APaymentVerification::new(
+            &payment,
+            &transaction,
+            &operations,
+            &self.signer.public_key(),
+            &self.network(),
+        ))
+    }
+
     /// This method cancels the payment in the Pi server.
-    pub async fn cancel_payment(&mut self, payment_id: String) -> Result<PaymentDTO, PiError> {
-        let client = get_reqwest_client(self.api_key.clone());
-        let url = match &self.reqwest_options {
-            Some(options) => options.base_url.clone(),
-            None => "https://api.minepi.com".to_string(),
-        };
-        let response = client
-            .post(format!("{url}/v2/payments/{payment_id}/cancel"))
-            .body("{}")
-            .send()
-            .await
-            .unwrap();
+    pub async fn cancel_payment(&self, payment_id: String) -> Result<PaymentDTO, PiError> {
+        let url = self.base_url();
+        let response = self
+            .send(
+                self.request(
+                    Method::POST,
+                    format!("{url}/v2/payments/{payment_id}/cancel"),
+                )
+                .body("{}"),
+                false,
+            )
+            .await?;
 
         if response.status() == StatusCode::OK {
             let response_data: PaymentDTO = response.json().await?;
             Ok(response_data)
         } else {
-            Err(PiError::Message(format!(
-                "Error, message from API: {:?}",
-                response.text().await
-            )))
+            Err(api_error(response).await)
         }
     }
 
@@ -470,14 +990,15 @@ impl PiNetwork {
     ///
     /// If you do not know what this payment maps to in your business logic, you may use its metadata property to retrieve which business logic item it relates to. Remember that metadata is a required argument when creating a payment, and should be used as a way to link this payment to an item of your business logic.
     pub async fn get_incomplete_server_payments(&self) -> Result<Vec<PaymentDTO>, PiError> {
-        let client = get_reqwest_client(self.api_key.clone());
-        let url = match &self.reqwest_options {
-            Some(options) => options.base_url.clone(),
-            None => "https://api.minepi.com".to_string(),
-        };
-        let response = client
-            .get(format!("{url}/v2/payments/incomplete_server_payments"))
-            .send()
+        let url = self.base_url();
+        let response = self
+            .send(
+                self.request(
+                    Method::GET,
+                    format!("{url}/v2/payments/incomplete_server_payments"),
+                ),
+                true,
+            )
             .await?;
 
         if response.status() == StatusCode::OK {
@@ -485,13 +1006,166 @@ impl PiNetwork {
             let payment_vec: Vec<PaymentDTO> = response_data.incomplete_server_payments;
             Ok(payment_vec)
         } else {
-            Err(PiError::Message(format!(
-                "Error, message from API: {:?}",
-                response.text().await
-            )))
+            Err(api_error(response).await)
+        }
+    }
+
+    /// This method resolves every incomplete payment returned by `get_incomplete_server_payments`, so new payments can be created again.
+    /// Call it at startup to clear the "You need to complete the ongoing payment first" error automatically.
+    ///
+    /// Every payment is classified by its linked transaction and status, and Horizon is checked for a transaction with the payment ID as memo:
+    /// - a successful transaction is on the blockchain: the payment is completed with it
+    /// - no transaction was made: the `policy` decides whether it's submitted and completed, or cancelled
+    ///
+    /// Before submitting or cancelling, it waits until a transaction made by an earlier run can't get into a ledger anymore
+    /// (see `transaction_validity`), then checks the memo again, so a payment in flight isn't paid twice.
+    ///
+    /// A failure doesn't stop the other payments, it's reported in the `error` of that payment's resolution.
+    pub async fn resolve_incomplete_payments(
+        &self,
+        policy: IncompletePaymentPolicy,
+    ) -> Result<Vec<IncompletePaymentResolution>, PiError> {
+        let mut resolutions = Vec::new();
+        // Latest max time a transaction of an earlier run can have, looked up once when it's first needed
+        let mut earlier_max_time = None;
+        for payment in self.get_incomplete_server_payments().await? {
+            let mut resolution = IncompletePaymentResolution::new(&payment);
+            if let Err(err) = self
+                .resolve_incomplete_payment(
+                    &mut resolution,
+                    &payment,
+                    policy,
+                    &mut earlier_max_time,
+                )
+                .await
+            {
+                resolution.error = Some(err);
+            }
+            resolutions.push(resolution);
+        }
+        Ok(resolutions)
+    }
+
+    /// Request to the Pi API authorized with the app's API key
+    fn request(&self, method: Method, url: String) -> RequestBuilder {
+        self.client
+            .request(method, url)
+            .header(header::AUTHORIZATION, self.authorization.clone())
+            .header(header::CONTENT_TYPE, "application/json")
+    }
+
+    /// The Pi API base URL of the network profile
+    fn base_url(&self) -> String {
+        self.profile.pi_api_url.clone()
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        match &self.reqwest_options {
+            Some(options) => options.retry_policy.clone(),
+            None => RetryPolicy::default(),
+        }
+    }
+
+    pub(crate) fn fee_policy(&self) -> FeePolicy {
+        match &self.reqwest_options {
+            Some(options) => options.fee_policy.clone(),
+            None => FeePolicy::default(),
+        }
+    }
+
+    /// Sending the request to the Pi API with the retry policy of the options
+    /// `idempotent` requests are repeated on any transient failure, the others only if the Pi API surely didn't process them:
+    /// the connection couldn't be made or the request was rate limited
+    async fn send(&self, request: RequestBuilder, idempotent: bool) -> Result<Response, PiError> {
+        Ok(self.send_counted(request, idempotent).await?.0)
+    }
+
+    /// Sending the request like `send`, also returning the number of attempts made
+    async fn send_counted(
+        &self,
+        request: RequestBuilder,
+        idempotent: bool,
+    ) -> Result<(Response, u32), PiError> {
+        let policy = self.retry_policy();
+        let mut retry = 0;
+        loop {
+            let attempt = request
+                .try_clone()
+                .ok_or_else(|| PiError::Message("The request can't be retried".to_string()))?;
+            retry += 1;
+            let delay = match attempt.send().await {
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    let retriable =
+                        policy.is_retriable_status(status) && (idempotent || status == 429);
+                    if !retriable || !policy.allows_retry(retry) {
+                        return Ok((response, retry));
+                    }
+                    let retry_after = response
+                        .headers()
+                        .get(header::RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(parse_retry_after);
+                    match policy.delay(retry, retry_after) {
+                        Some(delay) => delay,
+                        None => return Ok((response, retry)),
+                    }
+                }
+                Err(err) => {
+                    let retriable =
+                        err.is_connect() || (idempotent && (err.is_timeout() || err.is_request()));
+                    if !retriable || !policy.allows_retry(retry) {
+                        return Err(err.into());
+                    }
+                    policy.backoff(retry)
+                }
+            };
+            tokio::time::sleep(delay).await;
         }
     }
 
+    /// The cache of the sequence numbers used for the transactions of the app wallet and the channel accounts
+    pub fn sequence_manager(&self) -> &SequenceManager {
+        &self.sequences
+    }
+
+    /// The profile of the network the app is configured for
+    pub fn profile(&self) -> &NetworkProfile {
+        &self.profile
+    }
+
+    /// The API key of the app
+    pub fn api_key(&self) -> &str {
+        &self.api_key
+    }
+
+    /// The signer of the app wallet's transactions
+    pub fn signer(&self) -> &dyn TransactionSigner {
+        self.signer.as_ref()
+    }
+
+    /// The public key of the app wallet, given by its signer
+    fn app_public_key(&self) -> Result<PublicKey, PiError> {
+        PublicKey::from_account_id(&self.signer.public_key())
+            .map_err(|e| PiError::Message(format!("Invalid public key of the signer! {:?}", e)))
+    }
+
+    /// The network the app is configured for, as the Pi API reports it for the payments
+    fn network(&self) -> NetworkPassphrase {
+        self.profile.network.clone()
+    }
+
+    /// Rejecting the payments of another network, their transactions would be signed for the wrong one
+    pub(crate) fn check_network(&self, payment: &PaymentDTO) -> Result<(), PiError> {
+        if payment.network != self.profile.network {
+            return Err(PiError::Message(format!(
+                "Payment {} is on {}, but the app is configured for {}",
+                payment.identifier, payment.network, self.profile.network
+            )));
+        }
+        Ok(())
+    }
+
     /// Validating the seed format, trying to filter out invalid wallet secret seeds
     pub fn validate_seed_format(seed: &str) -> Result<(), PiError> {
         if !seed.starts_with("S") {
@@ -507,42 +1181,46 @@ impl PiNetwork {
         Ok(())
     }
 
-    /// Based on which network we want to use it returning the correct URI
-    async fn get_horizon_client(network: NetworkPassphrase) -> Server {
-        let server_url = match network {
-            NetworkPassphrase::PiNetwork => "https://api.mainnet.minepi.com".to_string(),
-            NetworkPassphrase::PiTestnet => "https://api.testnet.minepi.com".to_string(),
+    /// Horizon client of the network profile
+    fn horizon_client(&self) -> Result<Server, PiError> {
+        let options = ServerOptions {
+            allow_http: Some(self.profile.horizon_url.starts_with("http://")),
+            retry_policy: Some(self.retry_policy()),
+            network_passphrase: Some(self.profile.passphrase.clone()),
+            // The client of the Pi API, its timeouts and proxy apply to Horizon too
+            client: Some(self.client.clone()),
+            ..ServerOptions::default()
         };
-        Server::new(server_url, None).unwrap()
-    }
-
-    // Based on which network we wnat to use it retunring the network's passphrase
-    async fn get_network_passphrase(network: NetworkPassphrase) -> String {
-        let server_url = match network {
-            NetworkPassphrase::PiNetwork => "Pi Network".to_string(),
-            NetworkPassphrase::PiTestnet => "Pi Testnet".to_string(),
-        };
-        server_url
+        Ok(Server::new(
+            self.profile.horizon_url.clone(),
+            Some(options),
+        )?)
     }
 
     /// Building app to user trasanction
+    /// With a channel account the channel is the source of the transaction, but the payment is still made from the app wallet
+    /// The fee comes from the fee strategy, but it's at least `min_fee` if it's given
     async fn build_a2u_transaction(
         &self,
         pi_horizon: Server,
         transaction_data: TransactionData,
+        channel: Option<&Keypair>,
+        min_fee: Option<i64>,
     ) -> Result<Transaction, PiError> {
-        if transaction_data.from_address != self.my_key_pair.public_key() {
+        let app_account_id = self.signer.public_key();
+        if transaction_data.from_address != app_account_id {
             return Err(PiError::Message(
                 "You should use a private seed of your app wallet!".to_string(),
             ));
         }
 
-        let my_account: Account = pi_horizon.load_account(&self.my_key_pair.public_key())?;
-        let base_fee_string = pi_horizon.fetch_base_fee()?;
-        let base_fee_i64 = base_fee_string.parse::<i64>()?;
-        let base_fee = Stroops::new(base_fee_i64);
+        let source_account_id = channel.map_or_else(|| app_account_id.clone(), Keypair::public_key);
+        let sequence = self.sequences.next(&pi_horizon, &source_account_id).await?;
+        let fee = self.fee_policy().strategy.fee(&pi_horizon).await?;
+        let base_fee = Stroops::new(fee.max(min_fee.unwrap_or_default()));
 
-        let amount_str = transaction_data.amount.clone().to_string();
+        let amount = Amount::try_from(transaction_data.amount)
+            .map_err(|e| PiError::Message(format!("Invalid payment amount! {:?}", e)))?;
         let destination_account_public_key =
             PublicKey::from_account_id(&transaction_data.to_address.clone());
         let destination_account_muxed: MuxedAccount = match destination_account_public_key {
@@ -555,46 +1233,297 @@ impl PiNetwork {
             }
         };
 
-        let payment_operation = Operation::new_payment()
+        let app_public_key = self.app_public_key()?;
+        let mut payment_operation = Operation::new_payment()
             .with_destination(destination_account_muxed.clone())
-            .with_amount(Amount::from_str(&amount_str).unwrap())
+            .with_amount(amount)
             .unwrap()
-            .with_asset(Asset::new_native())
-            .build()
-            .unwrap();
-
-        let sequence = my_account.sequence.clone().parse::<i64>().unwrap() + 1; // Getting the current sequence of the account and adding 1 to it
+            .with_asset(Asset::new_native());
+        if channel.is_some() {
+            payment_operation = payment_operation.with_source_account(app_public_key);
+        }
+        let payment_operation = payment_operation.build().unwrap();
 
-        let source_account_keypair: SodiumKeyPair = self.my_key_pair.clone().into();
-        let source_account_public_key = source_account_keypair.public_key();
-        let source_account_muxed: MuxedAccount = source_account_public_key.clone().into();
+        let source_account_muxed: MuxedAccount = match channel {
+            Some(channel) => SodiumKeyPair::from(channel.clone()).public_key(),
+            None => app_public_key,
+        }
+        .into();
 
-        let mut transaction = Transaction::builder(source_account_muxed, sequence, base_fee)
+        let mut builder = Transaction::builder(source_account_muxed, sequence, base_fee)
             .with_memo(Memo::Text(transaction_data.payment_identifier.clone()))
-            .add_operation(payment_operation)
-            .into_transaction()
-            .unwrap();
-
-        // If the user gave us the network passphrase we are using that if he not then going with testnet as default
-        let network_passphrase_enum: NetworkPassphrase = match &self.network_passphrase {
-            Some(passphrase) => passphrase.clone(),
-            None => NetworkPassphrase::PiTestnet,
-        };
+            .add_operation(payment_operation);
+        if let Some(time_bounds) = self.time_bounds(&pi_horizon).await? {
+            builder = builder.with_time_bounds(time_bounds);
+        }
+        let mut transaction = builder.into_transaction().unwrap();
 
-        // Signing the transaction
-        let _ = transaction.sign(
-            &source_account_keypair.as_ref(),
-            &Network::new(PiNetwork::get_network_passphrase(network_passphrase_enum).await),
-        );
+        let network = Network::new(self.profile.passphrase.clone());
+
+        // Signing the transaction, by the channel too if it's made by one
+        // If the signer fails the sequence number stays unused, so it's loaded again for the next transaction
+        signer::sign_transaction(self.signer.as_ref(), &mut transaction, &network)
+            .await
+            .inspect_err(|_| self.sequences.invalidate(&source_account_id))?;
+        if let Some(channel) = channel {
+            transaction
+                .sign(SodiumKeyPair::from(channel.clone()).as_ref(), &network)
+                .map_err(|e| PiError::Message(format!("Can't sign the transaction! {:?}", e)))
+                .inspect_err(|_| self.sequences.invalidate(&source_account_id))?;
+        }
         Ok(transaction)
     }
 
-    /// Submitting the built transaction to the blockchain
-    async fn submit_transaction(
-        pi_horizon: Server,
-        transaction: Transaction,
-    ) -> Result<String, PiError> {
-        let tx_response = pi_horizon.submit_transaction(transaction)?;
-        Ok(tx_response.id)
+    /// How long an A2U transaction is valid after it's built, `None` if the transactions are valid forever
+    fn transaction_validity(&self) -> Option<Duration> {
+        match &self.reqwest_options {
+            Some(options) => options.transaction_validity,
+            None => ReqwestClientOptions::default().transaction_validity,
+        }
+    }
+
+    /// Time bounds of a new A2U transaction from the time of Horizon, `None` if the transactions are valid forever
+    async fn time_bounds(&self, pi_horizon: &Server) -> Result<Option<TimeBounds>, PiError> {
+        let Some(validity) = self.transaction_validity() else {
+            return Ok(None);
+        };
+
+        let timebounds = pi_horizon
+            .fetch_timebounds(validity.as_secs() as i64, false)
+            .await?;
+        let max_time = Utc
+            .timestamp_opt(timebounds.max_time, 0)
+            .single()
+            .ok_or_else(|| PiError::Message("Invalid max time of the transaction".to_string()))?;
+        let time_bounds = TimeBounds::always_valid()
+            .with_upper(max_time)
+            .map_err(|e| PiError::Message(format!("Invalid time bounds! {:?}", e)))?;
+        Ok(Some(time_bounds))
+    }
+
+    /// The latest max time a transaction built before now can have, by the time of Horizon
+    /// Without a transaction validity an earlier transaction can get in anytime, so it's an error
+    async fn earlier_max_time(&self, pi_horizon: &Server) -> Result<i64, PiError> {
+        let validity = self.transaction_validity().ok_or_else(|| {
+            PiError::Message(
+                "The transactions have no max time, a payment can't be submitted again safely"
+                    .to_string(),
+            )
+        })?;
+        let timebounds = pi_horizon
+            .fetch_timebounds(validity.as_secs() as i64, false)
+            .await?;
+        Ok(timebounds.max_time)
+    }
+
+    /// Waiting until a ledger closed after the max time, a transaction with that max time can't be applied anymore
+    /// It's an error if no such ledger closed by `EXPIRY_MARGIN` after the max time
+    async fn wait_until_expired(pi_horizon: &Server, max_time: i64) -> Result<(), PiError> {
+        let max_time = Utc
+            .timestamp_opt(max_time, 0)
+            .single()
+            .ok_or_else(|| PiError::Message("Invalid max time of the transaction".to_string()))?;
+        let deadline =
+            Instant::now() + (max_time - Utc::now()).to_std().unwrap_or_default() + EXPIRY_MARGIN;
+        loop {
+            let ledgers = pi_horizon
+                .ledgers()
+                .order(HorizonDirection::Desc)
+                .limit(1)
+                .call()
+                .await?;
+            let closed_at = ledgers
+                ._embedded
+                .records
+                .first()
+                .map(|ledger| {
+                    DateTime::parse_from_rfc3339(&ledger.closed_at)
+                        .map(|closed_at| closed_at.with_timezone(&Utc))
+                        .map_err(|e| {
+                            PiError::Message(format!(
+                                "Invalid close time {} of ledger {}: {}",
+                                ledger.closed_at, ledger.sequence, e
+                            ))
+                        })
+                })
+                .transpose()?;
+            if closed_at.is_some_and(|closed_at| closed_at > max_time) {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(PiError::Message(format!(
+                    "No ledger closed after {} by the deadline, the transaction may still get in",
+                    max_time.to_rfc3339()
+                )));
+            }
+
+            let wait = closed_at
+                .and_then(|closed_at| (max_time - closed_at).to_std().ok())
+                .unwrap_or_default();
+            tokio::time::sleep(wait.clamp(Duration::from_secs(1), Duration::from_secs(5))).await;
+        }
+    }
+
+    /// The envelope of the signed transaction with its hash, computed locally in the same format as Horizon's txid
+    pub(crate) fn encode_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<SignedTransaction, PiError> {
+        SignedTransaction::new(transaction, &self.profile.passphrase)
+    }
+
+    /// Finding out whether a transaction got into a ledger after its submission got no clear answer
+    ///
+    /// Returns the txid if it was applied successfully, `None` if it expired without getting in, so it can be built
+    /// again. Without a max time it could still get in anytime, then the submission error is returned.
+    async fn submission_outcome(
+        &self,
+        pi_horizon: &Server,
+        signed: &SignedTransaction,
+        err: PiError,
+    ) -> Result<Option<String>, PiError> {
+        let (Some(max_time), Some(time_left)) = (signed.max_time, signed.time_left()) else {
+            return Err(err);
+        };
+
+        let mut found = pi_horizon
+            .wait_for_transaction(&signed.txid, time_left)
+            .await?;
+        if found.is_none() {
+            // The clock of the ledgers may be behind, it's only certain once a ledger closed after the max time
+            PiNetwork::wait_until_expired(pi_horizon, max_time).await?;
+            found = PiNetwork::find_transaction(pi_horizon, &signed.txid).await?;
+        }
+        match found {
+            Some(transaction) if transaction.successful => Ok(Some(signed.txid.clone())),
+            Some(_) => Err(PiError::Message(format!(
+                "Transaction {} failed on the blockchain",
+                signed.txid
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    /// Looking up a transaction on Horizon by its hash, `None` if Horizon doesn't know about it
+    async fn find_transaction(
+        pi_horizon: &Server,
+        hash: &str,
+    ) -> Result<Option<HorizonTransaction>, PiError> {
+        match pi_horizon
+            .load_transaction(hash)
+            .await
+            .map_err(PiError::from)
+        {
+            Ok(transaction) => Ok(Some(transaction)),
+            Err(PiError::Horizon(err)) if err.status == 404 => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Looking for a successful transaction of the app wallet with the payment ID as memo
+    /// Only the latest 200 transactions of the wallet are checked
+    async fn find_transaction_by_memo(
+        &self,
+        pi_horizon: &Server,
+        payment_id: &str,
+    ) -> Result<Option<HorizonTransaction>, PiError> {
+        let transactions = pi_horizon
+            .transactions()
+            .for_endpoint(Endpoint::Accounts(self.signer.public_key()))
+            .include_failed(false)
+            .order(HorizonDirection::Desc)
+            .limit(200)
+            .call()
+            .await?
+            ._embedded
+            .records;
+
+        Ok(transactions
+            .into_iter()
+            .find(|transaction| transaction.memo.as_deref() == Some(payment_id)))
+    }
+
+    /// Carrying out the action for one incomplete payment, the resolution is updated before every attempt
+    async fn resolve_incomplete_payment(
+        &self,
+        resolution: &mut IncompletePaymentResolution,
+        payment: &PaymentDTO,
+        policy: IncompletePaymentPolicy,
+        earlier_max_time: &mut Option<i64>,
+    ) -> Result<(), PiError> {
+        self.check_network(payment)?;
+        let pi_horizon = self.horizon_client()?;
+
+        match resolution.kind {
+            IncompletePaymentKind::LinkedTransaction => {
+                let txid = resolution.txid.clone().unwrap_or_default();
+                match PiNetwork::find_transaction(&pi_horizon, &txid).await? {
+                    Some(transaction) if transaction.successful => {}
+                    Some(_) => {
+                        return Err(PiError::Message(format!(
+                            "Transaction {} linked to payment {} failed on the blockchain",
+                            txid, payment.identifier
+                        )))
+                    }
+                    None => {
+                        return Err(PiError::Message(format!(
+                            "Transaction {} linked to payment {} is not found on the blockchain",
+                            txid, payment.identifier
+                        )))
+                    }
+                }
+            }
+            IncompletePaymentKind::Unsubmitted => {
+                let mut found = self
+                    .find_transaction_by_memo(&pi_horizon, &payment.identifier)
+                    .await?;
+                if found.is_none() {
+                    // A transaction of a crashed run can still be in flight, the payment is only cancelled or paid
+                    // again once it expired without getting in
+                    resolution.action = match policy {
+                        IncompletePaymentPolicy::Cancel => IncompletePaymentAction::Cancel,
+                        IncompletePaymentPolicy::Submit => {
+                            IncompletePaymentAction::SubmitAndComplete
+                        }
+                    };
+                    let max_time = match *earlier_max_time {
+                        Some(max_time) => max_time,
+                        None => *earlier_max_time.insert(self.earlier_max_time(&pi_horizon).await?),
+                    };
+                    PiNetwork::wait_until_expired(&pi_horizon, max_time).await?;
+                    found = self
+                        .find_transaction_by_memo(&pi_horizon, &payment.identifier)
+                        .await?;
+                }
+
+                if let Some(transaction) = found {
+                    // Paid already, only the Pi API doesn't know about it
+                    resolution.kind = IncompletePaymentKind::MemoTransaction;
+                    resolution.action = IncompletePaymentAction::Complete;
+                    resolution.txid = Some(transaction.hash);
+                } else if policy == IncompletePaymentPolicy::Cancel {
+                    self.cancel_payment(payment.identifier.clone()).await?;
+                    return Ok(());
+                } else {
+                    let txid = self.submit_payment(payment.identifier.clone()).await?;
+                    resolution.txid = Some(txid);
+                }
+            }
+            IncompletePaymentKind::MemoTransaction => {}
+            IncompletePaymentKind::Completed | IncompletePaymentKind::Cancelled => return Ok(()),
+        }
+
+        if resolution.action == IncompletePaymentAction::Nothing {
+            resolution.action = IncompletePaymentAction::Complete;
+        }
+        let txid = resolution.txid.clone().unwrap_or_default();
+        match self
+            .complete_payment(payment.identifier.clone(), txid)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) if err.api_error_kind() == Some(PiApiErrorKind::AlreadyCompleted) => Ok(()),
+            Err(err) => Err(err),
+        }
     }
 }
@@ -0,0 +1,1336 @@
+use data_encoding::{BASE64, HEXLOWER};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use stellar_base::{
+    amount::Amount,
+    crypto::{hash, MuxedAccount, PublicKey},
+    memo::Memo,
+    operations::Operation,
+    transaction::{Transaction as TransactionSBase, TransactionEnvelope},
+    xdr::XDRDeserialize,
+    Network,
+};
+
+use super::http::{MockRequest, MockResponse, MockServer};
+use super::MockError;
+use crate::stellar_sdk::keypair::Keypair;
+use crate::stellar_sdk::types::{FeeStats, Transaction};
+use crate::types::PiError;
+
+const STROOPS_PER_UNIT: i64 = 10_000_000;
+const MAX_PAGE_LIMIT: usize = 200;
+
+/// Local stand-in of Horizon, available with the `mock` cargo feature
+///
+/// Keeps an in-memory ledger of native balances and sequence numbers, and serves `/accounts`, `/fee_stats`,
+/// `/transactions` (GET and POST), `/operations`, `/payments` and `/ledgers` from it.
+/// Submitted envelopes are checked like stellar-core does: time bounds, fee, sequence number and signatures,
+/// then the native payments, account creations and account merges are applied, every included transaction closes a new ledger.
+/// The responses are `SubmitTransactionResponse` or `HorizonError` bodies with the real result codes.
+/// Point `Server::new` or `ReqwestClientOptions::horizon_url` at `url()`.
+///
+/// ```ignore
+/// let horizon = MockHorizon::start("Pi Testnet")?;
+/// horizon.create_account(&app_wallet_address, "100")?;
+/// let server = Server::new(horizon.url(), Some(ServerOptions { allow_http: Some(true), ..Default::default() }))?;
+/// ```
+pub struct MockHorizon {
+    server: MockServer,
+    state: Arc<Mutex<HorizonState>>,
+}
+
+struct HorizonState {
+    network: Network,
+    base_fee: i64,
+    base_reserve: i64,
+    fee_stats: Option<FeeStats>,
+    ledger_interval: Option<chrono::Duration>,
+    ledgers: Vec<MockLedger>,
+    accounts: HashMap<String, MockAccount>,
+    transactions: Vec<MockTransaction>,
+    errors: Vec<MockError>,
+    requests: Vec<String>,
+}
+
+#[derive(Clone)]
+struct MockAccount {
+    balance: i64,
+    sequence: i64,
+    last_modified_ledger: u32,
+    last_modified_time: String,
+}
+
+struct MockLedger {
+    sequence: u32,
+    hash: String,
+    prev_hash: String,
+    closed_at: String,
+    successful_transaction_count: u32,
+    failed_transaction_count: u32,
+    operation_count: u32,
+}
+
+struct MockTransaction {
+    id: i64,
+    hash: String,
+    ledger: u32,
+    successful: bool,
+    participants: Vec<String>,
+    record: Value,
+    operations: Vec<MockOperation>,
+    operation_codes: Vec<&'static str>,
+}
+
+struct MockOperation {
+    id: i64,
+    is_payment: bool,
+    participants: Vec<String>,
+    record: Value,
+}
+
+/// Result of one operation, with the codes of the `OperationResult` XDR
+struct OperationOutcome {
+    code: &'static str,
+    // opINNER is 0, the other values are failures before the operation was looked at
+    outer: i32,
+    // Operation type and its result code, if `outer` is opINNER
+    inner: Option<(i32, i32)>,
+    // The balance of the merged account, the payload of a successful account merge
+    merged_balance: Option<i64>,
+}
+
+/// A transaction rejected before it got into a ledger
+struct Rejection {
+    code: &'static str,
+    value: i32,
+}
+
+impl MockHorizon {
+    /// Starting the server for the network with the given passphrase, e.g. "Pi Testnet"
+    pub fn start(network_passphrase: &str) -> Result<Self, PiError> {
+        let now = timestamp(chrono::Utc::now());
+        let genesis = MockLedger {
+            sequence: 1,
+            hash: HEXLOWER.encode(&hash(network_passphrase.as_bytes())),
+            prev_hash: HEXLOWER.encode(&[0; 32]),
+            closed_at: now,
+            successful_transaction_count: 0,
+            failed_transaction_count: 0,
+            operation_count: 0,
+        };
+        let state = Arc::new(Mutex::new(HorizonState {
+            network: Network::new(network_passphrase.to_string()),
+            base_fee: 100,
+            base_reserve: STROOPS_PER_UNIT / 2,
+            fee_stats: None,
+            ledger_interval: None,
+            ledgers: vec![genesis],
+            accounts: HashMap::new(),
+            transactions: Vec::new(),
+            errors: Vec::new(),
+            requests: Vec::new(),
+        }));
+
+        let handler_state = state.clone();
+        let server = MockServer::start(Arc::new(move |request: &MockRequest| {
+            let mut state = handler_state
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            state.handle(request)
+        }))
+        .map_err(|e| PiError::Message(format!("Can't start the mock Horizon: {}", e)))?;
+
+        Ok(MockHorizon { server, state })
+    }
+
+    pub fn url(&self) -> String {
+        self.server.url()
+    }
+
+    /// Creating a funded account, like the friendbot does, the balance is in Pi e.g. "100.5"
+    pub fn create_account(&self, account_id: &str, balance: &str) -> Result<(), PiError> {
+        PublicKey::from_account_id(account_id)
+            .map_err(|e| PiError::Message(format!("Invalid account ID {}: {:?}", account_id, e)))?;
+        let balance = parse_amount(balance)?;
+        let mut state = self.state();
+        let account = state.new_account(balance);
+        state.accounts.insert(account_id.to_string(), account);
+        Ok(())
+    }
+
+    /// The native balance of the account, e.g. "99.9999900"
+    pub fn balance(&self, account_id: &str) -> Option<String> {
+        let state = self.state();
+        state
+            .accounts
+            .get(account_id)
+            .map(|account| format_amount(account.balance))
+    }
+
+    pub fn sequence(&self, account_id: &str) -> Option<i64> {
+        let state = self.state();
+        state
+            .accounts
+            .get(account_id)
+            .map(|account| account.sequence)
+    }
+
+    /// Setting the sequence number of the account, e.g. to simulate a transaction sent by someone else
+    pub fn set_sequence(&self, account_id: &str, sequence: i64) -> Result<(), PiError> {
+        let mut state = self.state();
+        let account = state
+            .accounts
+            .get_mut(account_id)
+            .ok_or_else(|| PiError::Message(format!("Unknown mock account {}", account_id)))?;
+        account.sequence = sequence;
+        Ok(())
+    }
+
+    /// Setting the minimum fee per operation in stroops, 100 by default
+    pub fn set_base_fee(&self, base_fee: i64) {
+        self.state().base_fee = base_fee;
+    }
+
+    /// Setting the base reserve in stroops, 0.5 Pi by default, an account has to keep 2 base reserves
+    pub fn set_base_reserve(&self, base_reserve: i64) {
+        self.state().base_reserve = base_reserve;
+    }
+
+    /// Serving these fee stats instead of the ones made from the base fee
+    pub fn set_fee_stats(&self, fee_stats: FeeStats) {
+        self.state().fee_stats = Some(fee_stats);
+    }
+
+    /// Closing an empty ledger whenever this much time passed without one, like the real network does every 5 seconds
+    /// By default a ledger is only closed when a transaction is included
+    pub fn set_ledger_interval(&self, interval: std::time::Duration) {
+        self.state().ledger_interval = chrono::Duration::from_std(interval).ok();
+    }
+
+    /// Sequence of the last closed ledger
+    pub fn latest_ledger(&self) -> u32 {
+        self.state().latest_ledger().sequence
+    }
+
+    /// The transaction included in a ledger with the given hash
+    pub fn transaction(&self, hash: &str) -> Option<Transaction> {
+        let state = self.state();
+        let transaction = state.transactions.iter().find(|tx| tx.hash == hash)?;
+        serde_json::from_value(transaction.record.clone()).ok()
+    }
+
+    /// Making the next matching requests fail, see at `MockError`
+    pub fn inject_error(&self, error: MockError) {
+        self.state().errors.push(error);
+    }
+
+    /// Number of the received requests whose "METHOD /path" contains the pattern
+    pub fn request_count(&self, pattern: &str) -> usize {
+        self.state()
+            .requests
+            .iter()
+            .filter(|request| request.contains(pattern))
+            .count()
+    }
+
+    fn state(&self) -> MutexGuard<'_, HorizonState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl HorizonState {
+    fn handle(&mut self, request: &MockRequest) -> MockResponse {
+        self.close_empty_ledgers();
+        let line = format!("{} {}", request.method, request.path);
+        self.requests.push(line.clone());
+        match MockError::take(&mut self.errors, &line) {
+            Some(error) if error.processed => {
+                self.route(request);
+                error.response
+            }
+            Some(error) => error.response,
+            None => self.route(request),
+        }
+    }
+
+    fn route(&mut self, request: &MockRequest) -> MockResponse {
+        let params = request.params();
+        let segments = request.segments();
+        if request.method == "POST" {
+            return match segments.as_slice() {
+                ["transactions"] => self.submit(&params),
+                _ => not_found(),
+            };
+        }
+
+        match segments.as_slice() {
+            [] => MockResponse::json(200, &self.root()),
+            ["fee_stats"] => MockResponse::json(200, &self.fee_stats()),
+            ["accounts"] => self.accounts(&params),
+            ["accounts", id] => match self.accounts.get(*id) {
+                Some(account) => MockResponse::json(200, &self.account_record(id, account)),
+                None => not_found(),
+            },
+            ["accounts", id, resource] => {
+                let id = id.to_string();
+                self.list(resource, &params, |participants, _| {
+                    participants.contains(&id)
+                })
+            }
+            ["transactions"] | ["operations"] | ["payments"] | ["ledgers"] => {
+                self.list(segments[0], &params, |_, _| true)
+            }
+            ["transactions", hash] => match self.transactions.iter().find(|tx| tx.hash == *hash) {
+                Some(transaction) => MockResponse::json(200, &transaction.record),
+                None => not_found(),
+            },
+            ["transactions", hash, resource] => {
+                let hash = hash.to_string();
+                match self.transactions.iter().find(|tx| tx.hash == hash) {
+                    Some(transaction) => {
+                        let ledger = transaction.ledger;
+                        let id = transaction.id;
+                        self.list(resource, &params, move |_, position| {
+                            position.ledger == ledger && position.transaction == id
+                        })
+                    }
+                    None => not_found(),
+                }
+            }
+            ["operations", id] => {
+                let operation = self
+                    .transactions
+                    .iter()
+                    .flat_map(|tx| &tx.operations)
+                    .find(|operation| operation.id.to_string() == *id);
+                match operation {
+                    Some(operation) => MockResponse::json(200, &operation.record),
+                    None => not_found(),
+                }
+            }
+            ["ledgers", sequence] => {
+                let ledger = self
+                    .ledgers
+                    .iter()
+                    .find(|ledger| ledger.sequence.to_string() == *sequence);
+                match ledger {
+                    Some(ledger) => MockResponse::json(200, &self.ledger_record(ledger)),
+                    None => not_found(),
+                }
+            }
+            ["ledgers", sequence, resource] => match sequence.parse::<u32>() {
+                Ok(sequence) if self.ledgers.iter().any(|l| l.sequence == sequence) => {
+                    self.list(resource, &params, move |_, position| {
+                        position.ledger == sequence
+                    })
+                }
+                _ => not_found(),
+            },
+            _ => not_found(),
+        }
+    }
+
+    fn root(&self) -> Value {
+        let latest = self.latest_ledger();
+        json!({
+            "horizon_version": "mock",
+            "core_version": "mock",
+            "history_latest_ledger": latest.sequence,
+            "history_latest_ledger_closed_at": latest.closed_at,
+            "history_elder_ledger": 1,
+            "core_latest_ledger": latest.sequence,
+            "network_passphrase": self.network.passphrase(),
+            "current_protocol_version": 19,
+            "core_supported_protocol_version": 19,
+        })
+    }
+
+    fn fee_stats(&self) -> Value {
+        if let Some(fee_stats) = &self.fee_stats {
+            return serde_json::to_value(fee_stats).unwrap_or_default();
+        }
+        let fee = self.base_fee.to_string();
+        let distribution = json!({
+            "max": fee, "min": fee, "mode": fee,
+            "p10": fee, "p20": fee, "p30": fee, "p40": fee, "p50": fee,
+            "p60": fee, "p70": fee, "p80": fee, "p90": fee, "p95": fee, "p99": fee,
+        });
+        json!({
+            "last_ledger": self.latest_ledger().sequence.to_string(),
+            "last_ledger_base_fee": fee,
+            "ledger_capacity_usage": "0.01",
+            "fee_charged": distribution,
+            "max_fee": distribution,
+        })
+    }
+
+    fn accounts(&self, params: &HashMap<String, String>) -> MockResponse {
+        // Only the master key can sign in the mock, so an account is only its own signer
+        let signer = match params.get("signer") {
+            Some(signer) => signer,
+            None => {
+                return bad_request(
+                    "One of the filters is required: signer, sponsor, asset or liquidity_pool",
+                )
+            }
+        };
+        let records: Vec<(i64, Value)> = self
+            .accounts
+            .get(signer)
+            .map(|account| (0, self.account_record(signer, account)))
+            .into_iter()
+            .collect();
+        page("/accounts", records, params)
+    }
+
+    /// Listing the transactions, operations, payments or ledgers, filtered by the participants or the position
+    fn list(
+        &self,
+        resource: &str,
+        params: &HashMap<String, String>,
+        filter: impl Fn(&[String], &Position) -> bool,
+    ) -> MockResponse {
+        let include_failed = params.get("include_failed").map(String::as_str) == Some("true");
+        let records: Vec<(i64, Value)> = match resource {
+            "transactions" => self
+                .transactions
+                .iter()
+                .filter(|tx| include_failed || tx.successful)
+                .filter(|tx| filter(&tx.participants, &Position::of_transaction(tx)))
+                .map(|tx| (tx.id, tx.record.clone()))
+                .collect(),
+            "operations" | "payments" => self
+                .transactions
+                .iter()
+                .filter(|tx| include_failed || tx.successful)
+                .flat_map(|tx| {
+                    tx.operations
+                        .iter()
+                        .map(move |operation| (Position::of_transaction(tx), operation))
+                })
+                .filter(|(_, operation)| resource == "operations" || operation.is_payment)
+                .filter(|(position, operation)| filter(&operation.participants, position))
+                .map(|(_, operation)| (operation.id, operation.record.clone()))
+                .collect(),
+            "ledgers" => self
+                .ledgers
+                .iter()
+                .map(|ledger| ((ledger.sequence as i64) << 32, self.ledger_record(ledger)))
+                .collect(),
+            _ => return not_found(),
+        };
+        page(&format!("/{}", resource), records, params)
+    }
+
+    fn submit(&mut self, params: &HashMap<String, String>) -> MockResponse {
+        let envelope_xdr = match params.get("tx") {
+            Some(tx) => tx.clone(),
+            None => return bad_request("The tx parameter is required"),
+        };
+        let envelope = match TransactionEnvelope::from_xdr_base64(&envelope_xdr) {
+            Ok(envelope) => envelope,
+            Err(_) => {
+                return horizon_error(
+                    400,
+                    "transaction_malformed",
+                    "Transaction Malformed",
+                    "Horizon could not decode the transaction envelope in this request.",
+                    None,
+                )
+            }
+        };
+        let transaction = match envelope.as_transaction() {
+            Some(transaction) => transaction,
+            None => {
+                let rejection = Rejection {
+                    code: "tx_not_supported",
+                    value: -12,
+                };
+                return transaction_failed(&envelope_xdr, 0, &rejection, &[]);
+            }
+        };
+        let hash = match transaction.hash(&self.network) {
+            Ok(hash) => hash,
+            Err(_) => return bad_request("The transaction can't be hashed"),
+        };
+        let hash_hex = HEXLOWER.encode(&hash);
+
+        // Horizon answers with the result of the transaction if it's already in a ledger
+        if let Some(existing) = self.transactions.iter().find(|tx| tx.hash == hash_hex) {
+            return if existing.successful {
+                MockResponse::json(200, &existing.record)
+            } else {
+                failed_included(&envelope_xdr, existing)
+            };
+        }
+
+        let fee_bid = transaction.fee().to_i64();
+        if let Err(rejection) = self.check(transaction, &hash) {
+            return transaction_failed(&envelope_xdr, fee_bid, &rejection, &[]);
+        }
+
+        let source = account_id(transaction.source_account());
+        let fee_charged = fee_bid.min(self.base_fee * transaction.operations().len() as i64);
+        let reserve = self.base_reserve;
+        let mut accounts = self.accounts.clone();
+        if let Some(account) = accounts.get_mut(&source) {
+            account.balance -= fee_charged;
+            account.sequence = *transaction.sequence();
+        }
+
+        // The operations are applied on a copy, which is dropped if any of them fails
+        let mut applied = accounts.clone();
+        let outcomes: Vec<OperationOutcome> = transaction
+            .operations()
+            .iter()
+            .map(|operation| {
+                let operation_source = operation
+                    .source_account()
+                    .as_ref()
+                    .map(account_id)
+                    .unwrap_or_else(|| source.clone());
+                if !is_signed_by(transaction, &hash, &operation_source) {
+                    return OperationOutcome::outer("op_bad_auth", -1);
+                }
+                apply_operation(&mut applied, operation, &operation_source, reserve)
+            })
+            .collect();
+        let successful = outcomes.iter().all(|outcome| outcome.code == "op_success");
+        self.accounts = if successful { applied } else { accounts };
+
+        self.close_ledger(transaction, &envelope_xdr, hash_hex, fee_charged, outcomes);
+        match self.transactions.last() {
+            Some(included) if included.successful => MockResponse::json(200, &included.record),
+            Some(included) => failed_included(&envelope_xdr, included),
+            None => not_found(),
+        }
+    }
+
+    /// The checks of stellar-core before a transaction gets into a ledger
+    fn check(&self, transaction: &TransactionSBase, hash: &[u8]) -> Result<(), Rejection> {
+        let operation_count = transaction.operations().len() as i64;
+        if operation_count == 0 {
+            return Err(Rejection {
+                code: "tx_missing_operation",
+                value: -4,
+            });
+        }
+
+        if let Some(time_bounds) = transaction.time_bounds() {
+            let now = chrono::Utc::now();
+            if time_bounds.lower().is_some_and(|lower| now < lower) {
+                return Err(Rejection {
+                    code: "tx_too_early",
+                    value: -2,
+                });
+            }
+            // An upper bound of 0 means no upper bound
+            if time_bounds
+                .upper()
+                .is_some_and(|upper| upper.timestamp() != 0 && now > upper)
+            {
+                return Err(Rejection {
+                    code: "tx_too_late",
+                    value: -3,
+                });
+            }
+        }
+
+        if transaction.fee().to_i64() < self.base_fee * operation_count {
+            return Err(Rejection {
+                code: "tx_insufficient_fee",
+                value: -9,
+            });
+        }
+
+        let source = account_id(transaction.source_account());
+        let account = self.accounts.get(&source).ok_or(Rejection {
+            code: "tx_no_source_account",
+            value: -8,
+        })?;
+        if *transaction.sequence() != account.sequence + 1 {
+            return Err(Rejection {
+                code: "tx_bad_seq",
+                value: -5,
+            });
+        }
+        if !is_signed_by(transaction, hash, &source) {
+            return Err(Rejection {
+                code: "tx_bad_auth",
+                value: -6,
+            });
+        }
+        if account.balance - self.base_reserve * 2 < transaction.fee().to_i64() {
+            return Err(Rejection {
+                code: "tx_insufficient_balance",
+                value: -7,
+            });
+        }
+        Ok(())
+    }
+
+    /// Closing a new ledger with the transaction
+    /// Catching up with the ledgers which would have been closed since the last one with the ledger interval
+    fn close_empty_ledgers(&mut self) {
+        let Some(interval) = self
+            .ledger_interval
+            .filter(|interval| *interval > chrono::Duration::zero())
+        else {
+            return;
+        };
+        loop {
+            let previous = self.latest_ledger();
+            let closed_at = match chrono::DateTime::parse_from_rfc3339(&previous.closed_at) {
+                Ok(closed_at) => closed_at.with_timezone(&chrono::Utc) + interval,
+                Err(_) => return,
+            };
+            if closed_at > chrono::Utc::now() {
+                return;
+            }
+            let ledger = MockLedger {
+                sequence: previous.sequence + 1,
+                hash: HEXLOWER.encode(&hash_of(&[previous.hash.as_bytes()])),
+                prev_hash: previous.hash.clone(),
+                closed_at: timestamp(closed_at),
+                successful_transaction_count: 0,
+                failed_transaction_count: 0,
+                operation_count: 0,
+            };
+            self.ledgers.push(ledger);
+        }
+    }
+
+    fn close_ledger(
+        &mut self,
+        transaction: &TransactionSBase,
+        envelope_xdr: &str,
+        hash: String,
+        fee_charged: i64,
+        outcomes: Vec<OperationOutcome>,
+    ) {
+        let successful = outcomes.iter().all(|outcome| outcome.code == "op_success");
+        let previous = self.latest_ledger();
+        let sequence = previous.sequence + 1;
+        let closed_at = timestamp(chrono::Utc::now());
+        let ledger_hash = HEXLOWER.encode(&hash_of(&[previous.hash.as_bytes(), hash.as_bytes()]));
+        let ledger = MockLedger {
+            sequence,
+            hash: ledger_hash,
+            prev_hash: previous.hash.clone(),
+            closed_at: closed_at.clone(),
+            successful_transaction_count: successful as u32,
+            failed_transaction_count: !successful as u32,
+            operation_count: outcomes.len() as u32,
+        };
+        self.ledgers.push(ledger);
+
+        let source = account_id(transaction.source_account());
+        // Accounts created by the transaction start with the sequence of this ledger
+        for account in self.accounts.values_mut() {
+            if account.last_modified_ledger == 0 {
+                account.sequence = (sequence as i64) << 32;
+            }
+        }
+        if let Some(account) = self.accounts.get_mut(&source) {
+            account.last_modified_ledger = sequence;
+            account.last_modified_time = closed_at.clone();
+        }
+
+        // Total order ID of Horizon: the ledger, the transaction in the ledger, then the operation in the transaction
+        let id = ((sequence as i64) << 32) | (1 << 12);
+        let mut participants = vec![source.clone()];
+        let mut operations = Vec::new();
+        for (index, operation) in transaction.operations().iter().enumerate() {
+            let operation_id = id | (index as i64 + 1);
+            let operation_source = operation
+                .source_account()
+                .as_ref()
+                .map(account_id)
+                .unwrap_or_else(|| source.clone());
+            let (record, operation_participants, is_payment) = operation_record(
+                operation,
+                operation_id,
+                &operation_source,
+                &hash,
+                &closed_at,
+                successful,
+            );
+            for participant in &operation_participants {
+                if let Some(account) = self.accounts.get_mut(participant) {
+                    account.last_modified_ledger = sequence;
+                    account.last_modified_time = closed_at.clone();
+                }
+                if !participants.contains(participant) {
+                    participants.push(participant.clone());
+                }
+            }
+            operations.push(MockOperation {
+                id: operation_id,
+                is_payment,
+                participants: operation_participants,
+                record,
+            });
+        }
+
+        let (memo, memo_bytes, memo_type) = memo_fields(transaction.memo());
+        let (valid_after, valid_before) = match transaction.time_bounds() {
+            Some(time_bounds) => (
+                time_bounds.lower().map(timestamp),
+                // An upper bound of 0 means no upper bound
+                time_bounds
+                    .upper()
+                    .filter(|upper| upper.timestamp() != 0)
+                    .map(timestamp),
+            ),
+            None => (None, None),
+        };
+        let href = |path: String| json!({ "href": path });
+        let record = json!({
+            "memo": memo,
+            "memo_bytes": memo_bytes,
+            "_links": {
+                "self": href(format!("/transactions/{}", hash)),
+                "account": href(format!("/accounts/{}", source)),
+                "ledger": href(format!("/ledgers/{}", sequence)),
+                "operations": href(format!("/transactions/{}/operations", hash)),
+                "effects": href(format!("/transactions/{}/effects", hash)),
+                "precedes": href(format!("/transactions?order=asc&cursor={}", id)),
+                "succeeds": href(format!("/transactions?order=desc&cursor={}", id)),
+                "transaction": href(format!("/transactions/{}", hash)),
+            },
+            "id": hash,
+            "paging_token": id.to_string(),
+            "successful": successful,
+            "hash": hash,
+            "ledger": sequence,
+            "created_at": closed_at,
+            "source_account": source,
+            "source_account_sequence": transaction.sequence().to_string(),
+            "fee_account": source,
+            "fee_charged": fee_charged.to_string(),
+            "max_fee": transaction.fee().to_i64().to_string(),
+            "operation_count": outcomes.len(),
+            "envelope_xdr": envelope_xdr,
+            "result_xdr": result_xdr(fee_charged, if successful { 0 } else { -1 }, &outcomes),
+            // Empty TransactionMeta and LedgerEntryChanges, the changes are not tracked
+            "result_meta_xdr": BASE64.encode(&[0; 8]),
+            "fee_meta_xdr": BASE64.encode(&[0; 4]),
+            "memo_type": memo_type,
+            "signatures": transaction
+                .signatures()
+                .iter()
+                .map(|signature| BASE64.encode(&signature.signature().to_bytes()))
+                .collect::<Vec<_>>(),
+            "valid_after": valid_after,
+            "valid_before": valid_before,
+        });
+
+        self.transactions.push(MockTransaction {
+            id,
+            hash,
+            ledger: sequence,
+            successful,
+            participants,
+            record,
+            operations,
+            operation_codes: outcomes.iter().map(|outcome| outcome.code).collect(),
+        });
+    }
+
+    fn new_account(&self, balance: i64) -> MockAccount {
+        let ledger = self.latest_ledger();
+        MockAccount {
+            balance,
+            // New accounts start at the sequence of the ledger they were created in, shifted like in stellar-core
+            sequence: (ledger.sequence as i64) << 32,
+            last_modified_ledger: ledger.sequence,
+            last_modified_time: ledger.closed_at.clone(),
+        }
+    }
+
+    fn latest_ledger(&self) -> &MockLedger {
+        // The genesis ledger is always there
+        &self.ledgers[self.ledgers.len() - 1]
+    }
+
+    fn account_record(&self, id: &str, account: &MockAccount) -> Value {
+        let href = |path: &str| json!({ "href": format!("/accounts/{}{}", id, path) });
+        let templated = |path: &str| json!({ "href": format!("/accounts/{}/{}{{?cursor,limit,order}}", id, path), "templated": true });
+        json!({
+            "_links": {
+                "self": href(""),
+                "transactions": templated("transactions"),
+                "operations": templated("operations"),
+                "payments": templated("payments"),
+                "effects": templated("effects"),
+                "offers": templated("offers"),
+                "trades": templated("trades"),
+            },
+            "id": id,
+            "account_id": id,
+            "sequence": account.sequence.to_string(),
+            "subentry_count": 0,
+            "inflation_destination": null,
+            "home_domain": null,
+            "last_modified_ledger": account.last_modified_ledger,
+            "last_modified_time": account.last_modified_time,
+            "thresholds": { "low_threshold": 0, "med_threshold": 0, "high_threshold": 0 },
+            "flags": {
+                "auth_required": false,
+                "auth_revocable": false,
+                "auth_immutable": false,
+                "auth_clawback_enabled": false,
+            },
+            "balances": [{
+                "balance": format_amount(account.balance),
+                "buying_liabilities": "0.0000000",
+                "selling_liabilities": "0.0000000",
+                "asset_type": "native",
+            }],
+            "signers": [{ "weight": 1, "key": id, "type": "ed25519_public_key" }],
+            "data": {},
+            "num_sponsoring": 0,
+            "num_sponsored": 0,
+            "paging_token": id,
+        })
+    }
+
+    fn ledger_record(&self, ledger: &MockLedger) -> Value {
+        let href = |path: &str| json!({ "href": format!("/ledgers/{}{}", ledger.sequence, path) });
+        let total_coins: i64 = self.accounts.values().map(|account| account.balance).sum();
+        let fee_pool: i64 = self
+            .transactions
+            .iter()
+            .filter_map(|tx| tx.record["fee_charged"].as_str()?.parse::<i64>().ok())
+            .sum();
+        json!({
+            "_links": {
+                "self": href(""),
+                "transactions": href("/transactions"),
+                "operations": href("/operations"),
+                "payments": href("/payments"),
+                "effects": href("/effects"),
+            },
+            "id": ledger.hash,
+            "paging_token": ((ledger.sequence as i64) << 32).to_string(),
+            "hash": ledger.hash,
+            "prev_hash": ledger.prev_hash,
+            "sequence": ledger.sequence,
+            "successful_transaction_count": ledger.successful_transaction_count,
+            "failed_transaction_count": ledger.failed_transaction_count,
+            "operation_count": ledger.operation_count,
+            "tx_set_operation_count": ledger.operation_count,
+            "closed_at": ledger.closed_at,
+            "total_coins": format_amount(total_coins),
+            "fee_pool": format_amount(fee_pool),
+            "base_fee_in_stroops": self.base_fee,
+            "base_reserve_in_stroops": self.base_reserve,
+            "max_tx_set_size": 1000,
+            "protocol_version": 19,
+            "header_xdr": "",
+        })
+    }
+}
+
+/// Where a record is on the ledger, to filter the operations of a transaction or a ledger
+struct Position {
+    ledger: u32,
+    transaction: i64,
+}
+
+impl Position {
+    fn of_transaction(transaction: &MockTransaction) -> Self {
+        Position {
+            ledger: transaction.ledger,
+            transaction: transaction.id,
+        }
+    }
+}
+
+impl OperationOutcome {
+    fn inner(code: &'static str, operation_type: i32, value: i32) -> Self {
+        OperationOutcome {
+            code,
+            outer: 0,
+            inner: Some((operation_type, value)),
+            merged_balance: None,
+        }
+    }
+
+    fn outer(code: &'static str, value: i32) -> Self {
+        OperationOutcome {
+            code,
+            outer: value,
+            inner: None,
+            merged_balance: None,
+        }
+    }
+}
+
+/// Applying a native payment, an account creation or an account merge on the accounts
+fn apply_operation(
+    accounts: &mut HashMap<String, MockAccount>,
+    operation: &Operation,
+    source: &str,
+    base_reserve: i64,
+) -> OperationOutcome {
+    let available = match accounts.get(source) {
+        Some(account) => account.balance - base_reserve * 2,
+        None => return OperationOutcome::outer("op_no_source_account", -2),
+    };
+
+    match operation {
+        Operation::Payment(payment) => {
+            let amount = payment.amount().to_i64();
+            let destination = account_id(payment.destination());
+            if amount <= 0 {
+                return OperationOutcome::inner("op_malformed", 1, -1);
+            }
+            if !payment.asset().is_native() {
+                return OperationOutcome::inner("op_src_no_trust", 1, -3);
+            }
+            if !accounts.contains_key(&destination) {
+                return OperationOutcome::inner("op_no_destination", 1, -5);
+            }
+            if available < amount {
+                return OperationOutcome::inner("op_underfunded", 1, -2);
+            }
+            if let Some(account) = accounts.get_mut(source) {
+                account.balance -= amount;
+            }
+            if let Some(account) = accounts.get_mut(&destination) {
+                account.balance += amount;
+            }
+            OperationOutcome::inner("op_success", 1, 0)
+        }
+        Operation::CreateAccount(create_account) => {
+            let amount = create_account.starting_balance().to_i64();
+            let destination = create_account.destination().account_id();
+            if amount <= 0 {
+                return OperationOutcome::inner("op_malformed", 0, -1);
+            }
+            if available < amount {
+                return OperationOutcome::inner("op_underfunded", 0, -2);
+            }
+            if amount < base_reserve * 2 {
+                return OperationOutcome::inner("op_low_reserve", 0, -3);
+            }
+            if accounts.contains_key(&destination) {
+                return OperationOutcome::inner("op_already_exists", 0, -4);
+            }
+            if let Some(account) = accounts.get_mut(source) {
+                account.balance -= amount;
+            }
+            accounts.insert(
+                destination,
+                MockAccount {
+                    balance: amount,
+                    sequence: 0,
+                    last_modified_ledger: 0,
+                    last_modified_time: String::new(),
+                },
+            );
+            OperationOutcome::inner("op_success", 0, 0)
+        }
+        Operation::AccountMerge(account_merge) => {
+            let destination = account_id(account_merge.destination());
+            if destination == source {
+                return OperationOutcome::inner("op_malformed", 8, -1);
+            }
+            if !accounts.contains_key(&destination) {
+                return OperationOutcome::inner("op_no_account", 8, -2);
+            }
+            let balance = accounts.remove(source).map_or(0, |account| account.balance);
+            if let Some(account) = accounts.get_mut(&destination) {
+                account.balance += balance;
+            }
+            OperationOutcome {
+                merged_balance: Some(balance),
+                ..OperationOutcome::inner("op_success", 8, 0)
+            }
+        }
+        _ => OperationOutcome::outer("op_not_supported", -3),
+    }
+}
+
+/// The JSON record of an operation, its participants and whether it's listed under `/payments`
+fn operation_record(
+    operation: &Operation,
+    id: i64,
+    source: &str,
+    hash: &str,
+    created_at: &str,
+    successful: bool,
+) -> (Value, Vec<String>, bool) {
+    let href = |path: String| json!({ "href": path });
+    let mut record = json!({
+        "_links": {
+            "self": href(format!("/operations/{}", id)),
+            "transaction": href(format!("/transactions/{}", hash)),
+            "effects": href(format!("/operations/{}/effects", id)),
+            "succeeds": href(format!("/effects?order=desc&cursor={}", id)),
+            "precedes": href(format!("/effects?order=asc&cursor={}", id)),
+        },
+        "id": id.to_string(),
+        "paging_token": id.to_string(),
+        "transaction_successful": successful,
+        "source_account": source,
+        "created_at": created_at,
+        "transaction_hash": hash,
+    });
+    let mut participants = vec![source.to_string()];
+
+    let (operation_type, type_i, is_payment) = match operation {
+        Operation::CreateAccount(create_account) => {
+            let account = create_account.destination().account_id();
+            record["starting_balance"] =
+                json!(format_amount(create_account.starting_balance().to_i64()));
+            record["funder"] = json!(source);
+            record["account"] = json!(account);
+            participants.push(account);
+            ("create_account", 0, true)
+        }
+        Operation::Payment(payment) => {
+            let to = account_id(payment.destination());
+            record["asset_type"] = json!(if payment.asset().is_native() {
+                "native"
+            } else {
+                "credit_alphanum4"
+            });
+            record["from"] = json!(source);
+            record["to"] = json!(to);
+            record["amount"] = json!(format_amount(payment.amount().to_i64()));
+            participants.push(to);
+            ("payment", 1, true)
+        }
+        Operation::PathPaymentStrictReceive(_) => ("path_payment_strict_receive", 2, true),
+        Operation::ManageSellOffer(_) => ("manage_sell_offer", 3, false),
+        Operation::CreatePassiveSellOffer(_) => ("create_passive_sell_offer", 4, false),
+        Operation::SetOptions(_) => ("set_options", 5, false),
+        Operation::ChangeTrust(_) => ("change_trust", 6, false),
+        Operation::AllowTrust(_) => ("allow_trust", 7, false),
+        Operation::AccountMerge(account_merge) => {
+            let into = account_id(account_merge.destination());
+            record["account"] = json!(source);
+            record["into"] = json!(into);
+            participants.push(into);
+            ("account_merge", 8, true)
+        }
+        Operation::Inflation(_) => ("inflation", 9, false),
+        Operation::ManageData(_) => ("manage_data", 10, false),
+        Operation::BumpSequence(_) => ("bump_sequence", 11, false),
+        Operation::ManageBuyOffer(_) => ("manage_buy_offer", 12, false),
+        Operation::PathPaymentStrictSend(_) => ("path_payment_strict_send", 13, true),
+        Operation::CreateClaimableBalance(_) => ("create_claimable_balance", 14, false),
+        Operation::ClaimClaimableBalance(_) => ("claim_claimable_balance", 15, false),
+        Operation::BeginSponsoringFutureReserves(_) => {
+            ("begin_sponsoring_future_reserves", 16, false)
+        }
+        Operation::EndSponsoringFutureReserves(_) => ("end_sponsoring_future_reserves", 17, false),
+        Operation::RevokeSponsorship(_) => ("revoke_sponsorship", 18, false),
+        Operation::Clawback(_) => ("clawback", 19, false),
+        Operation::ClawbackClaimableBalance(_) => ("clawback_claimable_balance", 20, false),
+        Operation::SetTrustLineFlags(_) => ("set_trust_line_flags", 21, false),
+        Operation::LiquidityPoolDeposit(_) => ("liquidity_pool_deposit", 22, false),
+        Operation::LiquidityPoolWithdraw(_) => ("liquidity_pool_withdraw", 23, false),
+    };
+    record["type"] = json!(operation_type);
+    record["type_i"] = json!(type_i);
+    (record, participants, is_payment)
+}
+
+/// True if the transaction has a valid signature of the account's master key
+fn is_signed_by(transaction: &TransactionSBase, hash: &[u8], account_id: &str) -> bool {
+    let keypair = match Keypair::from_public_key(account_id) {
+        Ok(keypair) => keypair,
+        Err(_) => return false,
+    };
+    let public_key = keypair.raw_public_key();
+    let hint = &public_key[public_key.len() - 4..];
+    transaction.signatures().iter().any(|signature| {
+        signature.hint().to_vec() == hint && keypair.verify(hash, &signature.signature().to_bytes())
+    })
+}
+
+/// Encoding the `TransactionResult` XDR, only the result codes are in it, the operation results carry no data
+fn result_xdr(fee_charged: i64, code: i32, outcomes: &[OperationOutcome]) -> String {
+    let mut xdr = Vec::new();
+    xdr.extend_from_slice(&fee_charged.to_be_bytes());
+    xdr.extend_from_slice(&code.to_be_bytes());
+    // Only txSUCCESS and txFAILED have the operation results
+    if code == 0 || code == -1 {
+        xdr.extend_from_slice(&(outcomes.len() as u32).to_be_bytes());
+        for outcome in outcomes {
+            xdr.extend_from_slice(&outcome.outer.to_be_bytes());
+            if let Some((operation_type, value)) = outcome.inner {
+                xdr.extend_from_slice(&operation_type.to_be_bytes());
+                xdr.extend_from_slice(&value.to_be_bytes());
+            }
+            if let Some(balance) = outcome.merged_balance {
+                xdr.extend_from_slice(&balance.to_be_bytes());
+            }
+        }
+    }
+    // Extension point
+    xdr.extend_from_slice(&0i32.to_be_bytes());
+    BASE64.encode(&xdr)
+}
+
+fn failed_body(envelope_xdr: &str, result_xdr: &str, code: &str, operations: &[&str]) -> Value {
+    let mut result_codes = json!({ "transaction": code });
+    if !operations.is_empty() {
+        result_codes["operations"] = json!(operations);
+    }
+    json!({
+        "type": "https://stellar.org/horizon-errors/transaction_failed",
+        "title": "Transaction Failed",
+        "status": 400,
+        "detail": "The transaction failed when submitted to the stellar network. The `extras.result_codes` field on this response contains further details.  Descriptions of each code can be found at: https://developers.stellar.org/api/errors/http-status-codes/horizon-specific/transaction-failed/",
+        "extras": {
+            "envelope_xdr": envelope_xdr,
+            "result_codes": result_codes,
+            "result_xdr": result_xdr,
+        },
+    })
+}
+
+fn transaction_failed(
+    envelope_xdr: &str,
+    fee_charged: i64,
+    rejection: &Rejection,
+    operations: &[&str],
+) -> MockResponse {
+    let result = result_xdr(fee_charged, rejection.value, &[]);
+    MockResponse::json(
+        400,
+        &failed_body(envelope_xdr, &result, rejection.code, operations),
+    )
+}
+
+/// The error of a failed transaction which is already in a ledger
+fn failed_included(envelope_xdr: &str, transaction: &MockTransaction) -> MockResponse {
+    let result = transaction.record["result_xdr"]
+        .as_str()
+        .unwrap_or_default();
+    MockResponse::json(
+        400,
+        &failed_body(
+            envelope_xdr,
+            result,
+            "tx_failed",
+            &transaction.operation_codes,
+        ),
+    )
+}
+
+/// A page of records in the `_embedded.records` format, with the `order`, `limit` and `cursor` parameters of Horizon
+fn page(
+    path: &str,
+    mut records: Vec<(i64, Value)>,
+    params: &HashMap<String, String>,
+) -> MockResponse {
+    let limit = match params.get("limit").map(|limit| limit.parse::<usize>()) {
+        None => 10,
+        Some(Ok(limit)) if (1..=MAX_PAGE_LIMIT).contains(&limit) => limit,
+        Some(_) => return bad_request("The limit must be between 1 and 200"),
+    };
+    let descending = match params.get("order").map(String::as_str) {
+        None | Some("asc") => false,
+        Some("desc") => true,
+        Some(_) => return bad_request("The order must be asc or desc"),
+    };
+    let cursor = match params.get("cursor").map(String::as_str) {
+        None | Some("now") => None,
+        Some(cursor) => match cursor.parse::<i64>() {
+            Ok(cursor) => Some(cursor),
+            Err(_) => return bad_request("The cursor must be a paging token"),
+        },
+    };
+
+    records.sort_by_key(|(id, _)| *id);
+    if descending {
+        records.reverse();
+    }
+    let records: Vec<(i64, Value)> = records
+        .into_iter()
+        .filter(|(id, _)| match cursor {
+            Some(cursor) if descending => *id < cursor,
+            Some(cursor) => *id > cursor,
+            None => true,
+        })
+        .take(limit)
+        .collect();
+
+    let order = if descending { "desc" } else { "asc" };
+    let link = |cursor: Option<i64>, order: &str| {
+        let cursor = cursor.map(|c| c.to_string()).unwrap_or_default();
+        json!({ "href": format!("{}?cursor={}&limit={}&order={}", path, cursor, limit, order) })
+    };
+    let first = records.first().map(|(id, _)| *id);
+    let last = records.last().map(|(id, _)| *id);
+    let reverse = if descending { "asc" } else { "desc" };
+    MockResponse::json(
+        200,
+        &json!({
+            "_links": {
+                "self": link(cursor, order),
+                "next": link(last.or(cursor), order),
+                "prev": link(first.or(cursor), reverse),
+            },
+            "_embedded": {
+                "records": records.into_iter().map(|(_, record)| record).collect::<Vec<_>>(),
+            },
+        }),
+    )
+}
+
+fn horizon_error(
+    status: u16,
+    kind: &str,
+    title: &str,
+    detail: &str,
+    extras: Option<Value>,
+) -> MockResponse {
+    let mut body = json!({
+        "type": format!("https://stellar.org/horizon-errors/{}", kind),
+        "title": title,
+        "status": status,
+        "detail": detail,
+    });
+    if let Some(extras) = extras {
+        body["extras"] = extras;
+    }
+    MockResponse::json(status, &body).with_header("Content-Type", "application/problem+json")
+}
+
+fn not_found() -> MockResponse {
+    horizon_error(
+        404,
+        "not_found",
+        "Resource Missing",
+        "The resource at the url requested was not found.  This usually occurs for one of two reasons:  The url requested is not valid, or no data in our database could be found with the parameters provided.",
+        None,
+    )
+}
+
+fn bad_request(detail: &str) -> MockResponse {
+    horizon_error(400, "bad_request", "Bad Request", detail, None)
+}
+
+/// The G... account ID of an account, also of a muxed one
+fn account_id(account: &MuxedAccount) -> String {
+    match account {
+        MuxedAccount::Ed25519(public_key) => public_key.account_id(),
+        MuxedAccount::MuxedEd25519(muxed) => muxed.public_key().account_id(),
+    }
+}
+
+/// The `memo`, `memo_bytes` and `memo_type` fields of a transaction record
+fn memo_fields(memo: &Memo) -> (Option<String>, Option<String>, &'static str) {
+    match memo {
+        Memo::None => (None, None, "none"),
+        Memo::Text(text) => (
+            Some(text.clone()),
+            Some(BASE64.encode(text.as_bytes())),
+            "text",
+        ),
+        Memo::Id(id) => (Some(id.to_string()), None, "id"),
+        Memo::Hash(hash) => (Some(BASE64.encode(hash)), None, "hash"),
+        Memo::Return(hash) => (Some(BASE64.encode(hash)), None, "return"),
+    }
+}
+
+fn hash_of(parts: &[&[u8]]) -> Vec<u8> {
+    hash(&parts.concat())
+}
+
+fn timestamp(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+fn parse_amount(amount: &str) -> Result<i64, PiError> {
+    Amount::from_str(amount)
+        .and_then(|amount| amount.to_stroops())
+        .map(|stroops| stroops.to_i64())
+        .map_err(|e| PiError::Message(format!("Invalid amount {}: {:?}", amount, e)))
+}
+
+fn format_amount(stroops: i64) -> String {
+    let sign = if stroops < 0 { "-" } else { "" };
+    let stroops = stroops.unsigned_abs();
+    let unit = STROOPS_PER_UNIT as u64;
+    format!("{}{}.{:07}", sign, stroops / unit, stroops % unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_base::transaction_result::TransactionResult;
+
+    #[test]
+    fn test_result_xdr() {
+        let outcomes = vec![
+            OperationOutcome::inner("op_success", 1, 0),
+            OperationOutcome::inner("op_underfunded", 1, -2),
+            OperationOutcome::outer("op_not_supported", -3),
+        ];
+        let failed = TransactionResult::from_xdr_base64(&result_xdr(200, -1, &outcomes)).unwrap();
+        assert_eq!(failed.as_failed().unwrap().results.len(), 3);
+
+        let success =
+            TransactionResult::from_xdr_base64(&result_xdr(100, 0, &outcomes[..1])).unwrap();
+        assert!(success.is_success());
+        let bad_seq = TransactionResult::from_xdr_base64(&result_xdr(100, -5, &[])).unwrap();
+        assert!(bad_seq.is_bad_sequence());
+    }
+
+    #[test]
+    fn test_amounts() {
+        assert_eq!(format_amount(25_000_000), "2.5000000");
+        assert_eq!(format_amount(1), "0.0000001");
+        assert_eq!(format_amount(-10_000_000), "-1.0000000");
+        assert_eq!(parse_amount("100.5").unwrap(), 1_005_000_000);
+        assert!(parse_amount("lots").is_err());
+    }
+
+    #[test]
+    fn test_page() {
+        let records: Vec<(i64, Value)> = (1..=5).map(|id| (id, json!({ "id": id }))).collect();
+        let params = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        let ids = |response: MockResponse| {
+            let body: Value = serde_json::from_slice(&response.body).unwrap();
+            body["_embedded"]["records"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|record| record["id"].as_i64().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ids(page("/x", records.clone(), &params(&[("limit", "2")]))),
+            [1, 2]
+        );
+        assert_eq!(
+            ids(page(
+                "/x",
+                records.clone(),
+                &params(&[("order", "desc"), ("cursor", "4")])
+            )),
+            [3, 2, 1]
+        );
+        assert_eq!(
+            page("/x", records, &params(&[("limit", "201")])).status,
+            400
+        );
+    }
+}
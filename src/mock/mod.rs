@@ -0,0 +1,80 @@
+//! Local stand-ins of the remote services, available with the `mock` cargo feature
+//!
+//! They are small HTTP servers on a random local port, keeping their state in memory, so the apps using this crate
+//! can test their payment flows offline and deterministically. Every stand-in stops when it's dropped.
+//!
+//! - `MockPiApi`: the Pi Platform API, point `ReqwestClientOptions::base_url` at its `url()`
+//! - `MockHorizon`: Horizon with an in-memory ledger, point `ReqwestClientOptions::horizon_url` or `Server::new` at its `url()`
+//! - `MockSigner`: a signing process holding the app wallet's keypair, point `RemoteSigner` at it
+
+mod horizon;
+mod http;
+mod pi_api;
+mod signer;
+
+pub use horizon::MockHorizon;
+pub use http::{MockRequest, MockResponse};
+pub use pi_api::MockPiApi;
+pub use signer::MockSigner;
+
+/// Error injected into a mock server, the next matching requests get this response instead of the normal one
+///
+/// - pattern: String - the request matches if its "METHOD /path" contains this, e.g. "POST /v2/payments"
+/// - response: MockResponse - the response sent instead
+/// - times: usize - how many requests get it, 1 by default
+/// - processed: bool - the request is still handled before the response is replaced, like when the server did the work
+///   but the response was lost, false by default
+
+#[derive(Debug, Clone)]
+pub struct MockError {
+    pub pattern: String,
+    pub response: MockResponse,
+    pub times: usize,
+    pub processed: bool,
+}
+
+impl MockError {
+    /// Responding with the status code and the `{"error": code, "error_message": message}` JSON body once
+    pub fn new(pattern: &str, status: u16, code: &str, message: &str) -> Self {
+        MockError {
+            pattern: pattern.to_string(),
+            response: pi_api::error(status, code, message),
+            times: 1,
+            processed: false,
+        }
+    }
+
+    /// Responding with any response once
+    pub fn response(pattern: &str, response: MockResponse) -> Self {
+        MockError {
+            pattern: pattern.to_string(),
+            response,
+            times: 1,
+            processed: false,
+        }
+    }
+
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = times;
+        self
+    }
+
+    pub fn after_processing(mut self) -> Self {
+        self.processed = true;
+        self
+    }
+
+    /// Taking the first injected error matching the request line
+    pub(crate) fn take(errors: &mut Vec<MockError>, line: &str) -> Option<MockError> {
+        let index = errors
+            .iter()
+            .position(|error| line.contains(&error.pattern))?;
+        let error = &mut errors[index];
+        let taken = error.clone();
+        error.times = error.times.saturating_sub(1);
+        if error.times == 0 {
+            errors.remove(index);
+        }
+        Some(taken)
+    }
+}
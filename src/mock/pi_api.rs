@@ -0,0 +1,390 @@
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::http::{MockRequest, MockResponse, MockServer};
+use super::MockError;
+use crate::types::{
+    Direction, NetworkPassphrase, PaymentArgs, PaymentDTO, PaymentDTOStatus, PaymentDTOTransaction,
+    PiError, UserCredentials, UserCredentialsValidUntil, UserDTO,
+};
+
+/// Local stand-in of the Pi Platform API, available with the `mock` cargo feature
+///
+/// Serves `/v2/payments` (create, get, approve, complete, cancel, incomplete_server_payments) and `/v2/me`
+/// from memory, with the same state transitions and error bodies as the real API, so the whole A2U and U2A
+/// lifecycle can be tested offline. Point `ReqwestClientOptions::base_url` at `url()`.
+/// The server runs on its own threads and stops when it's dropped.
+///
+/// ```ignore
+/// let mock = MockPiApi::start("api_key", &app_wallet_address)?;
+/// let access_token = mock.add_user("user_uid", Some("username"), &user_wallet_address);
+/// let options = ReqwestClientOptions { base_url: mock.url(), ..Default::default() };
+/// let pi = PiNetwork::new("api_key".to_string(), app_wallet_seed, None, Some(options))?;
+/// ```
+pub struct MockPiApi {
+    server: MockServer,
+    state: Arc<Mutex<PiApiState>>,
+}
+
+struct PiApiState {
+    api_key: String,
+    app_wallet: String,
+    network: NetworkPassphrase,
+    payments: HashMap<String, PaymentDTO>,
+    // Payment IDs in creation order
+    order: Vec<String>,
+    users: HashMap<String, MockUser>,
+    errors: Vec<MockError>,
+    requests: Vec<String>,
+}
+
+struct MockUser {
+    user: UserDTO,
+    access_token: String,
+    wallet: String,
+}
+
+impl MockPiApi {
+    /// Starting the server for the app with the given API key and app wallet, on the testnet
+    pub fn start(api_key: &str, app_wallet: &str) -> Result<Self, PiError> {
+        let state = Arc::new(Mutex::new(PiApiState {
+            api_key: api_key.to_string(),
+            app_wallet: app_wallet.to_string(),
+            network: NetworkPassphrase::PiTestnet,
+            payments: HashMap::new(),
+            order: Vec::new(),
+            users: HashMap::new(),
+            errors: Vec::new(),
+            requests: Vec::new(),
+        }));
+
+        let handler_state = state.clone();
+        let server = MockServer::start(Arc::new(move |request: &MockRequest| {
+            let mut state = handler_state
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            state.handle(request)
+        }))
+        .map_err(|e| PiError::Message(format!("Can't start the mock Pi API: {}", e)))?;
+
+        Ok(MockPiApi { server, state })
+    }
+
+    /// The base URL to use in `ReqwestClientOptions::base_url`
+    pub fn url(&self) -> String {
+        self.server.url()
+    }
+
+    /// Setting the network of the payments created from now on
+    pub fn set_network(&self, network: NetworkPassphrase) {
+        self.state().network = network;
+    }
+
+    /// Registering a user of the app with their wallet, returns the access token for `authenticate_user`
+    pub fn add_user(&self, uid: &str, username: Option<&str>, wallet: &str) -> String {
+        let access_token = random_id();
+        let user = UserDTO {
+            uid: uid.to_string(),
+            username: username.map(str::to_string),
+            credentials: UserCredentials {
+                scopes: vec![
+                    "username".to_string(),
+                    "payments".to_string(),
+                    "wallet_address".to_string(),
+                ],
+                valid_until: UserCredentialsValidUntil {
+                    timestamp: chrono::Utc::now().timestamp() + 3600,
+                    iso8601: (chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339(),
+                },
+            },
+        };
+        self.state().users.insert(
+            uid.to_string(),
+            MockUser {
+                user,
+                access_token: access_token.clone(),
+                wallet: wallet.to_string(),
+            },
+        );
+        access_token
+    }
+
+    /// Creating a U2A payment like the frontend's `Pi.createPayment` does, it's waiting for the server approval
+    pub fn create_user_payment(&self, args: PaymentArgs) -> Result<PaymentDTO, PiError> {
+        let mut state = self.state();
+        let wallet = state
+            .users
+            .get(&args.uid)
+            .map(|user| user.wallet.clone())
+            .ok_or_else(|| PiError::Message(format!("Unknown mock user {}", args.uid)))?;
+        let app_wallet = state.app_wallet.clone();
+        Ok(state.insert_payment(args, wallet, app_wallet, Direction::UserToApp, false))
+    }
+
+    /// Linking a blockchain transaction to a payment, like the Pi servers do when they see the user's transaction
+    pub fn link_transaction(&self, payment_id: &str, txid: &str) -> Result<PaymentDTO, PiError> {
+        let mut state = self.state();
+        let payment = state
+            .payments
+            .get_mut(payment_id)
+            .ok_or_else(|| PiError::Message(format!("Unknown mock payment {}", payment_id)))?;
+        payment.transaction = Some(transaction(txid));
+        payment.status.transaction_verified = true;
+        Ok(payment.clone())
+    }
+
+    /// Cancelling a payment like the user does on the frontend
+    pub fn cancel_by_user(&self, payment_id: &str) -> Result<PaymentDTO, PiError> {
+        let mut state = self.state();
+        let payment = state
+            .payments
+            .get_mut(payment_id)
+            .ok_or_else(|| PiError::Message(format!("Unknown mock payment {}", payment_id)))?;
+        payment.status.user_cancelled = true;
+        Ok(payment.clone())
+    }
+
+    pub fn payment(&self, payment_id: &str) -> Option<PaymentDTO> {
+        self.state().payments.get(payment_id).cloned()
+    }
+
+    /// Making the next matching requests fail, see at `MockError`
+    pub fn inject_error(&self, error: MockError) {
+        self.state().errors.push(error);
+    }
+
+    /// Number of the received requests whose "METHOD /path" contains the pattern
+    pub fn request_count(&self, pattern: &str) -> usize {
+        self.state()
+            .requests
+            .iter()
+            .filter(|request| request.contains(pattern))
+            .count()
+    }
+
+    fn state(&self) -> MutexGuard<'_, PiApiState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl PiApiState {
+    fn handle(&mut self, request: &MockRequest) -> MockResponse {
+        let line = format!("{} {}", request.method, request.path);
+        self.requests.push(line.clone());
+        match MockError::take(&mut self.errors, &line) {
+            Some(error) if error.processed => {
+                self.route(request);
+                error.response
+            }
+            Some(error) => error.response,
+            None => self.route(request),
+        }
+    }
+
+    fn route(&mut self, request: &MockRequest) -> MockResponse {
+        let segments = request.segments();
+        if segments == ["v2", "me"] {
+            return self.me(request);
+        }
+        if request.header("authorization") != Some(&format!("Key {}", self.api_key)) {
+            return error(401, "unauthorized", "Invalid API key");
+        }
+
+        match (request.method.as_str(), segments.as_slice()) {
+            ("POST", ["v2", "payments"]) => self.create_payment(request),
+            ("GET", ["v2", "payments", "incomplete_server_payments"]) => {
+                let payments: Vec<&PaymentDTO> = self
+                    .order
+                    .iter()
+                    .filter_map(|id| self.payments.get(id))
+                    .filter(|payment| is_incomplete_server_payment(payment))
+                    .collect();
+                MockResponse::json(200, &json!({ "incomplete_server_payments": payments }))
+            }
+            ("GET", ["v2", "payments", id]) => match self.payments.get(*id) {
+                Some(payment) => MockResponse::json(200, payment),
+                None => not_found(id),
+            },
+            ("POST", ["v2", "payments", id, action]) => {
+                let body: Value = serde_json::from_slice(&request.body).unwrap_or(Value::Null);
+                match self.payments.get_mut(*id) {
+                    Some(payment) => transition(payment, action, &body),
+                    None => not_found(id),
+                }
+            }
+            _ => error(404, "not_found", "Unknown endpoint"),
+        }
+    }
+
+    fn me(&self, request: &MockRequest) -> MockResponse {
+        let token = request
+            .header("authorization")
+            .and_then(|header| header.strip_prefix("Bearer "));
+        match self
+            .users
+            .values()
+            .find(|user| Some(user.access_token.as_str()) == token)
+        {
+            Some(user) => MockResponse::json(200, &user.user),
+            None => error(401, "unauthorized", "Invalid access token"),
+        }
+    }
+
+    fn create_payment(&mut self, request: &MockRequest) -> MockResponse {
+        #[derive(serde::Deserialize)]
+        struct Body {
+            payment: PaymentArgs,
+        }
+        let args = match serde_json::from_slice::<Body>(&request.body) {
+            Ok(body) => body.payment,
+            Err(e) => return error(400, "invalid_payment", &e.to_string()),
+        };
+        let wallet = match self.users.get(&args.uid) {
+            Some(user) => user.wallet.clone(),
+            None => {
+                return error(
+                    400,
+                    "invalid_user",
+                    "The user hasn't authenticated with the app",
+                )
+            }
+        };
+        let ongoing = self
+            .payments
+            .values()
+            .any(|payment| payment.user_uid == args.uid && is_incomplete_server_payment(payment));
+        if ongoing {
+            return error(
+                400,
+                "ongoing_payment_found",
+                "You need to complete the ongoing payment first to create a new one.",
+            );
+        }
+
+        let app_wallet = self.app_wallet.clone();
+        let payment = self.insert_payment(args, app_wallet, wallet, Direction::AppToUser, true);
+        MockResponse::json(200, &payment)
+    }
+
+    fn insert_payment(
+        &mut self,
+        args: PaymentArgs,
+        from_address: String,
+        to_address: String,
+        direction: Direction,
+        approved: bool,
+    ) -> PaymentDTO {
+        let payment = PaymentDTO {
+            identifier: random_id(),
+            user_uid: args.uid,
+            amount: args.amount,
+            memo: args.memo,
+            metadata: args.metadata,
+            from_address,
+            to_address,
+            direction,
+            status: PaymentDTOStatus {
+                developer_approved: approved,
+                transaction_verified: false,
+                developer_completed: false,
+                cancelled: false,
+                user_cancelled: false,
+            },
+            transaction: None,
+            created_at: chrono::Utc::now(),
+            network: self.network.clone(),
+        };
+        self.order.push(payment.identifier.clone());
+        self.payments
+            .insert(payment.identifier.clone(), payment.clone());
+        payment
+    }
+}
+
+/// Applying approve, complete or cancel on the payment, with the errors of the real API
+fn transition(payment: &mut PaymentDTO, action: &str, body: &Value) -> MockResponse {
+    let status = &payment.status;
+    if status.cancelled || status.user_cancelled {
+        return error(400, "payment_cancelled", "The payment is cancelled");
+    }
+    if status.developer_completed {
+        return error(400, "already_completed", "The payment is already completed");
+    }
+
+    match action {
+        "approve" => {
+            if payment.direction != Direction::UserToApp || status.developer_approved {
+                return error(400, "already_approved", "The payment is already approved");
+            }
+            payment.status.developer_approved = true;
+        }
+        "complete" => {
+            let txid = match body.get("txid").and_then(Value::as_str) {
+                Some(txid) if !txid.is_empty() => txid,
+                _ => return error(400, "invalid_txid", "The txid is missing"),
+            };
+            if !status.developer_approved {
+                return error(400, "payment_not_approved", "The payment is not approved");
+            }
+            match &payment.transaction {
+                Some(linked) if linked.txid != txid => {
+                    return error(
+                        400,
+                        "txid_mismatch",
+                        "The txid is not the one linked to the payment",
+                    )
+                }
+                Some(_) => {}
+                None if payment.direction == Direction::UserToApp => {
+                    return error(400, "transaction_not_verified", "No transaction is linked")
+                }
+                None => payment.transaction = Some(transaction(txid)),
+            }
+            payment.status.transaction_verified = true;
+            payment.status.developer_completed = true;
+        }
+        "cancel" => payment.status.cancelled = true,
+        _ => return error(404, "not_found", "Unknown endpoint"),
+    }
+    MockResponse::json(200, payment)
+}
+
+fn is_incomplete_server_payment(payment: &PaymentDTO) -> bool {
+    payment.direction == Direction::AppToUser
+        && !payment.status.developer_completed
+        && !payment.status.cancelled
+        && !payment.status.user_cancelled
+}
+
+fn transaction(txid: &str) -> PaymentDTOTransaction {
+    PaymentDTOTransaction {
+        txid: txid.to_string(),
+        verified: true,
+        _link: format!("https://api.testnet.minepi.com/transactions/{}", txid),
+    }
+}
+
+fn random_id() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(28)
+        .map(char::from)
+        .collect()
+}
+
+fn not_found(payment_id: &str) -> MockResponse {
+    error(
+        404,
+        "payment_not_found",
+        &format!("Payment {} is not found", payment_id),
+    )
+}
+
+pub(crate) fn error(status: u16, code: &str, message: &str) -> MockResponse {
+    MockResponse::json(status, &json!({ "error": code, "error_message": message }))
+}
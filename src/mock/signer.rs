@@ -0,0 +1,255 @@
+use data_encoding::HEXLOWER_PERMISSIVE;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::http::{MockRequest, MockResponse, MockServer};
+use crate::signer::{RemoteSignRequest, RemoteSignResponse};
+use crate::stellar_sdk::keypair::Keypair;
+use crate::types::PiError;
+
+/// Local stand-in of a signing process, available with the `mock` cargo feature
+///
+/// Holds the keypair and answers the requests of a `RemoteSigner` with the JSON protocol described there,
+/// over HTTP (`start_http`, point `RemoteSigner::http` at `url()`) or a Unix socket (`start_unix`).
+/// It can refuse to sign, or sign only for one network, like a signing process with a policy.
+///
+/// ```ignore
+/// let signer = MockSigner::start_http(Keypair::random()?)?;
+/// let remote = RemoteSigner::http(&signer.url().unwrap(), &signer.public_key())?;
+/// let pi = PiNetwork::new(api_key, remote, None, None)?;
+/// ```
+pub struct MockSigner {
+    transport: Transport,
+    state: Arc<Mutex<SignerState>>,
+}
+
+enum Transport {
+    Http(MockServer),
+    #[cfg(unix)]
+    Unix(unix::UnixServer),
+}
+
+struct SignerState {
+    keypair: Keypair,
+    network_passphrase: Option<String>,
+    refusal: Option<String>,
+    requests: Vec<RemoteSignRequest>,
+}
+
+impl MockSigner {
+    /// Serving the requests over HTTP on a random local port
+    pub fn start_http(keypair: Keypair) -> Result<Self, PiError> {
+        let state = SignerState::new(keypair);
+        let handler_state = state.clone();
+        let server = MockServer::start(Arc::new(move |request: &MockRequest| {
+            let response = match serde_json::from_slice::<RemoteSignRequest>(&request.body) {
+                Ok(sign_request) => lock(&handler_state).handle(sign_request),
+                Err(e) => refused(format!("Invalid request: {}", e)),
+            };
+            let status = if response.error.is_some() { 400 } else { 200 };
+            MockResponse::json(status, &response)
+        }))
+        .map_err(|e| PiError::Message(format!("Can't start the mock signer: {}", e)))?;
+
+        Ok(MockSigner {
+            transport: Transport::Http(server),
+            state,
+        })
+    }
+
+    /// Serving the requests over a Unix socket made at the path, the file is removed when the signer is dropped
+    #[cfg(unix)]
+    pub fn start_unix(
+        keypair: Keypair,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Self, PiError> {
+        let state = SignerState::new(keypair);
+        let handler_state = state.clone();
+        let server =
+            unix::UnixServer::start(
+                path.as_ref(),
+                move |line: &str| match serde_json::from_str::<RemoteSignRequest>(line) {
+                    Ok(sign_request) => lock(&handler_state).handle(sign_request),
+                    Err(e) => refused(format!("Invalid request: {}", e)),
+                },
+            )
+            .map_err(|e| PiError::Message(format!("Can't start the mock signer: {}", e)))?;
+
+        Ok(MockSigner {
+            transport: Transport::Unix(server),
+            state,
+        })
+    }
+
+    /// The URL for `RemoteSigner::http`, `None` if it's served over a Unix socket
+    pub fn url(&self) -> Option<String> {
+        match &self.transport {
+            Transport::Http(server) => Some(format!("{}/sign", server.url())),
+            #[cfg(unix)]
+            Transport::Unix(_) => None,
+        }
+    }
+
+    /// The path for `RemoteSigner::unix`, `None` if it's served over HTTP
+    #[cfg(unix)]
+    pub fn socket_path(&self) -> Option<&std::path::Path> {
+        match &self.transport {
+            Transport::Http(_) => None,
+            Transport::Unix(server) => Some(&server.path),
+        }
+    }
+
+    /// The account ID of the held keypair
+    pub fn public_key(&self) -> String {
+        self.state().keypair.public_key()
+    }
+
+    /// Signing only for the network with this passphrase, refusing the others
+    pub fn set_network(&self, network_passphrase: &str) {
+        self.state().network_passphrase = Some(network_passphrase.to_string());
+    }
+
+    /// Refusing every request with the message, or signing again with `None`
+    pub fn set_refusal(&self, message: Option<&str>) {
+        self.state().refusal = message.map(str::to_string);
+    }
+
+    /// The received requests, signed or refused
+    pub fn requests(&self) -> Vec<RemoteSignRequest> {
+        self.state().requests.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, SignerState> {
+        lock(&self.state)
+    }
+}
+
+impl SignerState {
+    fn new(keypair: Keypair) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(SignerState {
+            keypair,
+            network_passphrase: None,
+            refusal: None,
+            requests: Vec::new(),
+        }))
+    }
+
+    fn handle(&mut self, request: RemoteSignRequest) -> RemoteSignResponse {
+        self.requests.push(request.clone());
+        if let Some(refusal) = &self.refusal {
+            return refused(refusal.clone());
+        }
+        if request.public_key != self.keypair.public_key() {
+            return refused(format!("Unknown key {}", request.public_key));
+        }
+        if let Some(network_passphrase) = &self.network_passphrase {
+            if &request.network_passphrase != network_passphrase {
+                return refused(format!(
+                    "Not signing for the network {}",
+                    request.network_passphrase
+                ));
+            }
+        }
+
+        let hash = match HEXLOWER_PERMISSIVE.decode(request.transaction_hash.as_bytes()) {
+            Ok(hash) if hash.len() == 32 => hash,
+            _ => return refused("Invalid transaction hash".to_string()),
+        };
+        match self.keypair.sign(&hash) {
+            Ok(signature) => RemoteSignResponse {
+                signature: Some(HEXLOWER_PERMISSIVE.encode(&signature)),
+                error: None,
+            },
+            Err(e) => refused(e.to_string()),
+        }
+    }
+}
+
+fn refused(error: String) -> RemoteSignResponse {
+    RemoteSignResponse {
+        signature: None,
+        error: Some(error),
+    }
+}
+
+fn lock(state: &Mutex<SignerState>) -> MutexGuard<'_, SignerState> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread::{self, JoinHandle};
+
+    use crate::signer::RemoteSignResponse;
+
+    type Handler = Arc<dyn Fn(&str) -> RemoteSignResponse + Send + Sync>;
+
+    /// Server of the line based protocol on a Unix socket, every connection is served on its own thread
+    /// It's stopped and the socket file is removed when dropped.
+    pub(super) struct UnixServer {
+        pub(super) path: PathBuf,
+        stopped: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl UnixServer {
+        pub(super) fn start(
+            path: &Path,
+            handler: impl Fn(&str) -> RemoteSignResponse + Send + Sync + 'static,
+        ) -> io::Result<Self> {
+            let listener = UnixListener::bind(path)?;
+            let handler: Handler = Arc::new(handler);
+            let stopped = Arc::new(AtomicBool::new(false));
+
+            let stopped_thread = stopped.clone();
+            let thread = thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped_thread.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        let handler = handler.clone();
+                        thread::spawn(move || {
+                            let _ = serve(stream, handler);
+                        });
+                    }
+                }
+            });
+
+            Ok(UnixServer {
+                path: path.to_path_buf(),
+                stopped,
+                thread: Some(thread),
+            })
+        }
+    }
+
+    impl Drop for UnixServer {
+        fn drop(&mut self) {
+            self.stopped.store(true, Ordering::SeqCst);
+            // Waking up the accept loop so it sees the flag
+            let _ = UnixStream::connect(&self.path);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    fn serve(stream: UnixStream, handler: Handler) -> io::Result<()> {
+        let mut line = String::new();
+        BufReader::new(stream.try_clone()?).read_line(&mut line)?;
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        let mut response = serde_json::to_vec(&handler(line.trim()))?;
+        response.push(b'\n');
+        (&stream).write_all(&response)
+    }
+}
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+
+use crate::amount::PiAmount;
+use crate::payment_args::{PaymentArgsBuilder, PaymentLimits};
+use crate::types::{NetworkPassphrase, PaymentArgs};
+
+/// Everything that differs between the Pi networks
+///
+/// `PiNetwork::new` takes the preset of its `NetworkPassphrase`, `PiNetwork::with_profile` takes any profile,
+/// e.g. a local stand-in of the testnet:
+///
+/// ```
+/// use pi_rust::types::NetworkProfile;
+///
+/// let local = NetworkProfile::testnet()
+///     .with_horizon_url("http://localhost:8000")
+///     .with_pi_api_url("http://localhost:8001");
+/// assert_eq!(local.passphrase, "Pi Testnet");
+/// ```
+///
+/// - network: NetworkPassphrase - the network the Pi API reports for the payments of this network, the payments of any other network are rejected
+/// - passphrase: String - the passphrase the transactions are signed for
+/// - horizon_url: String - the Horizon of the network, plain http is allowed for it
+/// - pi_api_url: String - the Pi API base URL
+/// - base_reserve: PiAmount - the base reserve of the network, an account must hold at least two of it
+/// - payment_limits: PaymentLimits - the limits the payment arguments are checked against
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkProfile {
+    pub network: NetworkPassphrase,
+    pub passphrase: String,
+    pub horizon_url: String,
+    pub pi_api_url: String,
+    pub base_reserve: PiAmount,
+    #[serde(default)]
+    pub payment_limits: PaymentLimits,
+}
+
+impl NetworkProfile {
+    /// The Pi mainnet
+    pub fn mainnet() -> Self {
+        NetworkProfile {
+            network: NetworkPassphrase::PiNetwork,
+            passphrase: "Pi Network".to_string(),
+            horizon_url: "https://api.mainnet.minepi.com".to_string(),
+            pi_api_url: "https://api.minepi.com".to_string(),
+            base_reserve: PiAmount::from_stroops(PiAmount::STROOPS_PER_PI / 2),
+            payment_limits: PaymentLimits::default(),
+        }
+    }
+
+    /// The Pi testnet
+    pub fn testnet() -> Self {
+        NetworkProfile {
+            network: NetworkPassphrase::PiTestnet,
+            passphrase: "Pi Testnet".to_string(),
+            horizon_url: "https://api.testnet.minepi.com".to_string(),
+            ..NetworkProfile::mainnet()
+        }
+    }
+
+    pub fn with_horizon_url(mut self, horizon_url: impl Into<String>) -> Self {
+        self.horizon_url = horizon_url.into();
+        self
+    }
+
+    pub fn with_pi_api_url(mut self, pi_api_url: impl Into<String>) -> Self {
+        self.pi_api_url = pi_api_url.into();
+        self
+    }
+
+    pub fn with_passphrase(mut self, passphrase: impl Into<String>) -> Self {
+        self.passphrase = passphrase.into();
+        self
+    }
+
+    pub fn with_network(mut self, network: NetworkPassphrase) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub fn with_payment_limits(mut self, payment_limits: PaymentLimits) -> Self {
+        self.payment_limits = payment_limits;
+        self
+    }
+
+    /// Builder of the payment arguments, checking them against the payment limits of the network
+    pub fn payment_args(&self) -> PaymentArgsBuilder {
+        PaymentArgs::builder().limits(self.payment_limits.clone())
+    }
+
+    /// The least balance a new account can be created with, two base reserves
+    pub fn minimum_balance(&self) -> PiAmount {
+        self.base_reserve
+            .checked_mul(2)
+            .unwrap_or(self.base_reserve)
+    }
+}
+
+impl From<NetworkPassphrase> for NetworkProfile {
+    fn from(network: NetworkPassphrase) -> Self {
+        match network {
+            NetworkPassphrase::PiNetwork => NetworkProfile::mainnet(),
+            NetworkPassphrase::PiTestnet => NetworkProfile::testnet(),
+            // Signed for its name, with the URLs of the testnet until they are set
+            NetworkPassphrase::Custom(name) => NetworkProfile::testnet()
+                .with_passphrase(name.clone())
+                .with_network(NetworkPassphrase::Custom(name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        let mainnet = NetworkProfile::from(NetworkPassphrase::PiNetwork);
+        assert_eq!(mainnet.passphrase, "Pi Network");
+        assert_eq!(mainnet.horizon_url, "https://api.mainnet.minepi.com");
+        assert_eq!(mainnet.minimum_balance(), "1".parse().unwrap());
+
+        let testnet = NetworkProfile::from(NetworkPassphrase::PiTestnet);
+        assert_eq!(testnet.network, NetworkPassphrase::PiTestnet);
+        assert_eq!(testnet.passphrase, "Pi Testnet");
+        assert_eq!(testnet.horizon_url, "https://api.testnet.minepi.com");
+        assert_eq!(testnet.pi_api_url, "https://api.minepi.com");
+        assert_eq!(testnet.payment_limits, PaymentLimits::default());
+
+        // A custom network carries its own name, the Pi networks are still recognized by theirs
+        let devnet = NetworkPassphrase::Custom("Pi Devnet".to_string());
+        let profile = NetworkProfile::from(devnet.clone());
+        assert_eq!(profile.network, devnet);
+        assert_eq!(profile.passphrase, "Pi Devnet");
+        assert_ne!(profile.network, testnet.network);
+        assert_eq!(serde_json::to_string(&devnet).unwrap(), "\"Pi Devnet\"");
+        let parsed: NetworkPassphrase = serde_json::from_str("\"Pi Devnet\"").unwrap();
+        assert_eq!(parsed, devnet);
+        let parsed: NetworkPassphrase = serde_json::from_str("\"Pi Testnet\"").unwrap();
+        assert!(matches!(parsed, NetworkPassphrase::PiTestnet));
+        assert_eq!(
+            NetworkPassphrase::Custom("Pi Network".to_string()),
+            NetworkPassphrase::PiNetwork
+        );
+
+        // The presets have no minimum payout of their own, the app sets it
+        let args = testnet.payment_args().uid("uid");
+        assert!(args
+            .clone()
+            .amount("0.0000001".parse().unwrap())
+            .build()
+            .is_ok());
+        let limits = PaymentLimits {
+            min_amount: "0.01".parse().unwrap(),
+            ..PaymentLimits::default()
+        };
+        let args = testnet
+            .with_payment_limits(limits)
+            .payment_args()
+            .uid("uid");
+        assert!(args.clone().amount("0.01".parse().unwrap()).build().is_ok());
+        assert_eq!(
+            args.amount("0.009".parse().unwrap())
+                .build()
+                .unwrap_err()
+                .field(),
+            "amount"
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+
+use crate::amount::PiAmount;
+use crate::types::PaymentArgs;
+
+/// Limits of the A2U payments, the payment arguments are checked against them before anything is sent
+///
+/// - min_amount: PiAmount - the smallest amount that can be paid out, one stroop by default, the app sets its own minimum
+/// - max_decimals: u32 - the most decimals an amount can have, at most `PiAmount::DECIMALS`
+/// - max_memo_bytes: usize - the longest memo in bytes
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaymentLimits {
+    pub min_amount: PiAmount,
+    pub max_decimals: u32,
+    pub max_memo_bytes: usize,
+}
+
+impl Default for PaymentLimits {
+    fn default() -> Self {
+        PaymentLimits {
+            min_amount: PiAmount::from_stroops(1),
+            max_decimals: PiAmount::DECIMALS,
+            max_memo_bytes: 28,
+        }
+    }
+}
+
+/// Errors of the payment arguments, every one of them belongs to one field, see at `field`
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaymentArgsError {
+    MissingAmount,
+    AmountNotFinite(f64),
+    AmountNotPositive(PiAmount),
+    AmountBelowMinimum { amount: PiAmount, min: PiAmount },
+    AmountTooManyDecimals { amount: PiAmount, max_decimals: u32 },
+    MemoTooLong { bytes: usize, max: usize },
+    EmptyUid,
+    MetadataNotObject,
+}
+
+impl PaymentArgsError {
+    /// Name of the invalid field of the `PaymentArgs`
+    pub fn field(&self) -> &'static str {
+        match self {
+            PaymentArgsError::MissingAmount
+            | PaymentArgsError::AmountNotFinite(_)
+            | PaymentArgsError::AmountNotPositive(_)
+            | PaymentArgsError::AmountBelowMinimum { .. }
+            | PaymentArgsError::AmountTooManyDecimals { .. } => "amount",
+            PaymentArgsError::MemoTooLong { .. } => "memo",
+            PaymentArgsError::EmptyUid => "uid",
+            PaymentArgsError::MetadataNotObject => "metadata",
+        }
+    }
+}
+
+impl fmt::Display for PaymentArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaymentArgsError::MissingAmount => write!(f, "The amount of the payment is missing"),
+            PaymentArgsError::AmountNotFinite(amount) => {
+                write!(f, "The amount of the payment is not a number: {}", amount)
+            }
+            PaymentArgsError::AmountNotPositive(amount) => {
+                write!(f, "The amount of the payment must be positive: {}", amount)
+            }
+            PaymentArgsError::AmountBelowMinimum { amount, min } => write!(
+                f,
+                "The amount of the payment is less than the minimum {}: {}",
+                min, amount
+            ),
+            PaymentArgsError::AmountTooManyDecimals {
+                amount,
+                max_decimals,
+            } => write!(
+                f,
+                "The amount of the payment can have at most {} decimals: {}",
+                max_decimals, amount
+            ),
+            PaymentArgsError::MemoTooLong { bytes, max } => write!(
+                f,
+                "The memo of the payment is {} bytes long, at most {} bytes are allowed",
+                bytes, max
+            ),
+            PaymentArgsError::EmptyUid => write!(f, "The uid of the payment is empty"),
+            PaymentArgsError::MetadataNotObject => {
+                write!(f, "The metadata of the payment must be a JSON object")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PaymentArgsError {}
+
+// The amount is kept as it was given, so the float checks happen in `build`
+#[derive(Debug, Clone)]
+enum AmountInput {
+    Exact(PiAmount),
+    Float(f64),
+}
+
+/// Builder of the `PaymentArgs`, checking the arguments against the `PaymentLimits` in `build`
+///
+/// `NetworkProfile::payment_args` and `PiNetwork::payment_args` start it with the limits of the network,
+/// `PaymentArgs::builder` with the plain `PaymentLimits::default()`.
+///
+/// ```
+/// use pi_rust::types::{NetworkProfile, PaymentArgs, PaymentLimits};
+/// use serde_json::json;
+///
+/// let args = NetworkProfile::mainnet()
+///     .payment_args()
+///     .amount("1.5".parse().unwrap())
+///     .memo("Refund for apple pie")
+///     .metadata(json!({"productId": "apple-pie-1"}))
+///     .uid("user_uid_of_your_app")
+///     .build()
+///     .unwrap();
+/// assert_eq!(args.amount.to_string(), "1.5000000");
+///
+/// let err = PaymentArgs::builder().amount_f64(f64::NAN).uid("uid").build().unwrap_err();
+/// assert_eq!(err.field(), "amount");
+///
+/// // Below the minimum payout the app set
+/// let limits = PaymentLimits { min_amount: "0.01".parse().unwrap(), ..PaymentLimits::default() };
+/// let profile = NetworkProfile::mainnet().with_payment_limits(limits);
+/// let err = profile.payment_args().amount("0.001".parse().unwrap()).uid("uid").build().unwrap_err();
+/// assert_eq!(err.field(), "amount");
+/// ```
+#[derive(Debug, Clone)]
+pub struct PaymentArgsBuilder<M = Value> {
+    amount: Option<AmountInput>,
+    memo: String,
+    metadata: Option<M>,
+    uid: String,
+    limits: PaymentLimits,
+}
+
+impl<M> PaymentArgsBuilder<M> {
+    pub fn amount(mut self, amount: PiAmount) -> Self {
+        self.amount = Some(AmountInput::Exact(amount));
+        self
+    }
+
+    /// The amount in Pi as a float, rounded to the nearest stroop
+    pub fn amount_f64(mut self, amount: f64) -> Self {
+        self.amount = Some(AmountInput::Float(amount));
+        self
+    }
+
+    pub fn memo(mut self, memo: impl Into<String>) -> Self {
+        self.memo = memo.into();
+        self
+    }
+
+    /// Metadata of the payment, any type serialized to an object, e.g. your own struct
+    pub fn metadata<N>(self, metadata: N) -> PaymentArgsBuilder<N> {
+        PaymentArgsBuilder {
+            amount: self.amount,
+            memo: self.memo,
+            metadata: Some(metadata),
+            uid: self.uid,
+            limits: self.limits,
+        }
+    }
+
+    pub fn uid(mut self, uid: impl Into<String>) -> Self {
+        self.uid = uid.into();
+        self
+    }
+
+    /// Limits to check the arguments against, `PaymentLimits::default()` if it's not given
+    pub fn limits(mut self, limits: PaymentLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+impl<M: Serialize + DeserializeOwned> PaymentArgsBuilder<M> {
+    /// Building the checked arguments, the metadata is an empty object if it's not given
+    pub fn build(self) -> Result<PaymentArgs<M>, PaymentArgsError> {
+        let amount = match self.amount {
+            None => return Err(PaymentArgsError::MissingAmount),
+            Some(AmountInput::Exact(amount)) => amount,
+            Some(AmountInput::Float(amount)) => {
+                let stroops = (amount * PiAmount::STROOPS_PER_PI as f64).round();
+                if !stroops.is_finite() || stroops.abs() >= i64::MAX as f64 {
+                    return Err(PaymentArgsError::AmountNotFinite(amount));
+                }
+                PiAmount::from_stroops(stroops as i64)
+            }
+        };
+
+        let args = PaymentArgs {
+            amount,
+            memo: self.memo,
+            metadata: match self.metadata {
+                Some(metadata) => metadata,
+                None => serde_json::from_value(Value::Object(Map::new()))
+                    .map_err(|_| PaymentArgsError::MetadataNotObject)?,
+            },
+            uid: self.uid,
+        };
+        args.validate(&self.limits)?;
+        Ok(args)
+    }
+}
+
+impl PaymentArgs {
+    /// Builder checking the arguments against `PaymentLimits::default()`, see at `PiNetwork::payment_args` for the limits of a network
+    pub fn builder() -> PaymentArgsBuilder {
+        PaymentArgsBuilder {
+            amount: None,
+            memo: String::new(),
+            metadata: None,
+            uid: String::new(),
+            limits: PaymentLimits::default(),
+        }
+    }
+}
+
+impl<M: Serialize> PaymentArgs<M> {
+    /// Checking the arguments against the limits, the first invalid field is returned
+    pub fn validate(&self, limits: &PaymentLimits) -> Result<(), PaymentArgsError> {
+        if !self.amount.is_positive() {
+            return Err(PaymentArgsError::AmountNotPositive(self.amount));
+        }
+        if self.amount < limits.min_amount {
+            return Err(PaymentArgsError::AmountBelowMinimum {
+                amount: self.amount,
+                min: limits.min_amount,
+            });
+        }
+        let unit = 10_i64.pow(PiAmount::DECIMALS.saturating_sub(limits.max_decimals));
+        if self.amount.stroops() % unit != 0 {
+            return Err(PaymentArgsError::AmountTooManyDecimals {
+                amount: self.amount,
+                max_decimals: limits.max_decimals,
+            });
+        }
+        if self.memo.len() > limits.max_memo_bytes {
+            return Err(PaymentArgsError::MemoTooLong {
+                bytes: self.memo.len(),
+                max: limits.max_memo_bytes,
+            });
+        }
+        if self.uid.trim().is_empty() {
+            return Err(PaymentArgsError::EmptyUid);
+        }
+        let is_object =
+            serde_json::to_value(&self.metadata).is_ok_and(|metadata| metadata.is_object());
+        if !is_object {
+            return Err(PaymentArgsError::MetadataNotObject);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn builder() -> PaymentArgsBuilder {
+        PaymentArgs::builder()
+            .amount("1".parse().unwrap())
+            .memo("Refund for apple pie")
+            .uid("uid")
+    }
+
+    #[test]
+    fn test_builder() {
+        let args = builder()
+            .metadata(json!({"productId": "apple-pie-1"}))
+            .build()
+            .unwrap();
+        assert_eq!(args.amount, "1".parse().unwrap());
+        assert_eq!(args.memo, "Refund for apple pie");
+        assert_eq!(args.metadata, json!({"productId": "apple-pie-1"}));
+        assert_eq!(builder().build().unwrap().metadata, json!({}));
+        assert_eq!(
+            builder().amount_f64(0.1 + 0.2).build().unwrap().amount,
+            "0.3".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        let err = |builder: PaymentArgsBuilder| builder.build().unwrap_err();
+
+        assert_eq!(
+            err(PaymentArgs::builder().uid("uid")),
+            PaymentArgsError::MissingAmount
+        );
+        assert_eq!(
+            err(builder().amount(PiAmount::ZERO)),
+            PaymentArgsError::AmountNotPositive(PiAmount::ZERO)
+        );
+        assert_eq!(err(builder().amount_f64(-1.0)).field(), "amount");
+        assert!(matches!(
+            err(builder().amount_f64(f64::NAN)),
+            PaymentArgsError::AmountNotFinite(_)
+        ));
+        assert!(matches!(
+            err(builder().amount_f64(f64::INFINITY)),
+            PaymentArgsError::AmountNotFinite(_)
+        ));
+        assert_eq!(
+            err(builder().memo("a".repeat(29))),
+            PaymentArgsError::MemoTooLong { bytes: 29, max: 28 }
+        );
+        // Bytes, not characters
+        assert_eq!(err(builder().memo("π".repeat(15))).field(), "memo");
+        assert_eq!(err(builder().uid(" ")), PaymentArgsError::EmptyUid);
+        assert_eq!(
+            err(builder().metadata(json!(["apple-pie-1"]))),
+            PaymentArgsError::MetadataNotObject
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = PaymentLimits {
+            min_amount: "0.01".parse().unwrap(),
+            max_decimals: 2,
+            ..PaymentLimits::default()
+        };
+        let with_limits = |amount: &str| {
+            builder()
+                .amount(amount.parse().unwrap())
+                .limits(limits.clone())
+                .build()
+        };
+
+        assert!(with_limits("0.01").is_ok());
+        assert_eq!(
+            with_limits("0.001").unwrap_err(),
+            PaymentArgsError::AmountBelowMinimum {
+                amount: "0.001".parse().unwrap(),
+                min: "0.01".parse().unwrap(),
+            }
+        );
+        assert_eq!(
+            with_limits("1.005").unwrap_err(),
+            PaymentArgsError::AmountTooManyDecimals {
+                amount: "1.005".parse().unwrap(),
+                max_decimals: 2,
+            }
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::types::{PaymentDTO, PaymentDTOStatus};
+
+/// Where a payment stands, made from the status flags and the transaction of the `PaymentDTO`
+///
+/// - AwaitingApproval - the U2A payment is waiting for `approve_payment`
+/// - Approved - approved, but no transaction is linked yet
+/// - TransactionSubmitted - the transaction is linked, but the Pi API didn't verify it yet
+/// - TransactionVerified - the transaction is verified, the payment is waiting for `complete_payment`
+/// - Completed - completed by the developer
+/// - CancelledByDeveloper - cancelled by the developer or by Pi Network
+/// - CancelledByUser - cancelled by the user
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PaymentState {
+    AwaitingApproval,
+    Approved,
+    TransactionSubmitted,
+    TransactionVerified,
+    Completed,
+    CancelledByDeveloper,
+    CancelledByUser,
+}
+
+impl PaymentState {
+    /// Nothing happens to the payment anymore
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            PaymentState::Completed
+                | PaymentState::CancelledByDeveloper
+                | PaymentState::CancelledByUser
+        )
+    }
+}
+
+/// Status flags which can't belong to any real payment, returned by `PaymentDTO::state`
+///
+/// - payment_id: String - identifier of the payment
+/// - status: PaymentDTOStatus - the flags of the payment
+/// - has_transaction: bool - whether a transaction is linked to the payment
+/// - reason: &'static str - what is wrong with them
+
+#[derive(Debug, Clone)]
+pub struct InvalidPaymentState {
+    pub payment_id: String,
+    pub status: PaymentDTOStatus,
+    pub has_transaction: bool,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for InvalidPaymentState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Payment {} is in an impossible state, {}: {:?}",
+            self.payment_id, self.reason, self.status
+        )
+    }
+}
+
+impl std::error::Error for InvalidPaymentState {}
+
+impl<M> PaymentDTO<M> {
+    /// The state of the payment, an error if the flags contradict each other
+    pub fn state(&self) -> Result<PaymentState, InvalidPaymentState> {
+        let status = &self.status;
+        let has_transaction = self.transaction.is_some();
+        let invalid = |reason| InvalidPaymentState {
+            payment_id: self.identifier.clone(),
+            status: status.clone(),
+            has_transaction,
+            reason,
+        };
+
+        if status.cancelled && status.user_cancelled {
+            return Err(invalid("cancelled by both the developer and the user"));
+        }
+        if (status.cancelled || status.user_cancelled) && status.developer_completed {
+            return Err(invalid("both cancelled and completed"));
+        }
+        if (status.transaction_verified || status.developer_completed) && !has_transaction {
+            return Err(invalid("verified or completed without a transaction"));
+        }
+        if (has_transaction || status.developer_completed) && !status.developer_approved {
+            return Err(invalid("has a transaction or completed without approval"));
+        }
+
+        Ok(if status.cancelled {
+            PaymentState::CancelledByDeveloper
+        } else if status.user_cancelled {
+            PaymentState::CancelledByUser
+        } else if status.developer_completed {
+            PaymentState::Completed
+        } else if status.transaction_verified {
+            PaymentState::TransactionVerified
+        } else if has_transaction {
+            PaymentState::TransactionSubmitted
+        } else if status.developer_approved {
+            PaymentState::Approved
+        } else {
+            PaymentState::AwaitingApproval
+        })
+    }
+
+    /// Time since the payment was created
+    pub fn age(&self) -> Duration {
+        self.age_at(Utc::now())
+    }
+
+    /// Time from the creation of the payment until `now`
+    pub fn age_at(&self, now: DateTime<Utc>) -> Duration {
+        now - self.created_at
+    }
+
+    /// The payment was created more than `max_age` ago, e.g. to find the stale incomplete payments
+    pub fn is_older_than(&self, max_age: std::time::Duration) -> bool {
+        Duration::from_std(max_age).is_ok_and(|max_age| self.age() > max_age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn payment(status: [bool; 5], transaction: bool) -> PaymentDTO {
+        let [developer_approved, transaction_verified, developer_completed, cancelled, user_cancelled] =
+            status;
+        serde_json::from_value(json!({
+            "identifier": "payment_id",
+            "user_uid": "user_uid",
+            "amount": 1,
+            "memo": "Refund for apple pie",
+            "metadata": {},
+            "from_address": "GA",
+            "to_address": "GB",
+            "direction": "user_to_app",
+            "status": {
+                "developer_approved": developer_approved,
+                "transaction_verified": transaction_verified,
+                "developer_completed": developer_completed,
+                "cancelled": cancelled,
+                "user_cancelled": user_cancelled
+            },
+            "transaction": transaction.then(|| json!({ "txid": "txid", "verified": true, "_link": "" })),
+            "created_at": "2023-01-01T00:00:00.000Z",
+            "network": "Pi Testnet"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_states() {
+        let state = |status, transaction| payment(status, transaction).state().unwrap();
+        assert_eq!(
+            state([false, false, false, false, false], false),
+            PaymentState::AwaitingApproval
+        );
+        assert_eq!(
+            state([true, false, false, false, false], false),
+            PaymentState::Approved
+        );
+        assert_eq!(
+            state([true, false, false, false, false], true),
+            PaymentState::TransactionSubmitted
+        );
+        assert_eq!(
+            state([true, true, false, false, false], true),
+            PaymentState::TransactionVerified
+        );
+        assert_eq!(
+            state([true, true, true, false, false], true),
+            PaymentState::Completed
+        );
+        assert_eq!(
+            state([true, false, false, true, false], true),
+            PaymentState::CancelledByDeveloper
+        );
+        assert_eq!(
+            state([false, false, false, false, true], false),
+            PaymentState::CancelledByUser
+        );
+        assert!(state([true, true, true, false, false], true).is_final());
+    }
+
+    #[test]
+    fn test_impossible_states() {
+        let reason = |status, transaction| payment(status, transaction).state().unwrap_err().reason;
+        assert_eq!(
+            reason([true, false, false, true, true], false),
+            "cancelled by both the developer and the user"
+        );
+        assert_eq!(
+            reason([true, true, true, true, false], true),
+            "both cancelled and completed"
+        );
+        assert_eq!(
+            reason([true, true, false, false, false], false),
+            "verified or completed without a transaction"
+        );
+        assert_eq!(
+            reason([false, false, false, false, false], true),
+            "has a transaction or completed without approval"
+        );
+    }
+
+    #[test]
+    fn test_age() {
+        let payment = payment([true, false, false, false, false], false);
+        assert_eq!(
+            payment.created_at,
+            DateTime::parse_from_rfc3339("2023-01-01T00:00:00Z").unwrap()
+        );
+        let now = DateTime::parse_from_rfc3339("2023-01-01T01:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(payment.age_at(now), Duration::hours(1));
+        assert!(payment.is_older_than(std::time::Duration::from_secs(3600)));
+    }
+}
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::{PaymentDTO, PiError};
+
+/// What `PiNetwork::resolve_incomplete_payments` does with the payments which have no blockchain transaction
+///
+/// - Submit: building and submitting the transaction once an earlier one can't get in anymore, then completing the payment
+/// - Cancel: cancelling the payment once an earlier transaction can't get in anymore
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IncompletePaymentPolicy {
+    Submit,
+    Cancel,
+}
+
+/// Classification of an incomplete payment
+///
+/// - LinkedTransaction: the Pi API already knows the blockchain transaction of the payment
+/// - MemoTransaction: the Pi API doesn't know about it, but a transaction with the payment ID as memo is on the blockchain
+/// - Unsubmitted: no transaction was made for the payment
+/// - Completed: the payment is already completed, nothing to do
+/// - Cancelled: the payment is already cancelled, nothing to do
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IncompletePaymentKind {
+    LinkedTransaction,
+    MemoTransaction,
+    Unsubmitted,
+    Completed,
+    Cancelled,
+}
+
+/// The action taken on an incomplete payment
+///
+/// - Complete: completing the payment with the transaction already on the blockchain
+/// - SubmitAndComplete: submitting a new transaction and completing the payment
+/// - Cancel: cancelling the payment
+/// - Nothing: nothing had to be done, or nothing could be done safely
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IncompletePaymentAction {
+    Complete,
+    SubmitAndComplete,
+    Cancel,
+    Nothing,
+}
+
+/// Report of one payment handled by `PiNetwork::resolve_incomplete_payments`
+///
+/// - payment_id: String - identifier of the payment
+/// - kind: IncompletePaymentKind - how the payment was classified
+/// - action: IncompletePaymentAction - the action taken, or attempted if it failed
+/// - txid: `Option<String>` - the blockchain transaction of the payment, if there is one
+/// - error: `Option<PiError>` - why the payment couldn't be resolved, it's still incomplete
+
+#[derive(Debug, Serialize)]
+pub struct IncompletePaymentResolution {
+    pub payment_id: String,
+    pub kind: IncompletePaymentKind,
+    pub action: IncompletePaymentAction,
+    pub txid: Option<String>,
+    pub error: Option<PiError>,
+}
+
+impl IncompletePaymentResolution {
+    /// Classifying the payment by its status and linked transaction, before anything is checked on the blockchain
+    pub fn new(payment: &PaymentDTO) -> Self {
+        let kind = if payment.status.developer_completed {
+            IncompletePaymentKind::Completed
+        } else if payment.status.cancelled || payment.status.user_cancelled {
+            IncompletePaymentKind::Cancelled
+        } else if payment.transaction.is_some() {
+            IncompletePaymentKind::LinkedTransaction
+        } else {
+            IncompletePaymentKind::Unsubmitted
+        };
+
+        IncompletePaymentResolution {
+            payment_id: payment.identifier.clone(),
+            kind,
+            action: IncompletePaymentAction::Nothing,
+            txid: payment
+                .transaction
+                .as_ref()
+                .map(|transaction| transaction.txid.clone()),
+            error: None,
+        }
+    }
+
+    /// True if the payment is not blocking new payments anymore
+    pub fn is_resolved(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn payment(transaction: bool, cancelled: bool) -> PaymentDTO {
+        serde_json::from_value(json!({
+            "identifier": "payment_id",
+            "user_uid": "user_uid",
+            "amount": 1,
+            "memo": "Refund for apple pie",
+            "metadata": { "productId": "apple-pie-1" },
+            "from_address": "GAUZUPTHOMSZEV65VNSRMUDAAE4VBMSRYYAX3UOWYU3BQUZ6OK65NOWM",
+            "to_address": "GAP2TJNW7NL52MPB36DZ2PB6PSIBEUEJXDG325BJQKUNDQBPKX3E2DLV",
+            "direction": "app_to_user",
+            "status": {
+                "developer_approved": true,
+                "transaction_verified": transaction,
+                "developer_completed": false,
+                "cancelled": cancelled,
+                "user_cancelled": false
+            },
+            "transaction": if transaction {
+                json!({ "txid": "txid", "verified": true, "_link": "link" })
+            } else {
+                serde_json::Value::Null
+            },
+            "created_at": "2023-01-01T00:00:00.000Z",
+            "network": "Pi Testnet"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_classification() {
+        let linked = IncompletePaymentResolution::new(&payment(true, false));
+        assert_eq!(linked.kind, IncompletePaymentKind::LinkedTransaction);
+        assert_eq!(linked.txid.as_deref(), Some("txid"));
+
+        let unsubmitted = IncompletePaymentResolution::new(&payment(false, false));
+        assert_eq!(unsubmitted.kind, IncompletePaymentKind::Unsubmitted);
+        assert_eq!(unsubmitted.txid, None);
+
+        let cancelled = IncompletePaymentResolution::new(&payment(false, true));
+        assert_eq!(cancelled.kind, IncompletePaymentKind::Cancelled);
+        assert_eq!(cancelled.action, IncompletePaymentAction::Nothing);
+    }
+
+    #[test]
+    fn test_is_resolved() {
+        let mut resolution = IncompletePaymentResolution::new(&payment(true, false));
+        assert!(resolution.is_resolved());
+        resolution.error = Some(PiError::Message("failed".to_string()));
+        assert!(!resolution.is_resolved());
+    }
+}
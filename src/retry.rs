@@ -0,0 +1,170 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Retry policy of the Pi API and Horizon calls
+///
+/// Used by `ReqwestClientOptions` for the Pi API and by `ServerOptions` for Horizon.
+/// Only the calls which are safe to repeat are retried on every retriable failure: the GET requests, `complete_payment`,
+/// and the transaction submission, which checks on Horizon by the transaction hash before it's sent again.
+/// The other calls are only retried if the request surely wasn't processed, e.g. the connection failed or the response was 429.
+///
+/// - max_attempts: u32 - number of attempts including the first one, 1 means no retry
+/// - initial_backoff: Duration - the wait before the first retry, doubled for every next one
+/// - max_backoff: Duration - the upper limit of the exponential backoff
+/// - jitter: bool - waiting a random time between the half and the whole backoff, so clients don't retry at the same moment
+/// - respect_retry_after: bool - waiting as long as the `Retry-After` header of the response asks instead of the backoff
+/// - max_retry_after: Duration - the longest `Retry-After` waited for, if a response asks for more it's not retried
+/// - retriable_status_codes: `Vec<u16>` - the HTTP status codes which count as transient failures
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub jitter: bool,
+    pub respect_retry_after: bool,
+    #[serde(default = "RetryPolicy::default_max_retry_after")]
+    pub max_retry_after: Duration,
+    pub retriable_status_codes: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            jitter: true,
+            respect_retry_after: true,
+            max_retry_after: RetryPolicy::default_max_retry_after(),
+            retriable_status_codes: vec![429, 500, 502, 503, 504],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy which never retries
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    fn default_max_retry_after() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    pub fn is_retriable_status(&self, status: u16) -> bool {
+        self.retriable_status_codes.contains(&status)
+    }
+
+    /// True if the `retry`-th retry (counted from 1) is allowed
+    pub fn allows_retry(&self, retry: u32) -> bool {
+        retry < self.max_attempts
+    }
+
+    /// The exponential backoff before the `retry`-th retry (counted from 1), with jitter if it's enabled
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(31);
+        let backoff = self
+            .initial_backoff
+            .checked_mul(1 << exponent)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+
+        if self.jitter && !backoff.is_zero() {
+            let half = backoff / 2;
+            half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+        } else {
+            backoff
+        }
+    }
+
+    /// The wait before the `retry`-th retry, the `Retry-After` of the response wins if it's respected
+    /// `None` if the `Retry-After` is longer than `max_retry_after`, then it's not retried
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        match retry_after {
+            Some(retry_after) if self.respect_retry_after => {
+                (retry_after <= self.max_retry_after).then_some(retry_after)
+            }
+            _ => Some(self.backoff(retry)),
+        }
+    }
+}
+
+/// Parsing the `Retry-After` header, which is either a number of seconds or an HTTP date
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let seconds = (date.timestamp() - chrono::Utc::now().timestamp()).max(0);
+    Some(Duration::from_secs(seconds as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_backoff() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(40), Duration::from_secs(10));
+
+        let policy = RetryPolicy::default();
+        for _ in 0..20 {
+            let backoff = policy.backoff(2);
+            assert!(backoff >= Duration::from_millis(500) && backoff <= Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn test_retry_after() {
+        assert_eq!(parse_retry_after("7"), Some(Duration::from_secs(7)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+
+        let policy = RetryPolicy::default();
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(7))),
+            Some(Duration::from_secs(7))
+        );
+        // A day long wait isn't made, the response is returned instead
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(86400))), None);
+        assert_eq!(
+            policy.delay(1, parse_retry_after("Fri, 01 Jan 2100 00:00:00 GMT")),
+            None
+        );
+        let policy = RetryPolicy {
+            respect_retry_after: false,
+            jitter: false,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(86400))),
+            Some(Duration::from_millis(500))
+        );
+    }
+
+    #[test]
+    fn test_attempts_and_statuses() {
+        let policy = RetryPolicy::default();
+        assert!(policy.allows_retry(2));
+        assert!(!policy.allows_retry(3));
+        assert!(!RetryPolicy::none().allows_retry(1));
+        assert!(policy.is_retriable_status(503));
+        assert!(!policy.is_retriable_status(400));
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::stellar_sdk::lib::Server;
+use crate::types::PiError;
+
+/// Local cache of the sequence numbers of the source accounts
+///
+/// Without it every transaction would load its source account from Horizon only to read the sequence number.
+/// The manager loads an account once, then hands out the following sequence numbers from memory, every number
+/// to one caller only, even if many transactions are built at the same time.
+/// If the cache gets out of date, e.g. the account was used by another program, Horizon rejects the transaction
+/// with `tx_bad_seq`. `handle_error` notices it and the account is loaded again for the next transaction.
+///
+/// ```ignore
+/// let sequence = pi.sequence_manager().next(&pi_horizon, &account_id)?;
+/// // ... building and submitting the transaction
+/// if let Err(err) = result {
+///     if pi.sequence_manager().handle_error(&account_id, &err) {
+///         // The sequence was wrong, a new transaction can be built
+///     }
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct SequenceManager {
+    // The last sequence number handed out for every account
+    last_used: Mutex<HashMap<String, i64>>,
+}
+
+impl SequenceManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The sequence number for the next transaction of the account, the account is loaded from Horizon if it's not cached
+    pub async fn next(&self, pi_horizon: &Server, account_id: &str) -> Result<i64, PiError> {
+        if let Some(last_used) = self.lock()?.get_mut(account_id) {
+            *last_used += 1;
+            return Ok(*last_used);
+        }
+
+        // Not holding the lock while waiting for Horizon, if another caller loaded it meanwhile that one is used
+        let current = pi_horizon
+            .load_account(account_id)
+            .await?
+            .parse_sequence_number()?;
+        let mut cache = self.lock()?;
+        let last_used = cache.entry(account_id.to_string()).or_insert(current);
+        *last_used += 1;
+        Ok(*last_used)
+    }
+
+    /// Loading the sequence number of the account from Horizon right away, returning the current one
+    pub async fn resync(&self, pi_horizon: &Server, account_id: &str) -> Result<i64, PiError> {
+        let current = pi_horizon
+            .load_account(account_id)
+            .await?
+            .parse_sequence_number()?;
+        self.lock()?.insert(account_id.to_string(), current);
+        Ok(current)
+    }
+
+    /// Forgetting the account, it's loaded from Horizon again for its next transaction
+    pub fn invalidate(&self, account_id: &str) {
+        if let Ok(mut cache) = self.lock() {
+            cache.remove(account_id);
+        }
+    }
+
+    /// The last sequence number handed out for the account, `None` if it's not cached
+    pub fn last_used(&self, account_id: &str) -> Option<i64> {
+        self.lock().ok()?.get(account_id).copied()
+    }
+
+    /// Checking the submission error of a transaction of the account
+    ///
+    /// Returns true if it was rejected with `tx_bad_seq`, so a new transaction can be built with a fresh sequence number.
+    /// When the transaction was rejected for any other reason its sequence number wasn't used, so the account is loaded
+    /// again too. A transaction included as failed (`tx_failed`) used its sequence number, and when there is no
+    /// result code at all it can't be known, so the cache is kept in those cases.
+    pub fn handle_error(&self, account_id: &str, err: &PiError) -> bool {
+        match err.transaction_result_code() {
+            Some("tx_failed") | None => false,
+            Some(code) => {
+                self.invalidate(account_id);
+                code == "tx_bad_seq"
+            }
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<String, i64>>, PiError> {
+        self.last_used
+            .lock()
+            .map_err(|_| PiError::Message("The sequence cache is poisoned".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stellar_sdk::types::HorizonError;
+    use serde_json::json;
+
+    fn rejected(code: &str) -> PiError {
+        let err: HorizonError = serde_json::from_value(json!({
+            "type": "https://stellar.org/horizon-errors/transaction_failed",
+            "title": "Transaction Failed",
+            "status": 400,
+            "extras": { "result_codes": { "transaction": code }, "result_xdr": "" }
+        }))
+        .unwrap();
+        PiError::from(err)
+    }
+
+    #[test]
+    fn test_handle_error() {
+        let manager = SequenceManager::new();
+        let account_id = "GAUZUPTHOMSZEV65VNSRMUDAAE4VBMSRYYAX3UOWYU3BQUZ6OK65NOWM";
+        let cached = |manager: &SequenceManager| {
+            manager.lock().unwrap().insert(account_id.to_string(), 41);
+        };
+
+        cached(&manager);
+        assert!(!manager.handle_error(account_id, &rejected("tx_failed")));
+        assert!(!manager.handle_error(account_id, &PiError::Message("timeout".to_string())));
+        assert_eq!(manager.last_used(account_id), Some(41));
+
+        assert!(manager.handle_error(account_id, &rejected("tx_bad_seq")));
+        assert_eq!(manager.last_used(account_id), None);
+
+        cached(&manager);
+        assert!(!manager.handle_error(account_id, &rejected("tx_insufficient_fee")));
+        assert_eq!(manager.last_used(account_id), None);
+    }
+}
@@ -0,0 +1,327 @@
+use data_encoding::HEXLOWER_PERMISSIVE;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use stellar_base::crypto::{DecoratedSignature, Signature, SignatureHint};
+use stellar_base::network::Network;
+use stellar_base::transaction::Transaction;
+use zeroize::Zeroizing;
+
+use crate::stellar_sdk::lib::Keypair;
+use crate::types::PiError;
+use crate::PiNetwork;
+
+/// How long a `RemoteSigner` waits for a signature by default
+const DEFAULT_REMOTE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The longest answer read from the Unix socket of a signer, in bytes
+const MAX_RESPONSE_LINE: u64 = 64 * 1024;
+
+/// The future of a signature made by a `TransactionSigner`
+pub type SignatureFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<u8>, PiError>> + Send + 'a>>;
+
+/// Signer of the transactions of the app wallet
+///
+/// The `PiNetwork` only asks the signer for the public key of the wallet and for the Ed25519 signatures of the
+/// transaction hashes, so the seed doesn't have to be in the process: it can be held by a separate signing process,
+/// see at `RemoteSigner`. A `Keypair` is the signer keeping the seed in memory.
+/// The hash is made for the network, the passphrase is given so the signer can check which network it signs for.
+///
+/// ```ignore
+/// let signer = RemoteSigner::unix("/run/pi-signer.sock", &app_wallet_address)?;
+/// let pi = PiNetwork::new(api_key, signer, None, None)?;
+/// ```
+pub trait TransactionSigner: fmt::Debug + Send + Sync {
+    /// The account ID of the wallet, starting with G
+    fn public_key(&self) -> String;
+
+    /// The 64 bytes Ed25519 signature of the transaction hash
+    fn sign_transaction_hash<'a>(
+        &'a self,
+        hash: &'a [u8],
+        network_passphrase: &'a str,
+    ) -> SignatureFuture<'a>;
+}
+
+impl TransactionSigner for Keypair {
+    fn public_key(&self) -> String {
+        Keypair::public_key(self)
+    }
+
+    fn sign_transaction_hash<'a>(
+        &'a self,
+        hash: &'a [u8],
+        _network_passphrase: &'a str,
+    ) -> SignatureFuture<'a> {
+        let signature = self
+            .sign(hash)
+            .map_err(|e| PiError::Signer(format!("Can't sign the transaction! {:?}", e)));
+        Box::pin(std::future::ready(signature))
+    }
+}
+
+/// What `PiNetwork::new` takes as the app wallet: its secret seed, or any `TransactionSigner`
+pub trait IntoTransactionSigner {
+    fn into_signer(self) -> Result<Arc<dyn TransactionSigner>, PiError>;
+}
+
+// The secret seed of the wallet, it's kept in memory by a `Keypair`
+impl IntoTransactionSigner for String {
+    fn into_signer(self) -> Result<Arc<dyn TransactionSigner>, PiError> {
+        Zeroizing::new(self).into_signer()
+    }
+}
+
+impl IntoTransactionSigner for Zeroizing<String> {
+    fn into_signer(self) -> Result<Arc<dyn TransactionSigner>, PiError> {
+        let seed = self;
+        PiNetwork::validate_seed_format(&seed)?;
+        let keypair =
+            Keypair::from_secret_key(&seed).map_err(|e| PiError::Message(format!("{:?}", e)))?;
+        Ok(Arc::new(keypair))
+    }
+}
+
+impl<S: TransactionSigner + 'static> IntoTransactionSigner for S {
+    fn into_signer(self) -> Result<Arc<dyn TransactionSigner>, PiError> {
+        Ok(Arc::new(self))
+    }
+}
+
+// One signer shared by many `PiNetwork`s
+impl IntoTransactionSigner for Arc<dyn TransactionSigner> {
+    fn into_signer(self) -> Result<Arc<dyn TransactionSigner>, PiError> {
+        Ok(self)
+    }
+}
+
+/// Adding the signature of the signer to the transaction
+pub(crate) async fn sign_transaction(
+    signer: &dyn TransactionSigner,
+    transaction: &mut Transaction,
+    network: &Network,
+) -> Result<(), PiError> {
+    let hash = transaction
+        .hash(network)
+        .map_err(|e| PiError::Message(format!("Can't hash the transaction! {:?}", e)))?;
+    let signature = signer
+        .sign_transaction_hash(&hash, network.passphrase())
+        .await?;
+
+    let public_key = Keypair::from_public_key(&signer.public_key())
+        .map_err(|e| PiError::Signer(format!("Invalid public key of the signer! {:?}", e)))?;
+    let hint = SignatureHint::from_public_key(public_key.raw_public_key());
+    let signature = Signature::try_from(signature.as_slice())
+        .map_err(|e| PiError::Signer(format!("Invalid signature! {:?}", e)))?;
+    transaction
+        .signatures_mut()
+        .push(DecoratedSignature::new(hint, signature));
+    Ok(())
+}
+
+/// The request a `RemoteSigner` sends, as JSON
+///
+/// - public_key: String - the account ID the signature is asked from
+/// - network_passphrase: String - the network the transaction is made for
+/// - transaction_hash: String - the hash to sign, hex encoded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteSignRequest {
+    pub public_key: String,
+    pub network_passphrase: String,
+    pub transaction_hash: String,
+}
+
+/// The answer of the signing process, as JSON, either the signature or the error is given
+///
+/// - signature: `Option<String>` - the 64 bytes Ed25519 signature, hex encoded
+/// - error: `Option<String>` - why the signature was refused
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RemoteSignResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Signer reached over HTTP or a local Unix socket, the seed is held by the signing process
+///
+/// Every signature is asked with a `RemoteSignRequest` and answered with a `RemoteSignResponse`, as JSON:
+/// over HTTP it's the body of a POST to the URL and of its response, over a Unix socket it's one line each way
+/// on a new connection. The returned signature is verified with the public key before it's used,
+/// and the errors of the signer are returned as `PiError::Signer`.
+///
+/// ```ignore
+/// // {"public_key":"G...","network_passphrase":"Pi Testnet","transaction_hash":"5d1c..."}
+/// // {"signature":"a3f0..."}
+/// let signer = RemoteSigner::http("http://127.0.0.1:7000/sign", &app_wallet_address)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    public_key: String,
+    endpoint: RemoteEndpoint,
+    timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+enum RemoteEndpoint {
+    Http {
+        url: String,
+        client: reqwest::Client,
+    },
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+impl RemoteSigner {
+    /// Asking the signatures with POST requests to the URL, the client times out after the default timeout
+    pub fn http(url: &str, public_key: &str) -> Result<Self, PiError> {
+        let client = reqwest::Client::builder()
+            .timeout(DEFAULT_REMOTE_TIMEOUT)
+            .build()
+            .map_err(|e| PiError::Message(format!("Can't build the HTTP client! {}", e)))?;
+        Self::with_endpoint(
+            RemoteEndpoint::Http {
+                url: url.to_string(),
+                client,
+            },
+            public_key,
+        )
+    }
+
+    /// Asking the signatures over the Unix socket at the path
+    #[cfg(unix)]
+    pub fn unix(path: impl Into<std::path::PathBuf>, public_key: &str) -> Result<Self, PiError> {
+        Self::with_endpoint(RemoteEndpoint::Unix(path.into()), public_key)
+    }
+
+    fn with_endpoint(endpoint: RemoteEndpoint, public_key: &str) -> Result<Self, PiError> {
+        Keypair::from_public_key(public_key)
+            .map_err(|e| PiError::Message(format!("Invalid public key! {:?}", e)))?;
+        Ok(RemoteSigner {
+            public_key: public_key.to_string(),
+            endpoint,
+            timeout: DEFAULT_REMOTE_TIMEOUT,
+        })
+    }
+
+    /// Using the client for the HTTP requests, e.g. with the certificates of the signer
+    ///
+    /// The requests are still given up after the timeout of the signer, see at `with_timeout`
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        if let RemoteEndpoint::Http { client: old, .. } = &mut self.endpoint {
+            *old = client;
+        }
+        self
+    }
+
+    /// How long a signature is waited for, 30 seconds by default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn request(&self, request: &RemoteSignRequest) -> Result<RemoteSignResponse, PiError> {
+        match &self.endpoint {
+            RemoteEndpoint::Http { url, client } => {
+                let response = client
+                    .post(url)
+                    .json(request)
+                    .send()
+                    .await
+                    .map_err(|e| PiError::Signer(format!("Can't reach the signer! {}", e)))?;
+                let status = response.status();
+                let body = response
+                    .text()
+                    .await
+                    .map_err(|e| PiError::Signer(format!("Can't read the signature! {}", e)))?;
+                match serde_json::from_str::<RemoteSignResponse>(&body) {
+                    Ok(response) => Ok(response),
+                    Err(_) if !status.is_success() => Err(PiError::Signer(format!(
+                        "The signer responded with {}",
+                        status
+                    ))),
+                    Err(e) => Err(PiError::Signer(format!("Invalid response! {}", e))),
+                }
+            }
+            #[cfg(unix)]
+            RemoteEndpoint::Unix(path) => {
+                use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+                let io_error = |e: std::io::Error| {
+                    PiError::Signer(format!(
+                        "Can't reach the signer at {}! {}",
+                        path.display(),
+                        e
+                    ))
+                };
+                let mut stream = tokio::net::UnixStream::connect(path)
+                    .await
+                    .map_err(io_error)?;
+                let mut line = serde_json::to_vec(request)?;
+                line.push(b'\n');
+                stream.write_all(&line).await.map_err(io_error)?;
+
+                // A signer that never ends its line can't make us read without end
+                let mut response = String::new();
+                BufReader::new(stream.take(MAX_RESPONSE_LINE))
+                    .read_line(&mut response)
+                    .await
+                    .map_err(io_error)?;
+                if !response.ends_with('\n') && response.len() as u64 >= MAX_RESPONSE_LINE {
+                    return Err(PiError::Signer(format!(
+                        "The response is longer than {} bytes",
+                        MAX_RESPONSE_LINE
+                    )));
+                }
+                serde_json::from_str(&response)
+                    .map_err(|e| PiError::Signer(format!("Invalid response! {}", e)))
+            }
+        }
+    }
+}
+
+impl TransactionSigner for RemoteSigner {
+    fn public_key(&self) -> String {
+        self.public_key.clone()
+    }
+
+    fn sign_transaction_hash<'a>(
+        &'a self,
+        hash: &'a [u8],
+        network_passphrase: &'a str,
+    ) -> SignatureFuture<'a> {
+        Box::pin(async move {
+            let request = RemoteSignRequest {
+                public_key: self.public_key.clone(),
+                network_passphrase: network_passphrase.to_string(),
+                transaction_hash: HEXLOWER_PERMISSIVE.encode(hash),
+            };
+            let response = tokio::time::timeout(self.timeout, self.request(&request))
+                .await
+                .map_err(|_| PiError::Signer("The signer didn't answer in time".to_string()))??;
+
+            let signature = match (response.signature, response.error) {
+                (_, Some(error)) => {
+                    return Err(PiError::Signer(format!("The signer refused: {}", error)))
+                }
+                (Some(signature), None) => HEXLOWER_PERMISSIVE
+                    .decode(signature.as_bytes())
+                    .map_err(|e| PiError::Signer(format!("Invalid signature! {}", e)))?,
+                (None, None) => return Err(PiError::Signer("No signature returned".to_string())),
+            };
+
+            // A wrong signature would only be noticed by Horizon, rejecting the transaction
+            let public_key = Keypair::from_public_key(&self.public_key)
+                .map_err(|e| PiError::Signer(format!("Invalid public key! {:?}", e)))?;
+            if !public_key.verify(hash, &signature) {
+                return Err(PiError::Signer(
+                    "The signature doesn't match the public key".to_string(),
+                ));
+            }
+            Ok(signature)
+        })
+    }
+}
@@ -1,45 +1,163 @@
+use reqwest::Response;
 use serde::de::DeserializeOwned;
 use std::collections::HashMap;
-use ureq::{self, Error as UreqError};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
 
+use crate::retry::{parse_retry_after, RetryPolicy};
+use crate::stellar_sdk::endpoints::ServerOptions;
 use crate::stellar_sdk::types::{HorizonError, HttpMethod};
 
-pub fn api_call<T: DeserializeOwned>(
+/// Outcome of one attempt of an API call
+pub(crate) enum Attempt<T> {
+    Done(Result<T, anyhow::Error>),
+    /// Transient failure, `maybe_processed` is false if Horizon surely didn't process the request
+    Retry {
+        error: anyhow::Error,
+        retry_after: Option<Duration>,
+        maybe_processed: bool,
+    },
+}
+
+pub async fn api_call<T: DeserializeOwned>(
     url: String,
     method: HttpMethod,
     query_params: &HashMap<String, String>,
-    token: &Option<String>,
+    options: &ServerOptions,
 ) -> Result<T, anyhow::Error> {
-    let mut req = match method {
-        HttpMethod::GET => ureq::get(&url),
-        HttpMethod::POST => ureq::post(&url),
-    };
-    if token.is_some() {
-        req = req.set("Authorization", token.clone().unwrap().as_str());
-    }
+    api_call_with_check(url, method, query_params, options, None).await
+}
 
-    for query_param in query_params.iter() {
-        req = req.query(query_param.0, query_param.1);
-    }
+/// Type of the check made before repeating a request which Horizon could have processed already
+pub(crate) type ProcessedCheck<'a, T> = Box<
+    dyn FnMut() -> Pin<Box<dyn Future<Output = Result<Option<T>, anyhow::Error>> + Send + 'a>>
+        + Send
+        + 'a,
+>;
 
-    match req.call() {
-        Ok(res) => {
-            let res_str = res.into_string()?;
+/// Boxing an async closure into a `ProcessedCheck`
+pub(crate) fn processed_check<'a, T, F, Fut>(mut check: F) -> ProcessedCheck<'a, T>
+where
+    F: FnMut() -> Fut + Send + 'a,
+    Fut: Future<Output = Result<Option<T>, anyhow::Error>> + Send + 'a,
+{
+    Box::new(move || Box::pin(check()))
+}
 
-            Ok(serde_json::from_str::<T>(&res_str)?)
-        }
-        Err(e) => match e {
-            UreqError::Status(code, res) => {
-                if code >= 500 {
-                    return Err(UreqError::Status(code, res).into());
+/// Making the call with the retry policy of the options
+///
+/// GET requests are repeated on any transient failure. Other requests are only repeated if Horizon surely didn't
+/// process them, or if the `check` is given: it's called before repeating, if it finds the result of the earlier
+/// attempt that's returned, if it's `None` the request is repeated.
+pub(crate) async fn api_call_with_check<T: DeserializeOwned>(
+    url: String,
+    method: HttpMethod,
+    query_params: &HashMap<String, String>,
+    options: &ServerOptions,
+    mut check: Option<ProcessedCheck<'_, T>>,
+) -> Result<T, anyhow::Error> {
+    let policy = options.retry_policy();
+    let repeatable = matches!(method, HttpMethod::GET);
+    let mut retry = 0;
+    loop {
+        match api_call_once(&url, &method, query_params, options, &policy).await {
+            Attempt::Done(result) => return result,
+            Attempt::Retry {
+                error,
+                retry_after,
+                maybe_processed,
+            } => {
+                retry += 1;
+                if !policy.allows_retry(retry)
+                    || (maybe_processed && !repeatable && check.is_none())
+                {
+                    return Err(error);
+                }
+                let Some(delay) = policy.delay(retry, retry_after) else {
+                    return Err(error);
+                };
+                tokio::time::sleep(delay).await;
+                if maybe_processed && !repeatable {
+                    if let Some(check) = check.as_mut() {
+                        if let Some(found) = check().await? {
+                            return Ok(found);
+                        }
+                    }
                 }
+            }
+        }
+    }
+}
 
-                let res_str = res.into_string()?;
-                let parsed: HorizonError = serde_json::from_str(&res_str)?;
+async fn api_call_once<T: DeserializeOwned>(
+    url: &str,
+    method: &HttpMethod,
+    query_params: &HashMap<String, String>,
+    options: &ServerOptions,
+    policy: &RetryPolicy,
+) -> Attempt<T> {
+    // The options of a `Server` always have the client, so the connections are reused between the calls
+    let client = options.client.clone().unwrap_or_default();
+    let mut req = match method {
+        HttpMethod::GET => client.get(url),
+        HttpMethod::POST => client.post(url),
+    };
+    if let Some(token) = &options.auth_token {
+        req = req.header("Authorization", token);
+    }
 
-                Err(parsed.into())
-            }
-            other => Err(other.into()),
+    match req.query(query_params).send().await {
+        Ok(res) if !res.status().is_client_error() && !res.status().is_server_error() => {
+            Attempt::Done(
+                res.text()
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|res_str| Ok(serde_json::from_str::<T>(&res_str)?)),
+            )
+        }
+        Ok(res) => status_attempt(res, policy).await,
+        Err(err) if err.is_connect() => Attempt::Retry {
+            // The request didn't reach Horizon
+            error: err.into(),
+            retry_after: None,
+            maybe_processed: false,
         },
+        // Connection reset or timeout, the request could have been processed
+        Err(err) if err.is_timeout() || err.is_request() || err.is_body() => Attempt::Retry {
+            error: err.into(),
+            retry_after: None,
+            maybe_processed: true,
+        },
+        Err(err) => Attempt::Done(Err(err.into())),
+    }
+}
+
+// Attempt of an error response, the Horizon error is parsed out of it. A 5xx without a Horizon error in the body,
+// e.g. from a proxy, stays a status error
+async fn status_attempt<T>(res: Response, policy: &RetryPolicy) -> Attempt<T> {
+    let code = res.status().as_u16();
+    let retriable = policy.is_retriable_status(code);
+    let retry_after = res
+        .headers()
+        .get("Retry-After")
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    let status_error = res.error_for_status_ref().err().filter(|_| code >= 500);
+    let error = match res.text().await {
+        Ok(res_str) => match serde_json::from_str::<HorizonError>(&res_str) {
+            Ok(parsed) => parsed.into(),
+            Err(e) => status_error.map_or_else(|| e.into(), Into::into),
+        },
+        Err(e) => status_error.map_or_else(|| e.into(), Into::into),
+    };
+    if !retriable {
+        return Attempt::Done(Err(error));
+    }
+    Attempt::Retry {
+        error,
+        retry_after,
+        // Rate limited requests are rejected before they are processed
+        maybe_processed: code != 429,
     }
 }
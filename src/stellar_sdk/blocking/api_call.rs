@@ -0,0 +1,137 @@
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::thread;
+use ureq::{self, Error as UreqError, ErrorKind};
+
+use crate::retry::{parse_retry_after, RetryPolicy};
+use crate::stellar_sdk::api_call::Attempt;
+use crate::stellar_sdk::endpoints::ServerOptions;
+use crate::stellar_sdk::types::{HorizonError, HttpMethod};
+
+pub fn api_call<T: DeserializeOwned>(
+    url: String,
+    method: HttpMethod,
+    query_params: &HashMap<String, String>,
+    options: &ServerOptions,
+) -> Result<T, anyhow::Error> {
+    api_call_with_check(url, method, query_params, options, None)
+}
+
+/// Type of the check made before repeating a request which Horizon could have processed already
+pub(crate) type ProcessedCheck<'a, T> = &'a mut dyn FnMut() -> Result<Option<T>, anyhow::Error>;
+
+/// Making the call with the retry policy of the options, the blocking counterpart of `stellar_sdk::api_call::api_call_with_check`
+///
+/// GET requests are repeated on any transient failure. Other requests are only repeated if Horizon surely didn't
+/// process them, or if the `check` is given: it's called before repeating, if it finds the result of the earlier
+/// attempt that's returned, if it's `None` the request is repeated.
+pub(crate) fn api_call_with_check<T: DeserializeOwned>(
+    url: String,
+    method: HttpMethod,
+    query_params: &HashMap<String, String>,
+    options: &ServerOptions,
+    mut check: Option<ProcessedCheck<T>>,
+) -> Result<T, anyhow::Error> {
+    let policy = options.retry_policy();
+    let repeatable = matches!(method, HttpMethod::GET);
+    let mut retry = 0;
+    loop {
+        match api_call_once(&url, &method, query_params, options, &policy) {
+            Attempt::Done(result) => return result,
+            Attempt::Retry {
+                error,
+                retry_after,
+                maybe_processed,
+            } => {
+                retry += 1;
+                if !policy.allows_retry(retry)
+                    || (maybe_processed && !repeatable && check.is_none())
+                {
+                    return Err(error);
+                }
+                let Some(delay) = policy.delay(retry, retry_after) else {
+                    return Err(error);
+                };
+                thread::sleep(delay);
+                if maybe_processed && !repeatable {
+                    if let Some(check) = check.as_mut() {
+                        if let Some(found) = check()? {
+                            return Ok(found);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn api_call_once<T: DeserializeOwned>(
+    url: &str,
+    method: &HttpMethod,
+    query_params: &HashMap<String, String>,
+    options: &ServerOptions,
+    policy: &RetryPolicy,
+) -> Attempt<T> {
+    let mut req = match method {
+        HttpMethod::GET => ureq::get(url),
+        HttpMethod::POST => ureq::post(url),
+    };
+    if let Some(token) = &options.auth_token {
+        req = req.set("Authorization", token);
+    }
+
+    for query_param in query_params.iter() {
+        req = req.query(query_param.0, query_param.1);
+    }
+
+    match req.call() {
+        Ok(res) => Attempt::Done(
+            res.into_string()
+                .map_err(anyhow::Error::from)
+                .and_then(|res_str| Ok(serde_json::from_str::<T>(&res_str)?)),
+        ),
+        Err(UreqError::Status(code, res)) => {
+            let retriable = policy.is_retriable_status(code);
+            let retry_after = res.header("Retry-After").and_then(parse_retry_after);
+            // A 5xx without a Horizon error in the body, e.g. from a proxy, stays a status error
+            let status_error = |e: anyhow::Error| {
+                if code >= 500 {
+                    anyhow::anyhow!("{}: status code {}", url, code)
+                } else {
+                    e
+                }
+            };
+            let error = match res.into_string() {
+                Ok(res_str) => match serde_json::from_str::<HorizonError>(&res_str) {
+                    Ok(parsed) => parsed.into(),
+                    Err(e) => status_error(e.into()),
+                },
+                Err(e) => status_error(e.into()),
+            };
+            if !retriable {
+                return Attempt::Done(Err(error));
+            }
+            Attempt::Retry {
+                error,
+                retry_after,
+                // Rate limited requests are rejected before they are processed
+                maybe_processed: code != 429,
+            }
+        }
+        Err(UreqError::Transport(transport)) => match transport.kind() {
+            // The request didn't reach Horizon
+            ErrorKind::Dns | ErrorKind::ConnectionFailed => Attempt::Retry {
+                error: UreqError::Transport(transport).into(),
+                retry_after: None,
+                maybe_processed: false,
+            },
+            // Connection reset or timeout, the request could have been processed
+            ErrorKind::Io => Attempt::Retry {
+                error: UreqError::Transport(transport).into(),
+                retry_after: None,
+                maybe_processed: true,
+            },
+            _ => Attempt::Done(Err(UreqError::Transport(transport).into())),
+        },
+    }
+}
@@ -0,0 +1,9 @@
+//! The Horizon client blocking the thread, made with `ureq`, enabled by the `ureq` feature
+//!
+//! The default `Server` is async, made with `reqwest`.
+
+pub mod api_call;
+pub mod request;
+pub mod server;
+
+pub use server::Server;
@@ -0,0 +1,275 @@
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::stellar_sdk::blocking::api_call::{api_call, api_call_with_check};
+use crate::stellar_sdk::blocking::request::get_current_server_time;
+use crate::stellar_sdk::endpoints::server::{Server as AsyncServer, Timebounds};
+use crate::stellar_sdk::endpoints::{
+    AccountCallBuilder, AssetCallBuilder, ClaimableBalanceCallbuilder, EffectCallBuilder,
+    LedgerCallBuilder, LiquidityPoolCallBuilder, OfferCallBuilder, OperationCallBuilder,
+    OrderBookCallBuilder, PaymentCallBuilder, ServerOptions, StrictReceiveCallBuilder,
+    StrictSendCallBuilder, TradeAggregationCallBuilder, TradeCallBuilder, TransactionCallBuilder,
+};
+use crate::stellar_sdk::types::{
+    Account, Asset, ClaimableBalance, FeeStats, HorizonError, HttpMethod, Ledger, LiquidityPool,
+    Offer, Operation, StrictPathSource, SubmitTransactionResponse, Transaction,
+};
+
+use stellar_base::{transaction::Transaction as TransactionSBase, xdr::XDRSerialize};
+
+// How often `wait_for_transaction` asks Horizon, about one ledger
+const TRANSACTION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Horizon client blocking the thread, made with `ureq`
+///
+/// It has the methods of the async `Server` with the same meaning. The call builders are the ones of the async
+/// `Server`, their requests are sent by `call_blocking`.
+
+#[derive(Debug, Clone)]
+pub struct Server {
+    inner: AsyncServer,
+}
+
+impl Server {
+    pub fn new(server_url: String, opts: Option<ServerOptions>) -> Result<Self, anyhow::Error> {
+        Ok(Server {
+            inner: AsyncServer::new(server_url, opts)?,
+        })
+    }
+
+    pub fn server_url(&self) -> &str {
+        &self.inner.server_url
+    }
+
+    pub fn options(&self) -> &ServerOptions {
+        &self.inner.options
+    }
+
+    pub fn set_auth_token(&mut self, token: String) {
+        self.inner.set_auth_token(token);
+    }
+
+    /// The async `Server` of the same Horizon
+    pub fn as_async(&self) -> &AsyncServer {
+        &self.inner
+    }
+
+    fn get<T: serde::de::DeserializeOwned>(&self, path: String) -> Result<T, anyhow::Error> {
+        let url = format!("{}{}", self.inner.server_url, path);
+        api_call::<T>(url, HttpMethod::GET, &HashMap::new(), &self.inner.options)
+    }
+
+    pub fn load_account(&self, account_id: &str) -> Result<Account, anyhow::Error> {
+        self.get(format!("/accounts/{}", account_id))
+    }
+
+    pub fn accounts(&self) -> AccountCallBuilder<'_> {
+        self.inner.accounts()
+    }
+
+    pub fn load_transaction(&self, hash: &str) -> Result<Transaction, anyhow::Error> {
+        self.get(format!("/transactions/{}", hash))
+    }
+
+    // Polling the transaction by its hash until it's in a ledger, see at `Server::wait_for_transaction`
+    pub fn wait_for_transaction(
+        &self,
+        hash: &str,
+        timeout: Duration,
+    ) -> Result<Option<Transaction>, anyhow::Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.load_transaction(hash) {
+                Ok(transaction) => return Ok(Some(transaction)),
+                Err(err) => match err.downcast_ref::<HorizonError>() {
+                    Some(horizon_error) if horizon_error.status == 404 => {}
+                    _ => return Err(err),
+                },
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            std::thread::sleep(TRANSACTION_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    pub fn transactions(&self) -> TransactionCallBuilder<'_> {
+        self.inner.transactions()
+    }
+
+    pub fn load_ledger(&self, sequence: u64) -> Result<Ledger, anyhow::Error> {
+        self.get(format!("/ledgers/{}", sequence))
+    }
+
+    pub fn ledgers(&self) -> LedgerCallBuilder<'_> {
+        self.inner.ledgers()
+    }
+
+    pub fn load_offer(&self, offer_id: &str) -> Result<Offer, anyhow::Error> {
+        self.get(format!("/offers/{}", offer_id))
+    }
+
+    pub fn offers(&self) -> OfferCallBuilder<'_> {
+        self.inner.offers()
+    }
+
+    pub fn load_operation(&self, operation_id: &str) -> Result<Operation, anyhow::Error> {
+        self.get(format!("/operations/{}", operation_id))
+    }
+
+    pub fn operations(&self) -> OperationCallBuilder<'_> {
+        self.inner.operations()
+    }
+
+    pub fn load_liquidity_pool(
+        &self,
+        liquidity_pool_id: &str,
+    ) -> Result<LiquidityPool, anyhow::Error> {
+        self.get(format!("/liquidity_pools/{}", liquidity_pool_id))
+    }
+
+    pub fn liquidity_pools(&self) -> LiquidityPoolCallBuilder<'_> {
+        self.inner.liquidity_pools()
+    }
+
+    pub fn load_claimable_balance(
+        &self,
+        claimable_balance_id: &str,
+    ) -> Result<ClaimableBalance, anyhow::Error> {
+        self.get(format!("/claimable_balances/{}", claimable_balance_id))
+    }
+
+    pub fn claimable_balances(&self) -> ClaimableBalanceCallbuilder<'_> {
+        self.inner.claimable_balances()
+    }
+
+    pub fn trade_aggregations<'a>(
+        &'a self,
+        base: &'a Asset,
+        counter: &'a Asset,
+        resolution: &'a str,
+    ) -> TradeAggregationCallBuilder<'a> {
+        self.inner.trade_aggregations(base, counter, resolution)
+    }
+
+    pub fn order_books(&self, selling: Asset, buying: Asset) -> OrderBookCallBuilder<'_> {
+        self.inner.order_books(selling, buying)
+    }
+
+    pub fn strict_receive_paths(
+        &self,
+        source: &StrictPathSource,
+        destination_asset: Asset,
+        destination_amount: String,
+    ) -> StrictReceiveCallBuilder<'_> {
+        self.inner
+            .strict_receive_paths(source, destination_asset, destination_amount)
+    }
+
+    pub fn strict_send_paths<'a>(
+        &'a self,
+        destination: &StrictPathSource,
+        source_asset: &'a Asset,
+        source_amount: &'a str,
+    ) -> StrictSendCallBuilder<'a> {
+        self.inner
+            .strict_send_paths(destination, source_asset, source_amount)
+    }
+
+    pub fn trades(&self) -> TradeCallBuilder<'_> {
+        self.inner.trades()
+    }
+
+    pub fn payments(&self) -> PaymentCallBuilder<'_> {
+        self.inner.payments()
+    }
+
+    pub fn assets(&self) -> AssetCallBuilder<'_> {
+        self.inner.assets()
+    }
+
+    pub fn effects(&self) -> EffectCallBuilder<'_> {
+        self.inner.effects()
+    }
+
+    pub fn fee_stats(&self) -> Result<FeeStats, anyhow::Error> {
+        self.get("/fee_stats".to_string())
+    }
+
+    pub fn fetch_base_fee(&self) -> Result<String, anyhow::Error> {
+        let fee_stats = self.fee_stats()?;
+        Ok(fee_stats.last_ledger_base_fee)
+    }
+
+    pub fn fetch_timebounds(
+        &self,
+        seconds: i64,
+        is_retry: bool,
+    ) -> Result<Timebounds, anyhow::Error> {
+        // Falling back to the local time if the time of the server can't be read
+        let now = match get_current_server_time(&self.inner.server_url) {
+            Some(server_time) if !is_retry => server_time,
+            _ => Local::now().timestamp(),
+        };
+        Ok(Timebounds {
+            min_time: 0,
+            max_time: now + seconds,
+        })
+    }
+
+    pub fn submit_transaction(
+        &self,
+        transaction: TransactionSBase,
+    ) -> Result<SubmitTransactionResponse, anyhow::Error> {
+        let tx = transaction.into_envelope().xdr_base64()?;
+        self.submit_transaction_envelope(&tx)
+    }
+
+    // Submitting an already signed and encoded envelope, see at `Server::submit_transaction_envelope`
+    pub fn submit_transaction_envelope(
+        &self,
+        envelope_xdr: &str,
+    ) -> Result<SubmitTransactionResponse, anyhow::Error> {
+        let url = format!("{}/transactions", self.inner.server_url);
+
+        let mut query = HashMap::new();
+        query.insert("tx".to_string(), envelope_xdr.to_string());
+
+        let hash = self.inner.envelope_hash(envelope_xdr);
+        let mut check = || match &hash {
+            Some(hash) => self.find_submitted_transaction(hash),
+            None => Ok(None),
+        };
+
+        api_call_with_check::<SubmitTransactionResponse>(
+            url,
+            HttpMethod::POST,
+            &query,
+            &self.inner.options,
+            hash.is_some().then_some(&mut check),
+        )
+    }
+
+    // The submitted transaction if Horizon knows about it, `None` if it's not found
+    fn find_submitted_transaction(
+        &self,
+        hash: &str,
+    ) -> Result<Option<SubmitTransactionResponse>, anyhow::Error> {
+        match self.get(format!("/transactions/{}", hash)) {
+            Ok(transaction) => Ok(Some(transaction)),
+            Err(err) => match err.downcast_ref::<HorizonError>() {
+                Some(horizon_error) if horizon_error.status == 404 => Ok(None),
+                _ => Err(err),
+            },
+        }
+    }
+}
+
+impl From<AsyncServer> for Server {
+    fn from(inner: AsyncServer) -> Self {
+        Server { inner }
+    }
+}
@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, CallBuilder, Server};
+use crate::stellar_sdk::endpoints::{CallBuilder, HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::types::{Account, Asset};
 use crate::stellar_sdk::utils::{Direction, Endpoint};
 
@@ -10,7 +9,7 @@ pub struct AccountCallBuilder<'a> {
     server_url: &'a str,
     endpoint: Endpoint,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> AccountCallBuilder<'a> {
@@ -19,7 +18,7 @@ impl<'a> AccountCallBuilder<'a> {
             server_url: &s.server_url,
             endpoint: Endpoint::None,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         }
     }
 
@@ -53,7 +52,7 @@ impl<'a> AccountCallBuilder<'a> {
 }
 
 impl<'a> CallBuilder<Account> for AccountCallBuilder<'a> {
-    fn call(&self) -> Result<Record<Account>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!(
             "{}{}{}",
             &self.server_url,
@@ -61,12 +60,11 @@ impl<'a> CallBuilder<Account> for AccountCallBuilder<'a> {
             "/accounts",
         );
 
-        api_call::<Record<Account>>(
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 
     fn cursor(&mut self, cursor: &str) -> &mut Self {
@@ -101,8 +99,9 @@ impl<'a> CallBuilder<Account> for AccountCallBuilder<'a> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn limit_account_call_builder() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn limit_account_call_builder() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
@@ -112,6 +111,7 @@ mod tests {
             .liquidity_pool("a468d41d8e9b8f3c7209651608b74b7db7ac9952dcae0cdf24871d1d9c7b0088")
             .limit(3)
             .call()
+            .await
             .unwrap();
 
         assert_eq!(account_records._embedded.records.len(), 3);
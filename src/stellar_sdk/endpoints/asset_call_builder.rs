@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, CallBuilder, Server};
+use crate::stellar_sdk::endpoints::{CallBuilder, HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::types::AssetHorizon;
 use crate::stellar_sdk::utils::{Direction, Endpoint};
 
@@ -10,7 +9,7 @@ pub struct AssetCallBuilder<'a> {
     server_url: &'a str,
     endpoint: Endpoint,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> AssetCallBuilder<'a> {
@@ -19,7 +18,7 @@ impl<'a> AssetCallBuilder<'a> {
             server_url: &s.server_url,
             endpoint: Endpoint::None,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         }
     }
 
@@ -66,7 +65,7 @@ impl<'a> CallBuilder<AssetHorizon> for AssetCallBuilder<'a> {
         self
     }
 
-    fn call(&self) -> Result<Record<AssetHorizon>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!(
             "{}{}{}",
             &self.server_url,
@@ -74,12 +73,11 @@ impl<'a> CallBuilder<AssetHorizon> for AssetCallBuilder<'a> {
             "/assets",
         );
 
-        api_call::<Record<AssetHorizon>>(
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 }
 
@@ -87,8 +85,9 @@ impl<'a> CallBuilder<AssetHorizon> for AssetCallBuilder<'a> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn assets_horizon_test() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn assets_horizon_test() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
@@ -98,6 +97,7 @@ mod tests {
             .asset_issuer("GA5BUT4SND34VRUJGFEVLG6LMEYOU5HSSYZLX673I2IJVRLLPATMH4RN")
             .limit(3)
             .call()
+            .await
             .unwrap();
 
         assert_eq!(asset_records._embedded.records.len(), 3);
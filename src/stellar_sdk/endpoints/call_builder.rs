@@ -1,12 +1,62 @@
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::future::Future;
+
+use crate::stellar_sdk::api_call::api_call;
 use crate::stellar_sdk::{
-    endpoints::horizon::Record,
+    endpoints::{horizon::Record, ServerOptions},
+    types::HttpMethod,
     utils::{Direction, Endpoint},
 };
 
+/// The GET request a call builder sends
+///
+/// - url: String - the URL of the endpoint, without the query
+/// - query_params: &HashMap<String, String> - the query parameters set on the builder
+/// - options: &ServerOptions - the options of the `Server` the builder was made by
+
+#[derive(Debug)]
+pub struct HorizonRequest<'a> {
+    pub url: String,
+    pub query_params: &'a HashMap<String, String>,
+    pub options: &'a ServerOptions,
+}
+
 pub trait CallBuilder<T> {
     fn cursor(&mut self, cursor: &str) -> &mut Self;
     fn order(&mut self, dir: Direction) -> &mut Self;
     fn limit(&mut self, limit_number: u8) -> &mut Self;
-    fn call(&self) -> Result<Record<T>, anyhow::Error>;
     fn for_endpoint(&mut self, endpoint: Endpoint) -> &mut Self;
+    fn request(&self) -> HorizonRequest<'_>;
+
+    fn call(&self) -> impl Future<Output = Result<Record<T>, anyhow::Error>> + Send
+    where
+        T: DeserializeOwned + Send,
+    {
+        let request = self.request();
+        async move {
+            api_call::<Record<T>>(
+                request.url,
+                HttpMethod::GET,
+                request.query_params,
+                request.options,
+            )
+            .await
+        }
+    }
+
+    /// The same call blocking the thread, made with `ureq`
+    #[cfg(feature = "ureq")]
+    fn call_blocking(&self) -> Result<Record<T>, anyhow::Error>
+    where
+        T: DeserializeOwned,
+    {
+        let request = self.request();
+        crate::stellar_sdk::blocking::api_call::api_call::<Record<T>>(
+            request.url,
+            HttpMethod::GET,
+            request.query_params,
+            request.options,
+        )
+    }
 }
@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, CallBuilder, Server};
+use crate::stellar_sdk::endpoints::{CallBuilder, HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::types::{Asset, ClaimableBalance};
 use crate::stellar_sdk::utils::{Direction, Endpoint};
 
@@ -10,7 +9,7 @@ pub struct ClaimableBalanceCallbuilder<'a> {
     server_url: &'a str,
     endpoint: Endpoint,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> ClaimableBalanceCallbuilder<'a> {
@@ -19,7 +18,7 @@ impl<'a> ClaimableBalanceCallbuilder<'a> {
             server_url: &s.server_url,
             endpoint: Endpoint::None,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         }
     }
 
@@ -73,7 +72,7 @@ impl<'a> CallBuilder<ClaimableBalance> for ClaimableBalanceCallbuilder<'a> {
         self
     }
 
-    fn call(&self) -> Result<Record<ClaimableBalance>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!(
             "{}{}{}",
             &self.server_url,
@@ -81,12 +80,11 @@ impl<'a> CallBuilder<ClaimableBalance> for ClaimableBalanceCallbuilder<'a> {
             "/claimable_balances",
         );
 
-        api_call::<Record<ClaimableBalance>>(
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 }
 
@@ -94,8 +92,9 @@ impl<'a> CallBuilder<ClaimableBalance> for ClaimableBalanceCallbuilder<'a> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_claimable_balance_sponsor() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_claimable_balance_sponsor() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
@@ -103,6 +102,7 @@ mod tests {
             .sponsor("GDCJIHD3623OCYNH65UUQC3NLG2D6YCNCDPZULRLCLOA76TBQRL6A3TF")
             .limit(1)
             .call()
+            .await
             .unwrap();
 
         if let Some(x) = &cbcb._embedded.records[0].sponsor {
@@ -113,8 +113,9 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_claimable_balance_for_asset() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_claimable_balance_for_asset() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
@@ -128,6 +129,7 @@ mod tests {
             .asset(&lsp)
             .limit(1)
             .call()
+            .await
             .unwrap();
 
         assert_eq!(lsp.to_string(), cbcb._embedded.records[0].asset);
@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, CallBuilder, Server};
+use crate::stellar_sdk::endpoints::{CallBuilder, HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::types::Effect;
 use crate::stellar_sdk::utils::{Direction, Endpoint};
 
@@ -10,7 +9,7 @@ pub struct EffectCallBuilder<'a> {
     server_url: &'a str,
     endpoint: Endpoint,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> EffectCallBuilder<'a> {
@@ -19,7 +18,7 @@ impl<'a> EffectCallBuilder<'a> {
             server_url: &s.server_url,
             endpoint: Endpoint::None,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         }
     }
 }
@@ -52,7 +51,7 @@ impl<'a> CallBuilder<Effect> for EffectCallBuilder<'a> {
         self
     }
 
-    fn call(&self) -> Result<Record<Effect>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!(
             "{}{}{}",
             &self.server_url,
@@ -60,12 +59,11 @@ impl<'a> CallBuilder<Effect> for EffectCallBuilder<'a> {
             "/effects",
         );
 
-        api_call::<Record<Effect>>(
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 }
 
@@ -73,14 +71,15 @@ impl<'a> CallBuilder<Effect> for EffectCallBuilder<'a> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn assets_horizon_test() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn assets_horizon_test() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
         let mut ecb = EffectCallBuilder::new(&s);
 
-        let effect_records = ecb.limit(200).call().unwrap();
+        let effect_records = ecb.limit(200).call().await.unwrap();
 
         assert_eq!(effect_records._embedded.records.len(), 200);
     }
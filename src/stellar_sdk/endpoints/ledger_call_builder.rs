@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, CallBuilder, Server};
+use crate::stellar_sdk::endpoints::{CallBuilder, HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::types::Ledger;
 use crate::stellar_sdk::utils::{Direction, Endpoint};
 
@@ -10,7 +9,7 @@ pub struct LedgerCallBuilder<'a> {
     server_url: &'a str,
     endpoint: Endpoint,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> LedgerCallBuilder<'a> {
@@ -19,7 +18,7 @@ impl<'a> LedgerCallBuilder<'a> {
             server_url: &s.server_url,
             endpoint: Endpoint::None,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         }
     }
 }
@@ -52,7 +51,7 @@ impl<'a> CallBuilder<Ledger> for LedgerCallBuilder<'a> {
         self
     }
 
-    fn call(&self) -> Result<Record<Ledger>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!(
             "{}{}{}",
             &self.server_url,
@@ -60,28 +59,27 @@ impl<'a> CallBuilder<Ledger> for LedgerCallBuilder<'a> {
             "/ledgers",
         );
 
-        api_call::<Record<Ledger>>(
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "mock"))]
 mod tests {
     use super::*;
+    use crate::stellar_sdk::endpoints::mock_horizon::TestHorizon;
 
-    #[test]
-    fn ledger_horizon_test() {
-        let s = Server::new(String::from("https://horizon.stellar.org"), None)
-            .expect("Cannot connect to insecure horizon server");
+    #[tokio::test]
+    async fn ledger_horizon_test() {
+        let test_horizon = TestHorizon::with_payments(3).await;
 
-        let mut lcb = LedgerCallBuilder::new(&s);
+        let mut lcb = LedgerCallBuilder::new(&test_horizon.server);
 
-        let ledger_records = lcb.limit(200).call().unwrap();
+        let ledger_records = lcb.limit(2).call().await.unwrap();
 
-        assert_eq!(ledger_records._embedded.records.len(), 200);
+        assert_eq!(ledger_records._embedded.records.len(), 2);
     }
 }
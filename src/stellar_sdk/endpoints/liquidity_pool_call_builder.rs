@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, CallBuilder, Server};
+use crate::stellar_sdk::endpoints::{CallBuilder, HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::types::{Asset, LiquidityPool};
 use crate::stellar_sdk::utils::{Direction, Endpoint};
 
@@ -10,7 +9,7 @@ pub struct LiquidityPoolCallBuilder<'a> {
     server_url: &'a str,
     endpoint: Endpoint,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> LiquidityPoolCallBuilder<'a> {
@@ -19,7 +18,7 @@ impl<'a> LiquidityPoolCallBuilder<'a> {
             server_url: &s.server_url,
             endpoint: Endpoint::None,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         }
     }
 
@@ -65,7 +64,7 @@ impl<'a> CallBuilder<LiquidityPool> for LiquidityPoolCallBuilder<'a> {
         self
     }
 
-    fn call(&self) -> Result<Record<LiquidityPool>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!(
             "{}{}{}",
             &self.server_url,
@@ -73,12 +72,11 @@ impl<'a> CallBuilder<LiquidityPool> for LiquidityPoolCallBuilder<'a> {
             "/liquidity_pools",
         );
 
-        api_call::<Record<LiquidityPool>>(
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 }
 
@@ -86,20 +84,22 @@ impl<'a> CallBuilder<LiquidityPool> for LiquidityPoolCallBuilder<'a> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_limit_liquidity_pools() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_limit_liquidity_pools() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
         let mut lpcb = LiquidityPoolCallBuilder::new(&s);
 
-        let records = lpcb.limit(200).call().unwrap();
+        let records = lpcb.limit(200).call().await.unwrap();
 
         assert_eq!(records._embedded.records.len(), 200);
     }
 
-    #[test]
-    fn test_liquidity_pools_for_assets() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_liquidity_pools_for_assets() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
@@ -118,7 +118,7 @@ mod tests {
 
         let mut lpcb = LiquidityPoolCallBuilder::new(&s);
 
-        let records = lpcb.for_assets(vec![y_xlm, y_usdc]).call().unwrap();
+        let records = lpcb.for_assets(vec![y_xlm, y_usdc]).call().await.unwrap();
 
         assert_eq!(records._embedded.records[0].reserves[0].asset, y_xlm_str);
     }
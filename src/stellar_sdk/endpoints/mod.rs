@@ -20,7 +20,7 @@ pub mod transaction_call_builder;
 
 pub use account_call_builder::AccountCallBuilder;
 pub use asset_call_builder::AssetCallBuilder;
-pub use call_builder::CallBuilder;
+pub use call_builder::{CallBuilder, HorizonRequest};
 pub use claimable_balance_call_builder::ClaimableBalanceCallbuilder;
 pub use effect_call_builder::EffectCallBuilder;
 pub use ledger_call_builder::LedgerCallBuilder;
@@ -29,10 +29,104 @@ pub use offer_call_builder::OfferCallBuilder;
 pub use operation_call_builder::OperationCallBuilder;
 pub use order_book_call_builder::OrderBookCallBuilder;
 pub use payment_call_builder::PaymentCallBuilder;
-pub use server::Server;
+pub use server::{Server, ServerOptions};
 pub use strict_receive_call_builder::StrictReceiveCallBuilder;
 pub use strict_send_call_builder::StrictSendCallBuilder;
 pub use toml_resolver::StellarTomlResolver;
 pub use trade_aggregation_call_builder::TradeAggregationCallBuilder;
 pub use trade_call_builder::TradeCallBuilder;
 pub use transaction_call_builder::TransactionCallBuilder;
+
+/// A local Horizon for the tests of the endpoints, with an account which made a few payments
+#[cfg(all(test, feature = "mock"))]
+pub(crate) mod mock_horizon {
+    use std::str::FromStr;
+    use stellar_base::{
+        amount::Amount,
+        asset::Asset,
+        crypto::SodiumKeyPair,
+        memo::Memo,
+        operations::Operation,
+        transaction::{Transaction, MIN_BASE_FEE},
+        Network, PublicKey,
+    };
+
+    use super::{Server, ServerOptions};
+    use crate::mock::MockHorizon;
+    use crate::stellar_sdk::keypair::Keypair;
+
+    pub(crate) const PASSPHRASE: &str = "Pi Testnet";
+
+    /// - horizon: MockHorizon - the local Horizon, it's stopped when dropped
+    /// - server: Server - client of the local Horizon
+    /// - source: SodiumKeyPair - the account which made the payments
+    /// - account_id: String - the account ID of the source
+    /// - txids: `Vec<String>` - the hashes of the payments, one ledger was closed by each
+    pub(crate) struct TestHorizon {
+        pub horizon: MockHorizon,
+        pub server: Server,
+        pub source: SodiumKeyPair,
+        pub account_id: String,
+        pub txids: Vec<String>,
+    }
+
+    impl TestHorizon {
+        /// Starting the local Horizon and making the payments of 0.1 from a new account
+        pub(crate) async fn with_payments(count: usize) -> Self {
+            let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+            let options = ServerOptions {
+                allow_http: Some(true),
+                network_passphrase: Some(PASSPHRASE.to_string()),
+                ..ServerOptions::default()
+            };
+            let server = Server::new(horizon.url(), Some(options)).unwrap();
+            let source = Keypair::random().unwrap();
+            let account_id = source.public_key();
+            horizon.create_account(&account_id, "100").unwrap();
+            let destination = Keypair::random().unwrap().public_key();
+            horizon.create_account(&destination, "1").unwrap();
+
+            let mut test_horizon = TestHorizon {
+                horizon,
+                server,
+                source: SodiumKeyPair::from(source),
+                account_id,
+                txids: Vec::new(),
+            };
+            for _ in 0..count {
+                let transaction = test_horizon.payment(&destination).await;
+                let response = test_horizon
+                    .server
+                    .submit_transaction(transaction)
+                    .await
+                    .unwrap();
+                test_horizon.txids.push(response.hash);
+            }
+            test_horizon
+        }
+
+        /// A signed payment of 0.1 from the source, with the next sequence number of the source
+        pub(crate) async fn payment(&self, destination: &str) -> Transaction {
+            let payment = Operation::new_payment()
+                .with_destination(PublicKey::from_account_id(destination).unwrap())
+                .with_amount(Amount::from_str("0.1").unwrap())
+                .unwrap()
+                .with_asset(Asset::new_native())
+                .build()
+                .unwrap();
+            let account = self.server.load_account(&self.account_id).await.unwrap();
+            let sequence = account.sequence.parse::<i64>().unwrap() + 1;
+
+            let mut transaction =
+                Transaction::builder(self.source.public_key(), sequence, MIN_BASE_FEE)
+                    .with_memo(Memo::Text("stellar_sdk_test".to_string()))
+                    .add_operation(payment)
+                    .into_transaction()
+                    .unwrap();
+            transaction
+                .sign(self.source.as_ref(), &Network::new(PASSPHRASE.to_string()))
+                .unwrap();
+            transaction
+        }
+    }
+}
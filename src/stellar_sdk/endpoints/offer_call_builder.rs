@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, CallBuilder, Server};
+use crate::stellar_sdk::endpoints::{CallBuilder, HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::types::{Asset, Offer};
 use crate::stellar_sdk::utils::{Direction, Endpoint};
 
@@ -10,7 +9,7 @@ pub struct OfferCallBuilder<'a> {
     server_url: &'a str,
     endpoint: Endpoint,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> OfferCallBuilder<'a> {
@@ -19,7 +18,7 @@ impl<'a> OfferCallBuilder<'a> {
             server_url: &s.server_url,
             endpoint: Endpoint::None,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         }
     }
 
@@ -80,7 +79,7 @@ impl<'a> CallBuilder<Offer> for OfferCallBuilder<'a> {
         self
     }
 
-    fn call(&self) -> Result<Record<Offer>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!(
             "{}{}{}",
             &self.server_url,
@@ -88,12 +87,11 @@ impl<'a> CallBuilder<Offer> for OfferCallBuilder<'a> {
             "/offers"
         );
 
-        api_call::<Record<Offer>>(
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 }
 
@@ -101,20 +99,22 @@ impl<'a> CallBuilder<Offer> for OfferCallBuilder<'a> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_offer_call_builder() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_offer_call_builder() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
         let mut ocb = OfferCallBuilder::new(&s);
 
-        let offer_records = ocb.limit(200).call().unwrap();
+        let offer_records = ocb.limit(200).call().await.unwrap();
 
         assert_eq!(offer_records._embedded.records.len(), 200);
     }
 
-    #[test]
-    fn test_offer_call_builder_selling_buying() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_offer_call_builder_selling_buying() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
@@ -130,6 +130,7 @@ mod tests {
             .buying(&xlm)
             .limit(2)
             .call()
+            .await
             .unwrap();
 
         assert_eq!(
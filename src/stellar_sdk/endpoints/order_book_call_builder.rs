@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::Server;
+use crate::stellar_sdk::endpoints::{Server, ServerOptions};
 use crate::stellar_sdk::types::{Asset, OrderBook};
 use crate::stellar_sdk::utils::Endpoint;
 
@@ -10,7 +10,7 @@ pub struct OrderBookCallBuilder<'a> {
     server_url: &'a str,
     endpoint: Endpoint,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> OrderBookCallBuilder<'a> {
@@ -19,7 +19,7 @@ impl<'a> OrderBookCallBuilder<'a> {
             server_url: &s.server_url,
             endpoint: Endpoint::None,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         };
 
         new_self
@@ -40,19 +40,33 @@ impl<'a> OrderBookCallBuilder<'a> {
         self
     }
 
-    pub fn call(&self) -> Result<OrderBook, anyhow::Error> {
-        let url = format!(
+    pub async fn call(&self) -> Result<OrderBook, anyhow::Error> {
+        api_call::<OrderBook>(
+            self.url(),
+            crate::stellar_sdk::types::HttpMethod::GET,
+            &self.query_params,
+            self.options,
+        )
+        .await
+    }
+
+    /// The same call blocking the thread, made with `ureq`
+    #[cfg(feature = "ureq")]
+    pub fn call_blocking(&self) -> Result<OrderBook, anyhow::Error> {
+        crate::stellar_sdk::blocking::api_call::api_call::<OrderBook>(
+            self.url(),
+            crate::stellar_sdk::types::HttpMethod::GET,
+            &self.query_params,
+            self.options,
+        )
+    }
+
+    fn url(&self) -> String {
+        format!(
             "{}{}{}",
             &self.server_url,
             self.endpoint.as_str(),
             "/order_book"
-        );
-
-        api_call::<OrderBook>(
-            url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
         )
     }
 }
@@ -61,8 +75,9 @@ impl<'a> OrderBookCallBuilder<'a> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_order_book() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_order_book() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
@@ -75,7 +90,7 @@ mod tests {
 
         let mut ocb = OrderBookCallBuilder::new(&s, native, bat);
 
-        let op_records = ocb.limit(1).call().unwrap();
+        let op_records = ocb.limit(1).call().await.unwrap();
 
         assert_eq!(1, op_records.bids.len());
     }
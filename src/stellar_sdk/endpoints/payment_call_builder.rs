@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, CallBuilder, Server};
+use crate::stellar_sdk::endpoints::{CallBuilder, HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::types::Operation;
 use crate::stellar_sdk::utils::{Direction, Endpoint};
 
@@ -10,7 +9,7 @@ pub struct PaymentCallBuilder<'a> {
     server_url: &'a str,
     endpoint: Endpoint,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> PaymentCallBuilder<'a> {
@@ -19,7 +18,7 @@ impl<'a> PaymentCallBuilder<'a> {
             server_url: &s.server_url,
             endpoint: Endpoint::None,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         }
     }
 }
@@ -52,7 +51,7 @@ impl<'a> CallBuilder<Operation> for PaymentCallBuilder<'a> {
         self
     }
 
-    fn call(&self) -> Result<Record<Operation>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!(
             "{}{}{}",
             &self.server_url,
@@ -60,34 +59,32 @@ impl<'a> CallBuilder<Operation> for PaymentCallBuilder<'a> {
             "/payments",
         );
 
-        api_call::<Record<Operation>>(
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "mock"))]
 mod tests {
     use super::*;
+    use crate::stellar_sdk::endpoints::mock_horizon::TestHorizon;
 
-    #[test]
-    fn limit_payment_call_builder() {
-        let s = Server::new(String::from("https://horizon.stellar.org"), None)
-            .expect("Cannot connect to insecure horizon server");
+    #[tokio::test]
+    async fn limit_payment_call_builder() {
+        let test_horizon = TestHorizon::with_payments(3).await;
 
-        let mut pcb = PaymentCallBuilder::new(&s);
+        let mut pcb = PaymentCallBuilder::new(&test_horizon.server);
 
         let payment_records = pcb
-            .for_endpoint(Endpoint::Accounts(String::from(
-                "GAUZUPTHOMSZEV65VNSRMUDAAE4VBMSRYYAX3UOWYU3BQUZ6OK65NOWM",
-            )))
-            .limit(200)
+            .for_endpoint(Endpoint::Accounts(test_horizon.account_id.clone()))
+            .limit(2)
             .call()
+            .await
             .unwrap();
 
-        assert_eq!(payment_records._embedded.records.len(), 200);
+        assert_eq!(payment_records._embedded.records.len(), 2);
     }
 }
@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, Server};
+use crate::stellar_sdk::endpoints::{HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::lib::CallBuilder;
 use crate::stellar_sdk::types::{Asset, StrictPath, StrictPathSource};
 use crate::stellar_sdk::utils::{Direction, Endpoint};
@@ -10,7 +9,7 @@ use crate::stellar_sdk::utils::{Direction, Endpoint};
 pub struct StrictReceiveCallBuilder<'a> {
     server_url: &'a str,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> CallBuilder<StrictPath> for StrictReceiveCallBuilder<'a> {
@@ -39,14 +38,14 @@ impl<'a> CallBuilder<StrictPath> for StrictReceiveCallBuilder<'a> {
         self
     }
 
-    fn call(&self) -> Result<Record<StrictPath>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!("{}{}", &self.server_url, "/paths/strict-receive");
-        api_call::<Record<StrictPath>>(
+
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 }
 
@@ -60,7 +59,7 @@ impl<'a> StrictReceiveCallBuilder<'a> {
         let mut new_self = Self {
             server_url: &s.server_url,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         };
 
         match source {
@@ -94,8 +93,9 @@ impl<'a> StrictReceiveCallBuilder<'a> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_strict_receive() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_strict_receive() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
@@ -115,6 +115,7 @@ mod tests {
         )
         .limit(1)
         .call()
+        .await
         .unwrap();
     }
 }
@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, Server};
+use crate::stellar_sdk::endpoints::{HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::lib::CallBuilder;
 use crate::stellar_sdk::types::{Asset, StrictPath, StrictPathSource};
 use crate::stellar_sdk::utils::{Direction, Endpoint};
@@ -10,7 +9,7 @@ use crate::stellar_sdk::utils::{Direction, Endpoint};
 pub struct StrictSendCallBuilder<'a> {
     server_url: &'a str,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> CallBuilder<StrictPath> for StrictSendCallBuilder<'a> {
@@ -39,14 +38,14 @@ impl<'a> CallBuilder<StrictPath> for StrictSendCallBuilder<'a> {
         self
     }
 
-    fn call(&self) -> Result<Record<StrictPath>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!("{}{}", &self.server_url, "/paths/strict-send");
-        api_call::<Record<StrictPath>>(
+
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 }
 
@@ -60,7 +59,7 @@ impl<'a> StrictSendCallBuilder<'a> {
         let mut new_self = Self {
             server_url: &s.server_url,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         };
 
         match destination {
@@ -94,8 +93,9 @@ impl<'a> StrictSendCallBuilder<'a> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_strict_send() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_strict_send() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
@@ -106,6 +106,7 @@ mod tests {
             StrictSendCallBuilder::new(&s, &StrictPathSource::Account(rbt_issuer), &native, "20")
                 .limit(1)
                 .call()
+                .await
                 .unwrap();
     }
 }
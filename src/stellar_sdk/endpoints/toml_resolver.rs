@@ -77,7 +77,18 @@ pub struct StellarToml {
 }
 
 impl StellarTomlResolver {
-    pub fn resolve(domain: &str) -> Result<StellarToml, anyhow::Error> {
+    pub async fn resolve(domain: &str) -> Result<StellarToml, anyhow::Error> {
+        let url = format!("https://{}/.well-known/stellar.toml", domain);
+
+        let res = reqwest::get(&url).await?.error_for_status()?;
+        let res_str = res.text().await?;
+
+        Ok(toml::from_str::<StellarToml>(&res_str)?)
+    }
+
+    /// The same resolving blocking the thread, made with `ureq`
+    #[cfg(feature = "ureq")]
+    pub fn resolve_blocking(domain: &str) -> Result<StellarToml, anyhow::Error> {
         let url = format!("https://{}/.well-known/stellar.toml", domain);
 
         let res = ureq::get(&url).call()?;
@@ -91,9 +102,10 @@ impl StellarTomlResolver {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_toml_resolve() {
-        let rabet_toml = StellarTomlResolver::resolve("rabet.io").unwrap();
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_toml_resolve() {
+        let rabet_toml = StellarTomlResolver::resolve("rabet.io").await.unwrap();
 
         if let Some(d) = rabet_toml.DOCUMENTATION {
             assert_eq!(Some(String::from("Rabet")), d.ORG_DBA);
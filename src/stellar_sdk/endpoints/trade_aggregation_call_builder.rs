@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, Server};
+use crate::stellar_sdk::endpoints::{HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::lib::CallBuilder;
 use crate::stellar_sdk::types::{Asset, TradeAggregation};
 use crate::stellar_sdk::utils::{Direction, Endpoint};
@@ -11,7 +10,7 @@ pub struct TradeAggregationCallBuilder<'a> {
     server_url: &'a str,
     endpoint: Endpoint,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> TradeAggregationCallBuilder<'a> {
@@ -20,7 +19,7 @@ impl<'a> TradeAggregationCallBuilder<'a> {
             server_url: &s.server_url,
             endpoint: Endpoint::None,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         };
 
         new_self
@@ -65,14 +64,14 @@ impl<'a> CallBuilder<TradeAggregation> for TradeAggregationCallBuilder<'a> {
         self
     }
 
-    fn call(&self) -> Result<Record<TradeAggregation>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!("{}{}", &self.server_url, "/trade_aggregations");
-        api_call::<Record<TradeAggregation>>(
+
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 }
 
@@ -80,8 +79,9 @@ impl<'a> CallBuilder<TradeAggregation> for TradeAggregationCallBuilder<'a> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_trade_aggregation() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_trade_aggregation() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
@@ -94,7 +94,7 @@ mod tests {
 
         let mut ocb = TradeAggregationCallBuilder::new(&s, &native, &bat, "60000");
 
-        let op_records = ocb.limit(1).call().unwrap();
+        let op_records = ocb.limit(1).call().await.unwrap();
 
         assert_eq!(1, op_records._embedded.records.len());
     }
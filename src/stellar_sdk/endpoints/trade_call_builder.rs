@@ -1,5 +1,4 @@
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, CallBuilder, Server};
+use crate::stellar_sdk::endpoints::{CallBuilder, HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::types::{Asset, Trade};
 use crate::stellar_sdk::utils::{Direction, Endpoint, TradeType};
 use std::collections::HashMap;
@@ -9,7 +8,7 @@ pub struct TradeCallBuilder<'a> {
     server_url: &'a str,
     endpoint: Endpoint,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> TradeCallBuilder<'a> {
@@ -18,7 +17,7 @@ impl<'a> TradeCallBuilder<'a> {
             server_url: &s.server_url,
             endpoint: Endpoint::None,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         }
     }
 
@@ -47,7 +46,7 @@ impl<'a> TradeCallBuilder<'a> {
 }
 
 impl<'a> CallBuilder<Trade> for TradeCallBuilder<'a> {
-    fn call(&self) -> Result<Record<Trade>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!(
             "{}{}{}",
             &self.server_url,
@@ -55,12 +54,11 @@ impl<'a> CallBuilder<Trade> for TradeCallBuilder<'a> {
             "/trades",
         );
 
-        api_call::<Record<Trade>>(
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 
     fn cursor(&mut self, cursor: &str) -> &mut Self {
@@ -95,20 +93,22 @@ impl<'a> CallBuilder<Trade> for TradeCallBuilder<'a> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn limit_trade_call_builder() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn limit_trade_call_builder() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
         let mut tcb = TradeCallBuilder::new(&s);
 
-        let records = tcb.limit(200).call().unwrap();
+        let records = tcb.limit(200).call().await.unwrap();
 
         assert_eq!(records._embedded.records.len(), 200);
     }
 
-    #[test]
-    fn test_trade_call_builder_asset_pair() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_trade_call_builder_asset_pair() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
@@ -124,16 +124,23 @@ mod tests {
             .for_asset_pair(&native, &y_usdc)
             .limit(2)
             .call()
+            .await
             .unwrap();
 
         assert_eq!(records._embedded.records.len(), 2);
     }
 
-    #[test]
-    fn test_for_type() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_for_type() {
         let s = Server::new(String::from("https://horizon.stellar.org"), None)
             .expect("Cannot connect to insecure horizon server");
 
-        let _records = s.trades().for_type(TradeType::Orderbook).call().unwrap();
+        let _records = s
+            .trades()
+            .for_type(TradeType::Orderbook)
+            .call()
+            .await
+            .unwrap();
     }
 }
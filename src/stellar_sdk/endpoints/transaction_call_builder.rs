@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
-use crate::stellar_sdk::api_call::api_call;
-use crate::stellar_sdk::endpoints::{horizon::Record, CallBuilder, Server};
+use crate::stellar_sdk::endpoints::{CallBuilder, HorizonRequest, Server, ServerOptions};
 use crate::stellar_sdk::types::Transaction;
 use crate::stellar_sdk::utils::{Direction, Endpoint};
 
@@ -10,7 +9,7 @@ pub struct TransactionCallBuilder<'a> {
     server_url: &'a str,
     endpoint: Endpoint,
     query_params: HashMap<String, String>,
-    token: &'a Option<String>,
+    options: &'a ServerOptions,
 }
 
 impl<'a> TransactionCallBuilder<'a> {
@@ -19,7 +18,7 @@ impl<'a> TransactionCallBuilder<'a> {
             server_url: &s.server_url,
             endpoint: Endpoint::None,
             query_params: HashMap::new(),
-            token: &s.options.auth_token,
+            options: &s.options,
         }
     }
 
@@ -59,7 +58,7 @@ impl<'a> CallBuilder<Transaction> for TransactionCallBuilder<'a> {
         self
     }
 
-    fn call(&self) -> Result<Record<Transaction>, anyhow::Error> {
+    fn request(&self) -> HorizonRequest<'_> {
         let url = format!(
             "{}{}{}",
             &self.server_url,
@@ -67,34 +66,32 @@ impl<'a> CallBuilder<Transaction> for TransactionCallBuilder<'a> {
             "/transactions",
         );
 
-        api_call::<Record<Transaction>>(
+        HorizonRequest {
             url,
-            crate::stellar_sdk::types::HttpMethod::GET,
-            &self.query_params,
-            self.token,
-        )
+            query_params: &self.query_params,
+            options: self.options,
+        }
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "mock"))]
 mod tests {
     use super::*;
+    use crate::stellar_sdk::endpoints::mock_horizon::TestHorizon;
 
-    #[test]
-    fn limit_transaction_call_builder() {
-        let s = Server::new(String::from("https://horizon.stellar.org"), None)
-            .expect("Cannot connect to insecure horizon server");
+    #[tokio::test]
+    async fn limit_transaction_call_builder() {
+        let test_horizon = TestHorizon::with_payments(3).await;
 
-        let mut tcb = TransactionCallBuilder::new(&s);
+        let mut tcb = TransactionCallBuilder::new(&test_horizon.server);
 
         let tx_records = tcb
-            .for_endpoint(Endpoint::Accounts(String::from(
-                "GAUZUPTHOMSZEV65VNSRMUDAAE4VBMSRYYAX3UOWYU3BQUZ6OK65NOWM",
-            )))
-            .limit(200)
+            .for_endpoint(Endpoint::Accounts(test_horizon.account_id.clone()))
+            .limit(2)
             .call()
+            .await
             .unwrap();
 
-        assert_eq!(tx_records._embedded.records.len(), 200);
+        assert_eq!(tx_records._embedded.records.len(), 2);
     }
 }
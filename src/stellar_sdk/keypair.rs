@@ -1,29 +1,50 @@
 use crate::stellar_sdk::str_key::StrKey;
 use anyhow::bail;
 use nacl::sign::{generate_keypair, signature, verify};
-use stellar_base::crypto::{SecretKey, SodiumKeyPair};
-
-#[derive(Debug, Clone, PartialEq)]
+use std::fmt;
+use stellar_base::crypto::SodiumKeyPair;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Ed25519 keypair of a Stellar account
+///
+/// The secret key and the seed are zeroed when the keypair is dropped, and they are printed as `[REDACTED]` by `Debug`.
+#[derive(Clone, PartialEq)]
 pub struct Keypair {
     public_key: Vec<u8>,
-    secret_key: Option<Vec<u8>>,
-    secret_seed: Option<Vec<u8>>,
+    secret_key: Option<Zeroizing<Vec<u8>>>,
+    secret_seed: Option<Zeroizing<Vec<u8>>>,
+}
+
+/// Printed by `Debug` in place of a secret
+pub(crate) struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keypair")
+            .field("public_key", &self.public_key())
+            .field("secret_key", &self.secret_key.as_ref().map(|_| Redacted))
+            .field("secret_seed", &self.secret_seed.as_ref().map(|_| Redacted))
+            .finish()
+    }
 }
 
 #[allow(dead_code)]
 impl Keypair {
-    fn new_from_secret_key(secret_seed: Vec<u8>) -> Result<Self, anyhow::Error> {
+    fn new_from_secret_key(secret_seed: Zeroizing<Vec<u8>>) -> Result<Self, anyhow::Error> {
         if secret_seed.len() != 32 {
             bail!("secret_key length is invalid")
         }
 
-        let mut cloned_secret_key = secret_seed.clone();
-        let keypair = generate_keypair(&secret_seed);
-        let mut pk = keypair.pkey.clone().to_vec();
-
-        let mut secret_key = Vec::new();
-        secret_key.append(&mut cloned_secret_key);
-        secret_key.append(&mut pk);
+        // The secret key of nacl is the seed followed by the public key
+        let mut keypair = generate_keypair(&secret_seed);
+        let secret_key = Zeroizing::new(keypair.skey.to_vec());
+        keypair.skey.zeroize();
 
         Ok(Self {
             secret_seed: Some(secret_seed),
@@ -45,7 +66,7 @@ impl Keypair {
     }
 
     pub fn from_secret_key(secret: &str) -> Result<Self, anyhow::Error> {
-        let raw_secret = StrKey::decode_ed25519_secret_seed(secret)?;
+        let raw_secret = Zeroizing::new(StrKey::decode_ed25519_secret_seed(secret)?);
 
         Keypair::from_raw_ed25519_seed(&raw_secret)
     }
@@ -66,10 +87,10 @@ impl Keypair {
     }
 
     pub fn from_raw_ed25519_seed(seed: &[u8]) -> Result<Self, anyhow::Error> {
-        Self::new_from_secret_key(seed.to_vec())
+        Self::new_from_secret_key(Zeroizing::new(seed.to_vec()))
     }
 
-    pub fn raw_secret_key(&self) -> Option<Vec<u8>> {
+    pub fn raw_secret_key(&self) -> Option<Zeroizing<Vec<u8>>> {
         self.secret_seed.clone()
     }
 
@@ -77,10 +98,11 @@ impl Keypair {
         &self.public_key
     }
 
-    pub fn secret_key(&mut self) -> Result<String, anyhow::Error> {
+    /// The encoded secret seed, it's zeroed when it's dropped
+    pub fn secret_key(&mut self) -> Result<Zeroizing<String>, anyhow::Error> {
         match &mut self.secret_seed {
             None => bail!("no secret_key available"),
-            Some(s) => Ok(StrKey::encode_ed25519_secret_seed(s)),
+            Some(s) => Ok(Zeroizing::new(StrKey::encode_ed25519_secret_seed(s))),
         }
     }
 
@@ -108,34 +130,36 @@ impl Keypair {
     }
 
     pub fn verify(&self, data: &[u8], signature: &[u8]) -> bool {
-        verify(signature, data, &self.public_key).is_ok()
+        matches!(verify(signature, data, &self.public_key), Ok(true))
     }
 
     pub fn random() -> Result<Self, anyhow::Error> {
-        Self::new_from_secret_key(rand::random::<[u8; 32]>().to_vec())
+        let mut seed = rand::random::<[u8; 32]>();
+        let keypair = Self::from_raw_ed25519_seed(&seed);
+        seed.zeroize();
+        keypair
     }
 }
 
 // Easy conversion between stellar_sdk and stellar_base keypairs and vica versa
+// The raw seed is passed over, so it's never encoded into a string
 impl From<Keypair> for SodiumKeyPair {
     fn from(sdk_keypair: Keypair) -> Self {
-        let mut sdk_keypair_inner = sdk_keypair;
-        let secret_key: String = sdk_keypair_inner
-            .secret_key()
+        let secret_seed = sdk_keypair
+            .secret_seed
+            .as_ref()
             .expect("Failed to get the secret key from the stellar_sdk::Keypair");
-        let stellar_base_keypair = SodiumKeyPair::from_secret_seed(&secret_key)
-            .expect("Failed to convert to generate stellar_base::KeyPair from the secret seed");
-        stellar_base_keypair
+        SodiumKeyPair::from_seed_bytes(secret_seed)
+            .expect("Failed to convert to generate stellar_base::KeyPair from the secret seed")
     }
 }
 
 impl From<SodiumKeyPair> for Keypair {
     fn from(base_keypair: SodiumKeyPair) -> Self {
-        let secret_key_struct: &SecretKey = base_keypair.secret_key();
-        let secret_key: String = secret_key_struct.secret_seed().into();
-        let stellar_sdk_keypair = Keypair::from_secret_key(&secret_key)
-            .expect("Failed to convert to generate stellar_sdk::Keypair from the secret seed");
-        stellar_sdk_keypair
+        // The secret key of sodiumoxide is the seed followed by the public key, it's zeroed on drop
+        let secret_key = &base_keypair.secret_key().inner().0;
+        Keypair::from_raw_ed25519_seed(&secret_key[..32])
+            .expect("Failed to convert to generate stellar_sdk::Keypair from the secret seed")
     }
 }
 
@@ -152,7 +176,7 @@ mod tests {
         let seed_from_keypair = keypair.secret_key().unwrap();
 
         assert_eq!(pk, keypair.public_key());
-        assert_eq!(seed, seed_from_keypair);
+        assert_eq!(seed, *seed_from_keypair);
     }
 
     #[test]
@@ -174,7 +198,7 @@ mod tests {
         let keypair = Keypair::from_raw_ed25519_seed(&raw_seed).unwrap();
 
         if let Some(x) = keypair.raw_secret_key() {
-            assert_eq!(raw_seed, x);
+            assert_eq!(raw_seed, *x);
         }
     }
 
@@ -210,7 +234,8 @@ mod tests {
             16, 41, 169, 20, 202, 137, 15,
         ];
 
-        assert!(keypair.verify(&signed_message, &unsigned_message))
+        assert!(keypair.verify(&unsigned_message, &signed_message));
+        assert!(!keypair.verify("Hello Pi".as_bytes(), &signed_message));
     }
 
     #[test]
@@ -231,4 +256,15 @@ mod tests {
 
         assert_eq!(keypair_sdk, keypair_sdk2);
     }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let seed = String::from("SAZ443I6BNR2MD3G27C4EZIEEFMKOPT4SR6IHZDLXPODEHR2GRQVIC7R");
+        let keypair = Keypair::from_secret_key(&seed).unwrap();
+        let debug = format!("{:?}", keypair);
+
+        assert!(debug.contains("GACAMF2WHKKQTYVHVA3CRMVUHN6GUBLTB7PBJQF73N7ATCIYAIFUCT6B"));
+        assert!(debug.contains("[REDACTED]"));
+        assert!(!debug.contains(&seed));
+    }
 }
@@ -2,10 +2,13 @@
 //!
 //! A lightweight Rust library for communicating with a Stellar Horizon server.
 //!
+//! The `Server` is async, made with `reqwest`. With the `ureq` feature `blocking::Server` makes the same calls
+//! blocking the thread, the call builders send their requests with `call_blocking` then.
+//!
 //! ## Usage:
 //!
 //!
-//! ```
+//! ```ignore
 //! use stellar_sdk::{CallBuilder, Server, types::Asset, utils::{Direction, Endpoint}};
 //!
 //!     let s = String::from("https://horizon.stellar.org");
@@ -13,6 +16,7 @@
 //!
 //!     let my_acc = s
 //!         .load_account("GAUZUPTHOMSZEV65VNSRMUDAAE4VBMSRYYAX3UOWYU3BQUZ6OK65NOWM")
+//!         .await
 //!         .unwrap();
 //!
 //!     // Load transactions of an account
@@ -24,6 +28,7 @@
 //!         .include_failed(false)
 //!         .for_endpoint(Endpoint::Accounts(my_account_id))
 //!         .call()
+//!         .await
 //!         .unwrap();
 //!
 //!     // Load trades of yXLM and XLM
@@ -39,6 +44,7 @@
 //!         .for_asset_pair(&y_xlm, &native)
 //!         .limit(2)
 //!         .call()
+//!         .await
 //!         .unwrap();
 //!
 //!     // Load USDC liquidity pools
@@ -46,14 +52,13 @@
 //!         String::from("USDC"),
 //!         String::from("GA5ZSEJYB37JRC5AVCIA5MOP4RHTM335X2KGX3IHOJAPP5RE34K4KZVN"),
 //!     ).unwrap();
-//!     let usdc_liquidity_pools = s.liquidity_pools().for_assets(vec![usdc]).call().unwrap();
+//!     let usdc_liquidity_pools = s.liquidity_pools().for_assets(vec![usdc]).call().await.unwrap();
 //!
 //! ```
 
 pub use crate::stellar_sdk::endpoints::call_builder::CallBuilder;
 pub use crate::stellar_sdk::endpoints::server::Server;
 pub use crate::stellar_sdk::endpoints::StellarTomlResolver;
-#[cfg(feature = "nacl")]
 pub use crate::stellar_sdk::keypair::Keypair;
 pub use crate::stellar_sdk::str_key::StrKey;
 
@@ -66,13 +71,15 @@ mod tests {
         utils::{Direction, Endpoint},
     };
 
-    #[test]
-    fn test_app() {
+    #[tokio::test]
+    #[ignore = "needs the live Horizon, run with --ignored"]
+    async fn test_app() {
         let s = String::from("https://horizon.stellar.org");
         let s = Server::new(s, None).expect("Cannot connect to insecure horizon server");
 
         let _my_acc = s
             .load_account("GAUZUPTHOMSZEV65VNSRMUDAAE4VBMSRYYAX3UOWYU3BQUZ6OK65NOWM")
+            .await
             .unwrap();
 
         // Load transactions of an account
@@ -85,6 +92,7 @@ mod tests {
             .include_failed(false)
             .for_endpoint(Endpoint::Accounts(my_account_id))
             .call()
+            .await
             .unwrap();
 
         // Load trades of yXLM and XLM
@@ -101,6 +109,7 @@ mod tests {
             .for_asset_pair(&y_xlm, &native)
             .limit(2)
             .call()
+            .await
             .unwrap();
 
         // Load USDC liquidity pools
@@ -109,6 +118,11 @@ mod tests {
             String::from("GA5ZSEJYB37JRC5AVCIA5MOP4RHTM335X2KGX3IHOJAPP5RE34K4KZVN"),
         )
         .unwrap();
-        let _usdc_liquidity_pools = s.liquidity_pools().for_assets(vec![usdc]).call().unwrap();
+        let _usdc_liquidity_pools = s
+            .liquidity_pools()
+            .for_assets(vec![usdc])
+            .call()
+            .await
+            .unwrap();
     }
 }
@@ -1,6 +1,7 @@
 pub mod api_call;
+#[cfg(feature = "ureq")]
+pub mod blocking;
 pub mod endpoints;
-#[cfg(feature = "nacl")]
 pub mod keypair;
 pub mod lib;
 pub mod operations;
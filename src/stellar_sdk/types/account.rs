@@ -39,13 +39,20 @@ pub struct Account {
 }
 
 impl Account {
-    pub fn increment_sequence_number(&mut self) {
-        let mut new_sequence: u64 = self.sequence.parse().unwrap();
-        new_sequence += 1;
+    pub fn parse_sequence_number(&self) -> Result<i64, anyhow::Error> {
+        self.sequence
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid sequence number {:?}: {}", self.sequence, e))
+    }
 
-        let new_sequence = new_sequence.to_string();
+    pub fn increment_sequence_number(&mut self) -> Result<(), anyhow::Error> {
+        let new_sequence = self
+            .parse_sequence_number()?
+            .checked_add(1)
+            .ok_or_else(|| anyhow::anyhow!("Sequence number overflow"))?;
 
-        self.sequence = new_sequence;
+        self.sequence = new_sequence.to_string();
+        Ok(())
     }
 
     pub fn account_id(&self) -> &str {
@@ -173,7 +180,7 @@ mod tests {
                 paging_token: String::from("GAUZUPTHOMSZEV65VNSRMUDAAE4VBMSRYYAX3UOWYU3BQUZ6OK65NOWM"),         
         };
 
-        my_account.increment_sequence_number();
+        my_account.increment_sequence_number().unwrap();
 
         let seq = String::from("129664371176506169");
         let mut seq: u64 = seq.parse().unwrap();
@@ -181,5 +188,9 @@ mod tests {
         let seq = seq.to_string();
 
         assert_eq!(seq, my_account.sequence);
+
+        my_account.sequence = String::from("not a number");
+        assert!(my_account.increment_sequence_number().is_err());
+        assert_eq!(my_account.sequence, "not a number");
     }
 }
@@ -3,7 +3,7 @@ use std::{error::Error, fmt::Formatter};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct HorizonError {
     pub r#type: String,
     pub title: String,
@@ -12,23 +12,52 @@ pub struct HorizonError {
     pub extras: Option<ExtraHorizonError>,
 }
 
+impl HorizonError {
+    pub fn result_codes(&self) -> Option<&ErrorResultCode> {
+        self.extras.as_ref()?.result_codes.as_ref()
+    }
+
+    pub fn transaction_result_code(&self) -> Option<&str> {
+        self.result_codes().map(|codes| codes.transaction.as_str())
+    }
+
+    pub fn operation_result_codes(&self) -> &[String] {
+        self.result_codes()
+            .map(|codes| codes.operations.as_slice())
+            .unwrap_or_default()
+    }
+}
+
 impl Display for HorizonError {
-    fn fmt(&self, _f: &mut Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Result::Ok(())
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.title, self.status)?;
+        if let Some(detail) = &self.detail {
+            write!(f, ": {}", detail)?;
+        }
+        if let Some(codes) = self.result_codes() {
+            write!(f, " [{}", codes.transaction)?;
+            if !codes.operations.is_empty() {
+                write!(f, ": {}", codes.operations.join(", "))?;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
     }
 }
 
 impl Error for HorizonError {}
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ExtraHorizonError {
     pub envelope_xdr: Option<String>,
     pub result_codes: Option<ErrorResultCode>,
     pub result_xdr: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ErrorResultCode {
     pub transaction: String,
+    // Horizon leaves it out when the transaction failed before the operations were applied, like tx_bad_seq
+    #[serde(default)]
     pub operations: Vec<String>,
 }
@@ -23,8 +23,8 @@ pub use asset::Asset;
 pub use asset_horizon::AssetHorizon;
 pub use claimable_balance::ClaimableBalance;
 pub use effect::Effect;
-pub use fee_stats::FeeStats;
-pub use horizon_error::HorizonError;
+pub use fee_stats::{FeeDistribution, FeeStats};
+pub use horizon_error::{ErrorResultCode, ExtraHorizonError, HorizonError};
 pub use http_method::HttpMethod;
 pub use ledger::Ledger;
 pub use liquidity_pool::LiquidityPool;
@@ -1,27 +1,20 @@
 use chrono::prelude::*;
-use ureq;
 
-pub fn req(url: &str) -> Result<String, ureq::Error> {
-    let body: String = ureq::get(url).call()?.into_string()?;
+pub async fn req(url: &str) -> Result<String, reqwest::Error> {
+    let body: String = reqwest::get(url).await?.text().await?;
 
     Ok(body)
 }
 
-pub fn get_current_server_time(hostname: &str) -> Option<i64> {
-    let response = ureq::get(hostname).call();
+/// The time of the server from the Date header, requested with the client of the `Server` so its timeouts and proxy apply
+pub async fn get_current_server_time(client: &reqwest::Client, hostname: &str) -> Option<i64> {
+    let response = client.get(hostname).send().await.ok()?;
 
-    let mut server_timestamp: i64 = 0;
-    if response.is_ok() {
-        let response_unwrapped = response.unwrap();
-        let date_server_str = response_unwrapped.header("Date").unwrap();
-        if let Ok(chrono_date) = DateTime::parse_from_rfc2822(date_server_str) {
-            server_timestamp = chrono_date.timestamp();
-        }
-    }
-
-    if server_timestamp == 0 {
-        return None;
-    }
-
-    Some(server_timestamp)
+    response
+        .headers()
+        .get("Date")
+        .and_then(|date| date.to_str().ok())
+        .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+        .map(|chrono_date| chrono_date.timestamp())
+        .filter(|server_timestamp| *server_timestamp != 0)
 }
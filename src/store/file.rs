@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::{already_stored, poisoned, PaymentQuery, PaymentRecord, PaymentStore};
+use crate::types::PiError;
+
+/// `PaymentStore` in an append-only JSON-lines file
+///
+/// Every insert and update appends the whole record as a new line and flushes it to the disk,
+/// nothing is ever rewritten. On open the lines are replayed and the last line of every payment wins,
+/// so the file is also a history of the payments. A partially written last line (e.g. after a crash) is dropped on open.
+#[derive(Debug)]
+pub struct FilePaymentStore {
+    path: PathBuf,
+    inner: Mutex<FileStoreInner>,
+}
+
+#[derive(Debug)]
+struct FileStoreInner {
+    file: File,
+    records: HashMap<String, PaymentRecord>,
+}
+
+impl FilePaymentStore {
+    /// Opening the store, the file is created if it doesn't exist
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, PiError> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| io_error(&path, e))?;
+
+        let mut content = String::new();
+        BufReader::new(&file)
+            .read_to_string(&mut content)
+            .map_err(|e| io_error(&path, e))?;
+
+        let mut records = HashMap::new();
+        let mut valid_len = 0;
+        for (index, line) in content.split_inclusive('\n').enumerate() {
+            // Only the last line can be cut in half, it's dropped so the next write starts on a clean line
+            if !line.ends_with('\n') {
+                file.set_len(valid_len as u64)
+                    .map_err(|e| io_error(&path, e))?;
+                break;
+            }
+            if !line.trim().is_empty() {
+                let record: PaymentRecord = serde_json::from_str(line).map_err(|e| {
+                    PiError::Message(format!(
+                        "Invalid line {} in the payment store {}: {}",
+                        index + 1,
+                        path.display(),
+                        e
+                    ))
+                })?;
+                records.insert(record.payment_id.clone(), record);
+            }
+            valid_len += line.len();
+        }
+
+        Ok(FilePaymentStore {
+            path,
+            inner: Mutex::new(FileStoreInner { file, records }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn append(&self, inner: &mut FileStoreInner, record: &PaymentRecord) -> Result<(), PiError> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        inner
+            .file
+            .write_all(line.as_bytes())
+            .and_then(|_| inner.file.sync_data())
+            .map_err(|e| io_error(&self.path, e))?;
+        inner
+            .records
+            .insert(record.payment_id.clone(), record.clone());
+        Ok(())
+    }
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> PiError {
+    PiError::Message(format!("Payment store {} error: {}", path.display(), error))
+}
+
+impl PaymentStore for FilePaymentStore {
+    fn insert(&self, record: &PaymentRecord) -> Result<(), PiError> {
+        let mut inner = self.inner.lock().map_err(poisoned)?;
+        if inner.records.contains_key(&record.payment_id) {
+            return Err(already_stored(&record.payment_id));
+        }
+        self.append(&mut inner, record)
+    }
+
+    fn update(&self, record: &PaymentRecord) -> Result<(), PiError> {
+        let mut inner = self.inner.lock().map_err(poisoned)?;
+        self.append(&mut inner, record)
+    }
+
+    fn get(&self, payment_id: &str) -> Result<Option<PaymentRecord>, PiError> {
+        Ok(self
+            .inner
+            .lock()
+            .map_err(poisoned)?
+            .records
+            .get(payment_id)
+            .cloned())
+    }
+
+    fn find(&self, query: &PaymentQuery) -> Result<Vec<PaymentRecord>, PiError> {
+        Ok(self
+            .inner
+            .lock()
+            .map_err(poisoned)?
+            .records
+            .values()
+            .filter(|record| record.matches(query))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::PaymentFlowState;
+    use crate::store::tests::{check_store, record};
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("pi_rust_{}_{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_file_store() {
+        let path = temp_path("file_store");
+        check_store(&FilePaymentStore::open(&path).unwrap());
+
+        // Everything is replayed from the file
+        let store = FilePaymentStore::open(&path).unwrap();
+        let p1 = store.get("p1").unwrap().unwrap();
+        assert_eq!(p1.state, PaymentFlowState::Signed);
+        assert_eq!(p1.txid.as_deref(), Some("txid"));
+        assert_eq!(store.unfinished().unwrap().len(), 2);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_file_store_drops_torn_last_line() {
+        let path = temp_path("torn_line");
+        let store = FilePaymentStore::open(&path).unwrap();
+        store.insert(&record("p1", "alice", "apple-pie")).unwrap();
+        drop(store);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"payment_id":"p2","ui"#).unwrap();
+        drop(file);
+
+        let store = FilePaymentStore::open(&path).unwrap();
+        assert!(store.get("p1").unwrap().is_some());
+        assert!(store.get("p2").unwrap().is_none());
+        store.insert(&record("p2", "alice", "banana")).unwrap();
+        drop(store);
+
+        let store = FilePaymentStore::open(&path).unwrap();
+        assert_eq!(store.find_by_uid("alice").unwrap().len(), 2);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
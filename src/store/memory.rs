@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::{already_stored, poisoned, PaymentQuery, PaymentRecord, PaymentStore};
+use crate::types::PiError;
+
+/// `PaymentStore` keeping the payments in memory, it's lost on restart so mostly useful for testing
+#[derive(Debug, Default)]
+pub struct MemoryPaymentStore {
+    records: Mutex<HashMap<String, PaymentRecord>>,
+}
+
+impl PaymentStore for MemoryPaymentStore {
+    fn insert(&self, record: &PaymentRecord) -> Result<(), PiError> {
+        let mut records = self.records.lock().map_err(poisoned)?;
+        if records.contains_key(&record.payment_id) {
+            return Err(already_stored(&record.payment_id));
+        }
+        records.insert(record.payment_id.clone(), record.clone());
+        Ok(())
+    }
+
+    fn update(&self, record: &PaymentRecord) -> Result<(), PiError> {
+        self.records
+            .lock()
+            .map_err(poisoned)?
+            .insert(record.payment_id.clone(), record.clone());
+        Ok(())
+    }
+
+    fn get(&self, payment_id: &str) -> Result<Option<PaymentRecord>, PiError> {
+        Ok(self
+            .records
+            .lock()
+            .map_err(poisoned)?
+            .get(payment_id)
+            .cloned())
+    }
+
+    fn find(&self, query: &PaymentQuery) -> Result<Vec<PaymentRecord>, PiError> {
+        Ok(self
+            .records
+            .lock()
+            .map_err(poisoned)?
+            .values()
+            .filter(|record| record.matches(query))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_store() {
+        crate::store::tests::check_store(&MemoryPaymentStore::default());
+    }
+}
@@ -0,0 +1,281 @@
+//! Persistence of the payments
+//!
+//! The Pi documentation asks the developers to keep track of the payments (uid, product, amount, memo, payment_id, txid)
+//! on their own side. A `PaymentStore` does it: the `PaymentFlow` inserts the payment after it's created and
+//! writes every later step to it, and the store can be queried by user, by metadata or by state.
+//!
+//! - `MemoryPaymentStore`: keeps the payments in memory, lost on restart
+//! - `FilePaymentStore`: append-only JSON-lines file, every change is a new line
+//! - `SqlitePaymentStore`: SQLite database, behind the `sqlite` cargo feature
+//!
+//! ```ignore
+//! use pi_rust::flow::PaymentFlow;
+//! use pi_rust::store::{FilePaymentStore, PaymentStore};
+//!
+//! let flow = PaymentFlow::new(pi, FilePaymentStore::open("payments.jsonl")?);
+//! flow.start(payment_data).await?;
+//!
+//! let payments = flow.store().find_by_uid("user_uid")?;
+//! ```
+
+mod file;
+mod memory;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use file::FilePaymentStore;
+pub use memory::MemoryPaymentStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqlitePaymentStore;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::flow::PaymentFlowState;
+use crate::types::{PaymentArgs, PaymentDTO, PiAmount, PiError};
+
+/// The stored state of one payment
+///
+/// - payment_id: String - identifier of the payment on the Pi API
+/// - uid: String - the user's app-specific ID
+/// - amount: PiAmount - the amount of the payment
+/// - memo: String - the memo of the payment
+/// - metadata: Value - the metadata of the payment, like the product ID
+/// - state: PaymentFlowState - the last known state of the payment
+/// - txid: `Option<String>` - hash of the blockchain transaction, once it's known
+/// - envelope_xdr: `Option<String>` - the signed transaction envelope in base64 XDR, set from the Signed state
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaymentRecord {
+    pub payment_id: String,
+    pub uid: String,
+    pub amount: PiAmount,
+    pub memo: String,
+    pub metadata: Value,
+    pub state: PaymentFlowState,
+    pub txid: Option<String>,
+    pub envelope_xdr: Option<String>,
+}
+
+impl PaymentRecord {
+    /// Record of a freshly created payment, its state is taken from the payment status
+    pub fn from_payment(payment: &PaymentDTO) -> Self {
+        let state = if payment.status.developer_completed {
+            PaymentFlowState::Completed
+        } else if payment.status.cancelled || payment.status.user_cancelled {
+            PaymentFlowState::Cancelled
+        } else {
+            PaymentFlowState::Created
+        };
+
+        PaymentRecord {
+            payment_id: payment.identifier.clone(),
+            uid: payment.user_uid.clone(),
+            amount: payment.amount,
+            memo: payment.memo.clone(),
+            metadata: payment.metadata.clone(),
+            state,
+            txid: payment
+                .transaction
+                .as_ref()
+                .map(|transaction| transaction.txid.clone()),
+            envelope_xdr: None,
+        }
+    }
+
+    /// Record of a payment just created with the arguments, before anything else is known about it
+    pub fn from_args(payment_id: String, args: &PaymentArgs) -> Self {
+        PaymentRecord {
+            payment_id,
+            uid: args.uid.clone(),
+            amount: args.amount,
+            memo: args.memo.clone(),
+            metadata: args.metadata.clone(),
+            state: PaymentFlowState::Created,
+            txid: None,
+            envelope_xdr: None,
+        }
+    }
+
+    /// True if the record matches the query
+    pub fn matches(&self, query: &PaymentQuery) -> bool {
+        match query {
+            PaymentQuery::Uid(uid) => self.uid == *uid,
+            PaymentQuery::Metadata { key, value } => self.metadata.get(key) == Some(value),
+            PaymentQuery::State(state) => self.state == *state,
+        }
+    }
+}
+
+/// Filters of `PaymentStore::find`
+///
+/// - Uid: the payments of one user
+/// - Metadata: the payments whose metadata object has the key with the value, e.g. `productId` = `"apple-pie-1"`
+/// - State: the payments in one state
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaymentQuery {
+    Uid(String),
+    Metadata { key: String, value: Value },
+    State(PaymentFlowState),
+}
+
+/// Persistence of the payments, see the module documentation
+///
+/// The implementations only have to insert, replace, get and find records, the rest is built on those.
+pub trait PaymentStore: Send + Sync {
+    /// Saving a new payment, errors if the payment is already stored
+    fn insert(&self, record: &PaymentRecord) -> Result<(), PiError>;
+    /// Saving the record, replacing the stored one or inserting it if it's not stored yet
+    fn update(&self, record: &PaymentRecord) -> Result<(), PiError>;
+    fn get(&self, payment_id: &str) -> Result<Option<PaymentRecord>, PiError>;
+    fn find(&self, query: &PaymentQuery) -> Result<Vec<PaymentRecord>, PiError>;
+
+    fn update_state(&self, payment_id: &str, state: PaymentFlowState) -> Result<(), PiError> {
+        let mut record = get_existing(self, payment_id)?;
+        record.state = state;
+        self.update(&record)
+    }
+
+    fn attach_txid(&self, payment_id: &str, txid: &str) -> Result<(), PiError> {
+        let mut record = get_existing(self, payment_id)?;
+        record.txid = Some(txid.to_string());
+        self.update(&record)
+    }
+
+    fn find_by_uid(&self, uid: &str) -> Result<Vec<PaymentRecord>, PiError> {
+        self.find(&PaymentQuery::Uid(uid.to_string()))
+    }
+
+    fn find_by_metadata(&self, key: &str, value: Value) -> Result<Vec<PaymentRecord>, PiError> {
+        self.find(&PaymentQuery::Metadata {
+            key: key.to_string(),
+            value,
+        })
+    }
+
+    fn find_by_state(&self, state: PaymentFlowState) -> Result<Vec<PaymentRecord>, PiError> {
+        self.find(&PaymentQuery::State(state))
+    }
+
+    /// Every payment which is not in a final state
+    fn unfinished(&self) -> Result<Vec<PaymentRecord>, PiError> {
+        let mut records = Vec::new();
+        for state in [
+            PaymentFlowState::Created,
+            PaymentFlowState::Signed,
+            PaymentFlowState::Submitted,
+            PaymentFlowState::Confirmed,
+        ] {
+            records.extend(self.find_by_state(state)?);
+        }
+        Ok(records)
+    }
+}
+
+impl<S: PaymentStore + ?Sized> PaymentStore for Box<S> {
+    fn insert(&self, record: &PaymentRecord) -> Result<(), PiError> {
+        (**self).insert(record)
+    }
+
+    fn update(&self, record: &PaymentRecord) -> Result<(), PiError> {
+        (**self).update(record)
+    }
+
+    fn get(&self, payment_id: &str) -> Result<Option<PaymentRecord>, PiError> {
+        (**self).get(payment_id)
+    }
+
+    fn find(&self, query: &PaymentQuery) -> Result<Vec<PaymentRecord>, PiError> {
+        (**self).find(query)
+    }
+}
+
+fn get_existing<S: PaymentStore + ?Sized>(
+    store: &S,
+    payment_id: &str,
+) -> Result<PaymentRecord, PiError> {
+    store
+        .get(payment_id)?
+        .ok_or_else(|| PiError::Message(format!("Payment {} is not stored", payment_id)))
+}
+
+fn poisoned<T>(_: T) -> PiError {
+    PiError::Message("Payment store lock is poisoned".to_string())
+}
+
+fn already_stored(payment_id: &str) -> PiError {
+    PiError::Message(format!("Payment {} is already stored", payment_id))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::json;
+
+    pub(crate) fn record(payment_id: &str, uid: &str, product_id: &str) -> PaymentRecord {
+        PaymentRecord {
+            payment_id: payment_id.to_string(),
+            uid: uid.to_string(),
+            amount: "3.14".parse().unwrap(),
+            memo: "Apple pie".to_string(),
+            metadata: json!({ "productId": product_id }),
+            state: PaymentFlowState::Created,
+            txid: None,
+            envelope_xdr: None,
+        }
+    }
+
+    /// Common checks run against every store implementation
+    pub(crate) fn check_store(store: &dyn PaymentStore) {
+        store.insert(&record("p1", "alice", "apple-pie")).unwrap();
+        store.insert(&record("p2", "alice", "banana")).unwrap();
+        store.insert(&record("p3", "bob", "apple-pie")).unwrap();
+        assert!(store.insert(&record("p1", "alice", "apple-pie")).is_err());
+
+        store.update_state("p1", PaymentFlowState::Signed).unwrap();
+        store.attach_txid("p1", "txid").unwrap();
+        store
+            .update_state("p3", PaymentFlowState::Completed)
+            .unwrap();
+        assert!(store.attach_txid("unknown", "txid").is_err());
+
+        let p1 = store.get("p1").unwrap().unwrap();
+        assert_eq!(p1.state, PaymentFlowState::Signed);
+        assert_eq!(p1.txid.as_deref(), Some("txid"));
+        assert_eq!(store.get("unknown").unwrap(), None);
+
+        let ids = |records: Vec<PaymentRecord>| {
+            let mut ids: Vec<String> = records.into_iter().map(|r| r.payment_id).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(store.find_by_uid("alice").unwrap()), vec!["p1", "p2"]);
+        assert_eq!(
+            ids(store
+                .find_by_metadata("productId", json!("apple-pie"))
+                .unwrap()),
+            vec!["p1", "p3"]
+        );
+        assert_eq!(
+            ids(store.find_by_state(PaymentFlowState::Completed).unwrap()),
+            vec!["p3"]
+        );
+        assert_eq!(ids(store.unfinished().unwrap()), vec!["p1", "p2"]);
+    }
+
+    #[test]
+    fn test_record_matches() {
+        let record = record("p1", "alice", "apple-pie");
+        assert!(record.matches(&PaymentQuery::Uid("alice".to_string())));
+        assert!(!record.matches(&PaymentQuery::Metadata {
+            key: "productId".to_string(),
+            value: json!("banana"),
+        }));
+        assert!(!record.matches(&PaymentQuery::Metadata {
+            key: "missing".to_string(),
+            value: Value::Null,
+        }));
+        assert!(record.matches(&PaymentQuery::State(PaymentFlowState::Created)));
+    }
+}
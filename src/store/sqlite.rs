@@ -0,0 +1,208 @@
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::{already_stored, poisoned, PaymentQuery, PaymentRecord, PaymentStore};
+use crate::flow::PaymentFlowState;
+use crate::types::{PiAmount, PiError};
+
+/// `PaymentStore` in a SQLite database, available with the `sqlite` cargo feature
+///
+/// The payments are kept in the `pi_payments` table, which is created if it doesn't exist.
+/// The amount is stored in stroops and the metadata as JSON text, so the table can be queried directly as well.
+#[derive(Debug)]
+pub struct SqlitePaymentStore {
+    connection: Mutex<Connection>,
+}
+
+const COLUMNS: &str = "payment_id, uid, amount, memo, metadata, state, txid, envelope_xdr";
+
+impl SqlitePaymentStore {
+    /// Opening the database file, it's created if it doesn't exist
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, PiError> {
+        Self::with_connection(Connection::open(path).map_err(sqlite_error)?)
+    }
+
+    /// Database which lives only in memory, mostly useful for testing
+    pub fn open_in_memory() -> Result<Self, PiError> {
+        Self::with_connection(Connection::open_in_memory().map_err(sqlite_error)?)
+    }
+
+    /// Using an already opened connection, the table is created on it if it doesn't exist
+    pub fn with_connection(connection: Connection) -> Result<Self, PiError> {
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS pi_payments (
+                    payment_id TEXT PRIMARY KEY NOT NULL,
+                    uid TEXT NOT NULL,
+                    amount INTEGER NOT NULL,
+                    memo TEXT NOT NULL,
+                    metadata TEXT NOT NULL,
+                    state TEXT NOT NULL,
+                    txid TEXT,
+                    envelope_xdr TEXT
+                );
+                CREATE INDEX IF NOT EXISTS pi_payments_uid ON pi_payments (uid);
+                CREATE INDEX IF NOT EXISTS pi_payments_state ON pi_payments (state);",
+            )
+            .map_err(sqlite_error)?;
+
+        Ok(SqlitePaymentStore {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn select(
+        &self,
+        filter: &str,
+        param: &dyn rusqlite::ToSql,
+    ) -> Result<Vec<PaymentRecord>, PiError> {
+        let connection = self.connection.lock().map_err(poisoned)?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM pi_payments WHERE {}",
+                COLUMNS, filter
+            ))
+            .map_err(sqlite_error)?;
+        let rows = statement
+            .query_map([param], from_row)
+            .map_err(sqlite_error)?;
+        rows.collect::<Result<_, _>>().map_err(sqlite_error)
+    }
+}
+
+fn execute(
+    connection: &Connection,
+    sql: &str,
+    record: &PaymentRecord,
+) -> Result<usize, rusqlite::Error> {
+    connection.execute(
+        sql,
+        params![
+            record.payment_id,
+            record.uid,
+            record.amount.stroops(),
+            record.memo,
+            record.metadata.to_string(),
+            state_to_sql(record.state),
+            record.txid,
+            record.envelope_xdr,
+        ],
+    )
+}
+
+fn sqlite_error(error: rusqlite::Error) -> PiError {
+    PiError::Message(format!("Payment store SQLite error: {}", error))
+}
+
+fn state_to_sql(state: PaymentFlowState) -> String {
+    format!("{:?}", state)
+}
+
+fn state_from_sql(state: &str) -> Option<PaymentFlowState> {
+    serde_json::from_value(serde_json::Value::String(state.to_string())).ok()
+}
+
+fn from_row(row: &Row) -> Result<PaymentRecord, rusqlite::Error> {
+    let invalid = |index: usize, message: String| {
+        rusqlite::Error::FromSqlConversionFailure(
+            index,
+            rusqlite::types::Type::Text,
+            message.into(),
+        )
+    };
+
+    let metadata: String = row.get(4)?;
+    let state: String = row.get(5)?;
+    Ok(PaymentRecord {
+        payment_id: row.get(0)?,
+        uid: row.get(1)?,
+        amount: PiAmount::from_stroops(row.get(2)?),
+        memo: row.get(3)?,
+        metadata: serde_json::from_str(&metadata).map_err(|e| invalid(4, e.to_string()))?,
+        state: state_from_sql(&state)
+            .ok_or_else(|| invalid(5, format!("Unknown payment state {:?}", state)))?,
+        txid: row.get(6)?,
+        envelope_xdr: row.get(7)?,
+    })
+}
+
+impl PaymentStore for SqlitePaymentStore {
+    fn insert(&self, record: &PaymentRecord) -> Result<(), PiError> {
+        let sql = format!(
+            "INSERT INTO pi_payments ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            COLUMNS
+        );
+        let connection = self.connection.lock().map_err(poisoned)?;
+        match execute(&connection, &sql, record) {
+            Ok(_) => Ok(()),
+            Err(rusqlite::Error::SqliteFailure(error, _))
+                if error.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                Err(already_stored(&record.payment_id))
+            }
+            Err(e) => Err(sqlite_error(e)),
+        }
+    }
+
+    fn update(&self, record: &PaymentRecord) -> Result<(), PiError> {
+        let sql = format!(
+            "INSERT OR REPLACE INTO pi_payments ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            COLUMNS
+        );
+        let connection = self.connection.lock().map_err(poisoned)?;
+        execute(&connection, &sql, record)
+            .map(|_| ())
+            .map_err(sqlite_error)
+    }
+
+    fn get(&self, payment_id: &str) -> Result<Option<PaymentRecord>, PiError> {
+        let connection = self.connection.lock().map_err(poisoned)?;
+        connection
+            .query_row(
+                &format!("SELECT {} FROM pi_payments WHERE payment_id = ?1", COLUMNS),
+                [payment_id],
+                from_row,
+            )
+            .optional()
+            .map_err(sqlite_error)
+    }
+
+    fn find(&self, query: &PaymentQuery) -> Result<Vec<PaymentRecord>, PiError> {
+        match query {
+            PaymentQuery::Uid(uid) => self.select("uid = ?1", uid),
+            PaymentQuery::State(state) => self.select("state = ?1", &state_to_sql(*state)),
+            // Narrowing down to the payments having the key, the JSON values are compared after
+            PaymentQuery::Metadata { key, .. } => Ok(self
+                .select(
+                    "EXISTS (SELECT 1 FROM json_each(pi_payments.metadata) WHERE json_each.key = ?1)",
+                    key,
+                )?
+                .into_iter()
+                .filter(|record| record.matches(query))
+                .collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::check_store;
+
+    #[test]
+    fn test_sqlite_store() {
+        check_store(&SqlitePaymentStore::open_in_memory().unwrap());
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        for state in [
+            PaymentFlowState::Created,
+            PaymentFlowState::Submitted,
+            PaymentFlowState::Cancelled,
+        ] {
+            assert_eq!(state_from_sql(&state_to_sql(state)), Some(state));
+        }
+    }
+}
@@ -1,19 +1,35 @@
-use serde::{Deserialize, Serialize, Serializer};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
 use std::num::ParseIntError;
+use std::time::Duration;
+
+pub use crate::amount::{PiAmount, PiAmountError};
+pub use crate::fee::{FeePolicy, FeeStrategy};
+pub use crate::network::NetworkProfile;
+pub use crate::payment_args::{PaymentArgsBuilder, PaymentArgsError, PaymentLimits};
+pub use crate::payment_state::{InvalidPaymentState, PaymentState};
+pub use crate::retry::RetryPolicy;
+use crate::stellar_sdk::types::HorizonError;
+use data_encoding::HEXLOWER;
+use stellar_base::{
+    transaction::{Transaction, TransactionEnvelope},
+    xdr::{XDRDeserialize, XDRSerialize},
+    Network,
+};
 
 /// Payment arguments:
 ///
-/// - amount: f64 - the amount of Pi you're paying to your user
+/// - amount: PiAmount - the amount of Pi you're paying to your user, exact up to 7 decimals
 /// - memo: String - a short memo that describes what the payment is about, 28 english characters or 28-bytes
-/// - metadata: Value - an arbitrary object that you can attach to this payment. This is for your own use. You should use this object as a way to link this payment with your internal business logic. serde_json::Value
+/// - metadata: M - an arbitrary object that you can attach to this payment. This is for your own use. You should use this object as a way to link this payment with your internal business logic. serde_json::Value by default, or your own struct serialized to an object
 /// - uid: String - a user uid of your app. You should have access to this value if a user has authenticated on your app.
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PaymentArgs {
-    pub amount: f64,
+pub struct PaymentArgs<M = Value> {
+    pub amount: PiAmount,
     pub memo: String,
-    pub metadata: Value,
+    pub metadata: M,
     pub uid: String,
 }
 
@@ -21,30 +37,97 @@ pub struct PaymentArgs {
 ///
 /// Not necessary used by the crate's user it's an internal struct made by `submit_payment` function and consumed by `build_a2u_transaction`.
 /// It carrying information necessary for the transaction building.
-///     - amount: f64 - the amount of Pi you're paying to your user
+///     - amount: PiAmount - the amount of Pi you're paying to your user
 ///     - payment_identifier: String - This is generated by Pi API and this carried to the transaction building this will be the Memo's text
 ///     - from_address: String - From which address the transaction coming from
 ///     - to_address: String - To address where the transaction going to
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionData {
-    pub amount: f64,
+    pub amount: PiAmount,
     pub payment_identifier: String,
     pub from_address: String,
     pub to_address: String,
 }
 
+/// Signed transaction, ready to be submitted
+///
+/// The hash is computed locally, so the transaction can be looked up on Horizon even if the response of the submission is lost.
+///
+/// - txid: String - hash of the transaction, the same as its txid on the blockchain
+/// - envelope_xdr: String - the signed envelope in base64 XDR, submitting it again can't apply it twice
+/// - max_time: `Option<i64>` - UNIX time after which the transaction can't get into a ledger anymore, `None` if it has no time bounds
+/// - source_account: String - account ID of the source account, the app wallet or a channel account, whose sequence number it uses
+/// - base_fee: i64 - the fee per operation in stroops
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    pub txid: String,
+    pub envelope_xdr: String,
+    pub max_time: Option<i64>,
+    pub source_account: String,
+    pub base_fee: i64,
+}
+
+impl SignedTransaction {
+    /// Encoding the signed transaction, the hash is computed on the network with the given passphrase
+    pub fn new(transaction: &Transaction, network_passphrase: &str) -> Result<Self, PiError> {
+        let hash = transaction
+            .hash(&Network::new(network_passphrase.to_string()))
+            .map_err(|e| PiError::Message(format!("Can't hash the transaction! {:?}", e)))?;
+        let envelope_xdr = transaction
+            .to_envelope()
+            .xdr_base64()
+            .map_err(|e| PiError::Message(format!("Can't encode the transaction! {:?}", e)))?;
+        let max_time = transaction
+            .time_bounds()
+            .as_ref()
+            .and_then(|time_bounds| *time_bounds.upper())
+            .map(|max_time| max_time.timestamp())
+            // An upper bound of 0 means no upper bound
+            .filter(|max_time| *max_time != 0);
+        let operations = transaction.operations().len().max(1) as i64;
+
+        Ok(SignedTransaction {
+            txid: HEXLOWER.encode(&hash),
+            envelope_xdr,
+            max_time,
+            source_account: crate::source_account_id(transaction),
+            base_fee: transaction.fee().to_i64() / operations,
+        })
+    }
+
+    /// Decoding a signed envelope, e.g. the one stored by the `PaymentFlow`
+    pub fn from_envelope(envelope_xdr: &str, network_passphrase: &str) -> Result<Self, PiError> {
+        let envelope = TransactionEnvelope::from_xdr_base64(envelope_xdr)
+            .map_err(|e| PiError::Message(format!("Can't decode the transaction! {:?}", e)))?;
+        let transaction = envelope.as_transaction().ok_or_else(|| {
+            PiError::Message("The envelope is not a transaction envelope".to_string())
+        })?;
+        let mut signed = SignedTransaction::new(transaction, network_passphrase)?;
+        signed.envelope_xdr = envelope_xdr.to_string();
+        Ok(signed)
+    }
+
+    /// Time left until the max time, `None` if the transaction has no time bounds
+    pub fn time_left(&self) -> Option<Duration> {
+        let max_time = self.max_time?;
+        let left = max_time - chrono::Utc::now().timestamp();
+        Some(Duration::from_secs(left.max(0) as u64))
+    }
+}
+
 /// Payment data generated by Pi API
 ///
 /// - identifier: String - payment identifier
 /// - user_uid: String - user's app-specific ID
-/// - amount: f64 - payment amount
+/// - amount: PiAmount - payment amount
 /// - memo: String - a String provided by the developer, shown to the user
-/// - metadata: Value - an object provided by the developer for their own usage, serde_json::Value
+/// - metadata: M - an object provided by the developer for their own usage, serde_json::Value by default, see at `get_payment`
 /// - from_address: String - sender address of the blockchain transaction
 /// - to_address: String - recipient address of the blockchain transaction
 /// - direction: Direction - direction of the payment ("user_to_app" | "app_to_user")
-/// - status: PaymentDTOStatus - Status flags representing the current state of this payment
+/// - status: PaymentDTOStatus - Status flags representing the current state of this payment, see at `state`
 ///     - developer_approved: bool - Server-Side Approval (automatically approved for A2U payment)
 ///     - transaction_verified: bool - blockchain transaction verified
 ///     - developer_completed: bool - Server-Side Completion (handled by the create_payment! method)
@@ -54,25 +137,66 @@ pub struct TransactionData {
 ///     - txid: String - id of the blockchain transaction
 ///     - verified: bool - true if the transaction matches the payment, false otherwise
 ///     - _link: String - a link to the operation on the Pi Blockchain API
-/// - created_at: String - payment's creation timestamp
+/// - created_at: `DateTime<Utc>` - payment's creation timestamp, see at `age`
 /// - network: NetworkPassphrase - a network of the payment ("Pi Network" | "Pi Testnet")
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PaymentDTO {
+pub struct PaymentDTO<M = Value> {
     pub identifier: String,
     pub user_uid: String,
-    pub amount: f64,
+    pub amount: PiAmount,
     pub memo: String,
-    pub metadata: Value,
+    pub metadata: M,
     pub from_address: String,
     pub to_address: String,
     pub direction: Direction,
     pub status: PaymentDTOStatus,
     pub transaction: Option<PaymentDTOTransaction>,
-    pub created_at: String,
+    #[serde(deserialize_with = "deserialize_timestamp")]
+    pub created_at: DateTime<Utc>,
     pub network: NetworkPassphrase,
 }
 
+// The Pi API gives the timestamps in RFC 3339, a timestamp without offset is taken as UTC
+fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let timestamp = String::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&timestamp)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(&timestamp, "%Y-%m-%dT%H:%M:%S%.f")
+                .map(|timestamp| timestamp.and_utc())
+        })
+        .map_err(|_| serde::de::Error::custom(format!("Invalid timestamp: {:?}", timestamp)))
+}
+
+impl PaymentDTO {
+    /// The payment with its metadata deserialized to `M`, e.g. your own struct
+    pub fn deserialize_metadata<M: DeserializeOwned>(self) -> Result<PaymentDTO<M>, PiError> {
+        let metadata =
+            serde_json::from_value(self.metadata).map_err(|error| PiError::MetadataMismatch {
+                payment_id: self.identifier.clone(),
+                error,
+            })?;
+        Ok(PaymentDTO {
+            identifier: self.identifier,
+            user_uid: self.user_uid,
+            amount: self.amount,
+            memo: self.memo,
+            metadata,
+            from_address: self.from_address,
+            to_address: self.to_address,
+            direction: self.direction,
+            status: self.status,
+            transaction: self.transaction,
+            created_at: self.created_at,
+            network: self.network,
+        })
+    }
+}
+
 /// See at `PaymentDTO`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaymentDTOStatus {
@@ -91,22 +215,101 @@ pub struct PaymentDTOTransaction {
     pub _link: String,
 }
 
+/// User data returned by the Pi API's `/v2/me` endpoint
+///
+/// - uid: String - user's app-specific ID
+/// - username: `Option<String>` - the user's Pi username, only present if the `username` scope was requested
+/// - credentials: UserCredentials - the credentials of the verified access token
+///     - scopes: `Vec<String>` - scopes granted by the user ("username", "payments", "wallet_address")
+///     - valid_until: UserCredentialsValidUntil - expiration of the access token
+///         - timestamp: i64 - unix timestamp
+///         - iso8601: String - the same time in ISO 8601 format
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserDTO {
+    pub uid: String,
+    pub username: Option<String>,
+    pub credentials: UserCredentials,
+}
+
+/// See at `UserDTO`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserCredentials {
+    pub scopes: Vec<String>,
+    pub valid_until: UserCredentialsValidUntil,
+}
+
+/// See at `UserDTO`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserCredentialsValidUntil {
+    pub timestamp: i64,
+    pub iso8601: String,
+}
+
 /// Reqwest client options
 ///
 /// have base_url: String value, need to cleare `PiNetwork` struct, but since it's option also can be `None`
-/// Usally have to keep at None, then the library will use `https://api.minepi.com` as the API base URL
+/// Usally have to keep at None, then the library will use the Pi API base URL of the network, `https://api.minepi.com`
 /// But if for some reason the API url would change, we can give that also and the crate will keep working if nothing else changed.
+/// The URLs are only used by `PiNetwork::new`, `PiNetwork::with_profile` takes them from the `NetworkProfile`.
+/// The `PiNetwork` makes one client from these options and keeps it for its lifetime, so the connections are pooled.
+///
+/// - base_url: String - the Pi API base URL
+/// - horizon_url: `Option<String>` - Horizon URL used instead of the one of the network, e.g. a local Horizon, plain http is allowed for it
+/// - retry_policy: RetryPolicy - retrying the transient failures of the Pi API and Horizon calls, see at `RetryPolicy`
+/// - fee_policy: FeePolicy - choosing the fee of the transactions and raising it during surge pricing, see at `FeePolicy`
+/// - transaction_validity: `Option<Duration>` - an A2U transaction can only get into a ledger this long after it's built, by the time of Horizon,
+///   3 minutes by default, `None` means forever, but then a lost transaction can't be replaced safely
+/// - timeout: `Option<Duration>` - timeout of the whole request, 20 seconds by default
+/// - connect_timeout: `Option<Duration>` - timeout of making the connection
+/// - proxy: `Option<String>` - URL of the proxy every request goes through, e.g. `http://proxy:8080`
+/// - root_certificates: `Vec<Vec<u8>>` - extra trusted root certificates in PEM format
+/// - user_agent: `Option<String>` - the User-Agent header, `pi_rust/<version>` by default
+/// - pool_max_idle_per_host: `Option<usize>` - the maximum number of idle connections kept per host
+/// - client: `Option<reqwest::Client>` - your own client, every other client setting is ignored if it's given (not serialized)
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ReqwestClientOptions {
     pub base_url: String,
+    pub horizon_url: Option<String>,
+    pub retry_policy: RetryPolicy,
+    pub fee_policy: FeePolicy,
+    pub transaction_validity: Option<Duration>,
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub proxy: Option<String>,
+    pub root_certificates: Vec<Vec<u8>>,
+    pub user_agent: Option<String>,
+    pub pool_max_idle_per_host: Option<usize>,
+    #[serde(skip)]
+    pub client: Option<reqwest::Client>,
+}
+
+impl Default for ReqwestClientOptions {
+    fn default() -> Self {
+        ReqwestClientOptions {
+            base_url: NetworkProfile::testnet().pi_api_url,
+            horizon_url: None,
+            retry_policy: RetryPolicy::default(),
+            fee_policy: FeePolicy::default(),
+            transaction_validity: Some(Duration::from_secs(180)),
+            timeout: Some(Duration::from_secs(20)),
+            connect_timeout: None,
+            proxy: None,
+            root_certificates: Vec::new(),
+            user_agent: Some(concat!("pi_rust/", env!("CARGO_PKG_VERSION")).to_string()),
+            pool_max_idle_per_host: None,
+            client: None,
+        }
+    }
 }
 
 /// Direction of the payment
 ///
 /// Basically can be UserToApp or AppToUser and the json response from server ("user_to_app", "app_to_user") can be serialized in this enum.
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     #[serde(rename = "user_to_app")]
     UserToApp,
@@ -118,13 +321,40 @@ pub enum Direction {
 ///
 /// Option for the `PiNetwork` struct, it's determining we will using testnet or mainnet
 /// If we are creating the `Pi Network` struct with None, we will using the testnet as default
+/// Any other network is `Custom` with the name the Pi API reports for its payments, e.g. a local stand-in
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
 pub enum NetworkPassphrase {
     #[serde(rename = "Pi Network")]
     PiNetwork,
     #[serde(rename = "Pi Testnet")]
     PiTestnet,
+    #[serde(untagged)]
+    Custom(String),
+}
+
+impl NetworkPassphrase {
+    /// The name of the network as the Pi API reports it
+    pub fn as_str(&self) -> &str {
+        match self {
+            NetworkPassphrase::PiNetwork => "Pi Network",
+            NetworkPassphrase::PiTestnet => "Pi Testnet",
+            NetworkPassphrase::Custom(name) => name,
+        }
+    }
+}
+
+// A custom network with the name of a Pi network is that network
+impl PartialEq for NetworkPassphrase {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl std::fmt::Display for NetworkPassphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 /// Pi API's incomplete payments response serialized to this struct
@@ -134,25 +364,136 @@ pub struct IncompletePaymentResponse {
     pub incomplete_server_payments: Vec<PaymentDTO>,
 }
 
+/// Error returned by the Pi API
+///
+/// - status: u16 - HTTP status code of the response
+/// - code: String - the `error` field of the response body, empty if the body wasn't JSON
+/// - message: String - the `error_message` field of the response body, or the raw body if it wasn't JSON
+/// - kind: PiApiErrorKind - the well-known cases, so they can be matched without comparing strings
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PiApiError {
+    pub status: u16,
+    pub code: String,
+    pub message: String,
+    pub kind: PiApiErrorKind,
+}
+
+/// See at `PiApiError`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PiApiErrorKind {
+    /// "You need to complete the ongoing payment first to create a new one."
+    OngoingPaymentExists,
+    PaymentNotFound,
+    AlreadyCompleted,
+    /// The API key is missing or invalid
+    Unauthorized,
+    RateLimited,
+    Other,
+}
+
+#[derive(Deserialize)]
+struct PiApiErrorBody {
+    #[serde(default)]
+    error: String,
+    #[serde(default)]
+    error_message: String,
+}
+
+impl PiApiError {
+    /// Building the error from the HTTP status and the response body of the failed call
+    pub fn new(status: u16, body: &str) -> Self {
+        let (code, message) = match serde_json::from_str::<PiApiErrorBody>(body) {
+            Ok(parsed) => (parsed.error, parsed.error_message),
+            Err(_) => (String::new(), body.to_string()),
+        };
+        let kind = PiApiErrorKind::classify(status, &code, &message);
+        PiApiError {
+            status,
+            code,
+            message,
+            kind,
+        }
+    }
+}
+
+impl PiApiErrorKind {
+    fn classify(status: u16, code: &str, message: &str) -> Self {
+        let code = code.to_lowercase();
+        let message = message.to_lowercase();
+        let mentions = |needle: &str| code.contains(needle) || message.contains(needle);
+
+        if status == 429 {
+            PiApiErrorKind::RateLimited
+        } else if status == 401 {
+            PiApiErrorKind::Unauthorized
+        } else if mentions("ongoing_payment") || mentions("ongoing payment") {
+            PiApiErrorKind::OngoingPaymentExists
+        } else if mentions("already_completed") || mentions("already completed") {
+            PiApiErrorKind::AlreadyCompleted
+        } else if code == "payment_not_found" || message.contains("payment not found") {
+            // Only the payment's own error, a 404 of another endpoint or a wrong base URL isn't a missing payment
+            PiApiErrorKind::PaymentNotFound
+        } else {
+            PiApiErrorKind::Other
+        }
+    }
+}
+
+impl std::fmt::Display for PiApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Error, message from API ({}): {} {}",
+            self.status, self.code, self.message
+        )
+    }
+}
+
 /// Custom error wrapper for some possible error variants
 
 #[derive(Debug)]
 pub enum PiError {
     Message(String),
+    InvalidAccessToken(String),
+    Api(PiApiError),
     Reqwest(reqwest::Error),
     Json(serde_json::Error),
+    Horizon(Box<HorizonError>),
     Anyhow(anyhow::Error),
     ParseError(ParseIntError),
+    Amount(PiAmountError),
+    PaymentArgs(PaymentArgsError),
+    MetadataMismatch {
+        payment_id: String,
+        error: serde_json::Error,
+    },
+    /// The `TransactionSigner` couldn't sign, the transaction wasn't submitted
+    Signer(String),
 }
 
 impl std::fmt::Display for PiError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
             PiError::Message(ref msg) => write!(f, "{}", msg),
+            PiError::InvalidAccessToken(ref msg) => write!(f, "{}", msg),
+            PiError::Api(ref err) => write!(f, "{}", err),
             PiError::Reqwest(ref err) => write!(f, "Reqwest error: {}", err),
             PiError::Json(ref err) => write!(f, "JSON error: {}", err),
+            PiError::Horizon(ref err) => write!(f, "Horizon error: {}", err),
             PiError::Anyhow(ref err) => write!(f, "Horizon error: {}", err),
             PiError::ParseError(ref err) => write!(f, "Can't parse: {}", err),
+            PiError::Amount(ref err) => write!(f, "{}", err),
+            PiError::PaymentArgs(ref err) => write!(f, "{}", err),
+            PiError::MetadataMismatch {
+                ref payment_id,
+                ref error,
+            } => write!(
+                f,
+                "The metadata of payment {} doesn't match the expected type: {}",
+                payment_id, error
+            ),
+            PiError::Signer(ref msg) => write!(f, "Signer error: {}", msg),
         }
     }
 }
@@ -164,10 +505,17 @@ impl Serialize for PiError {
     {
         let error_message = match self {
             PiError::Message(msg) => msg.clone(),
+            PiError::InvalidAccessToken(msg) => msg.clone(),
+            PiError::Api(err) => err.to_string(),
             PiError::Reqwest(err) => err.to_string(),
             PiError::Json(err) => err.to_string(),
+            PiError::Horizon(err) => err.to_string(),
             PiError::Anyhow(err) => err.to_string(),
             PiError::ParseError(err) => err.to_string(),
+            PiError::Amount(err) => err.to_string(),
+            PiError::PaymentArgs(err) => err.to_string(),
+            PiError::MetadataMismatch { .. } => self.to_string(),
+            PiError::Signer(msg) => msg.clone(),
         };
 
         serializer.serialize_str(&error_message)
@@ -188,9 +536,46 @@ impl From<serde_json::Error> for PiError {
     }
 }
 
+impl PiError {
+    /// The kind of the Pi API error, `None` if the error didn't come from the Pi API
+    pub fn api_error_kind(&self) -> Option<PiApiErrorKind> {
+        match self {
+            PiError::Api(err) => Some(err.kind),
+            _ => None,
+        }
+    }
+
+    /// True if a transaction submission got no clear answer, e.g. a timeout or a 504, so it may have been applied or not
+    pub fn is_unknown_submission_outcome(&self) -> bool {
+        match self {
+            PiError::Horizon(err) => err.status >= 500,
+            PiError::Anyhow(_) | PiError::Reqwest(_) => true,
+            _ => false,
+        }
+    }
+
+    /// The transaction result code of a rejected transaction, e.g. `tx_bad_seq`, `None` if the error didn't come from Horizon
+    pub fn transaction_result_code(&self) -> Option<&str> {
+        match self {
+            PiError::Horizon(err) => err.transaction_result_code(),
+            _ => None,
+        }
+    }
+}
+
+// The stellar_sdk returns every error as anyhow, the Horizon errors are taken out of it to be matchable
 impl From<anyhow::Error> for PiError {
     fn from(err: anyhow::Error) -> Self {
-        PiError::Anyhow(err)
+        match err.downcast::<HorizonError>() {
+            Ok(horizon_error) => PiError::Horizon(Box::new(horizon_error)),
+            Err(err) => PiError::Anyhow(err),
+        }
+    }
+}
+
+impl From<HorizonError> for PiError {
+    fn from(err: HorizonError) -> Self {
+        PiError::Horizon(Box::new(err))
     }
 }
 
@@ -199,3 +584,160 @@ impl From<ParseIntError> for PiError {
         PiError::ParseError(err)
     }
 }
+
+impl From<PiAmountError> for PiError {
+    fn from(err: PiAmountError) -> Self {
+        PiError::Amount(err)
+    }
+}
+
+impl From<PaymentArgsError> for PiError {
+    fn from(err: PaymentArgsError) -> Self {
+        PiError::PaymentArgs(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reqwest_options_defaults() {
+        let options: ReqwestClientOptions =
+            serde_json::from_str(r#"{ "base_url": "https://example.com" }"#).unwrap();
+        assert_eq!(options.base_url, "https://example.com");
+        assert_eq!(options.timeout, Some(Duration::from_secs(20)));
+        assert_eq!(options.retry_policy, RetryPolicy::default());
+        assert_eq!(options.fee_policy.strategy, FeeStrategy::LastLedger);
+        assert_eq!(options.transaction_validity, Some(Duration::from_secs(180)));
+        assert!(options.client.is_none());
+    }
+
+    #[test]
+    fn test_signed_transaction_from_envelope() {
+        use stellar_base::{
+            amount::Stroops, asset::Asset, crypto::SodiumKeyPair, operations::Operation,
+        };
+
+        let source = SodiumKeyPair::random().unwrap();
+        let payment = Operation::new_payment()
+            .with_destination(SodiumKeyPair::random().unwrap().public_key())
+            .with_amount("1".parse::<stellar_base::amount::Amount>().unwrap())
+            .unwrap()
+            .with_asset(Asset::new_native())
+            .build()
+            .unwrap();
+        let transaction = Transaction::builder(source.public_key(), 42, Stroops::new(200))
+            .add_operation(payment)
+            .into_transaction()
+            .unwrap();
+
+        let signed = SignedTransaction::new(&transaction, "Pi Testnet").unwrap();
+        assert_eq!(signed.source_account, source.public_key().account_id());
+        assert_eq!(signed.base_fee, 200);
+        assert_eq!(signed.max_time, None);
+        assert_eq!(
+            SignedTransaction::from_envelope(&signed.envelope_xdr, "Pi Testnet").unwrap(),
+            signed
+        );
+        assert!(SignedTransaction::from_envelope("not an envelope", "Pi Testnet").is_err());
+    }
+
+    #[test]
+    fn test_pi_api_error_kinds() {
+        let ongoing = PiApiError::new(
+            400,
+            r#"{"error": "ongoing_payment_found", "error_message": "You need to complete the ongoing payment first to create a new one."}"#,
+        );
+        assert_eq!(ongoing.kind, PiApiErrorKind::OngoingPaymentExists);
+        assert_eq!(ongoing.code, "ongoing_payment_found");
+
+        let not_found = PiApiError::new(
+            404,
+            r#"{"error": "payment_not_found", "error_message": "Payment not found"}"#,
+        );
+        assert_eq!(not_found.kind, PiApiErrorKind::PaymentNotFound);
+
+        let unknown_endpoint = PiApiError::new(
+            404,
+            r#"{"error": "not_found", "error_message": "Unknown endpoint"}"#,
+        );
+        assert_eq!(unknown_endpoint.kind, PiApiErrorKind::Other);
+        assert_eq!(
+            PiApiError::new(404, "Not Found").kind,
+            PiApiErrorKind::Other
+        );
+
+        let completed = PiApiError::new(
+            400,
+            r#"{"error": "already_completed", "error_message": "Payment already completed"}"#,
+        );
+        assert_eq!(completed.kind, PiApiErrorKind::AlreadyCompleted);
+
+        let unauthorized = PiApiError::new(401, "Unauthorized");
+        assert_eq!(unauthorized.kind, PiApiErrorKind::Unauthorized);
+        assert_eq!(unauthorized.message, "Unauthorized");
+
+        let rate_limited = PiApiError::new(429, "");
+        assert_eq!(rate_limited.kind, PiApiErrorKind::RateLimited);
+
+        assert_eq!(
+            PiApiError::new(500, "Internal Server Error").kind,
+            PiApiErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn test_payment_timestamps() {
+        let created_at = |timestamp: &str| {
+            let payment: PaymentDTO = serde_json::from_value(serde_json::json!({
+                "identifier": "payment_id", "user_uid": "user_uid", "amount": 1, "memo": "", "metadata": {},
+                "from_address": "GA", "to_address": "GB", "direction": "app_to_user",
+                "status": {
+                    "developer_approved": true, "transaction_verified": false, "developer_completed": false,
+                    "cancelled": false, "user_cancelled": false
+                },
+                "transaction": null, "created_at": timestamp, "network": "Pi Testnet"
+            }))?;
+            Ok::<_, serde_json::Error>(payment.created_at)
+        };
+        let expected = DateTime::parse_from_rfc3339("2023-01-01T10:00:00.5Z").unwrap();
+        assert_eq!(created_at("2023-01-01T10:00:00.500Z").unwrap(), expected);
+        assert_eq!(
+            created_at("2023-01-01T11:00:00.500+01:00").unwrap(),
+            expected
+        );
+        assert_eq!(created_at("2023-01-01T10:00:00.500").unwrap(), expected);
+        assert!(created_at("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_horizon_error_downcast() {
+        let horizon_error: HorizonError = serde_json::from_str(
+            r#"{
+                "type": "https://stellar.org/horizon-errors/transaction_failed",
+                "title": "Transaction Failed",
+                "status": 400,
+                "detail": "The transaction failed when submitted to the stellar network.",
+                "extras": {
+                    "envelope_xdr": "AAAA",
+                    "result_codes": { "transaction": "tx_bad_seq" },
+                    "result_xdr": "AAAAAAAAAGT////7AAAAAA=="
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let error: PiError = anyhow::Error::from(horizon_error).into();
+        match error {
+            PiError::Horizon(err) => {
+                assert_eq!(err.transaction_result_code(), Some("tx_bad_seq"));
+                assert!(err.operation_result_codes().is_empty());
+            }
+            other => panic!("Expected Horizon error, got {:?}", other),
+        }
+
+        let error: PiError = anyhow::anyhow!("connection reset").into();
+        assert!(matches!(error, PiError::Anyhow(_)));
+    }
+}
@@ -0,0 +1,323 @@
+use serde::{Deserialize, Serialize};
+
+use crate::stellar_sdk::types::{Operation, Transaction};
+use crate::types::{Direction, NetworkPassphrase, PaymentDTO, PiAmount};
+
+/// Result of the server-side verification of a U2A (User-to-App) payment
+///
+/// Made by `PiNetwork::verify_u2a_payment`, comparing the payment stored by the Pi API with the transaction on the Pi Blockchain.
+/// Only complete the payment if `is_valid` returns true, otherwise `failed_checks` tells what didn't match.
+///
+/// - payment_id: String - identifier of the verified payment
+/// - txid: String - the blockchain transaction which was checked
+/// - checks: `Vec<VerificationCheck>` - every check made, passed or failed
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct U2APaymentVerification {
+    pub payment_id: String,
+    pub txid: String,
+    pub checks: Vec<VerificationCheck>,
+}
+
+/// One check of the `U2APaymentVerification`, with the expected and the actual value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationCheck {
+    pub kind: VerificationCheckKind,
+    pub passed: bool,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// See at `VerificationCheck`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerificationCheckKind {
+    /// The payment has to be user to app
+    Direction,
+    /// The payment has to be on the network the `PiNetwork` is configured for
+    Network,
+    /// If the Pi API already linked a txid to the payment, it has to be the same
+    LinkedTransaction,
+    /// The transaction has to be successful on the blockchain
+    Successful,
+    /// The memo of the transaction has to be the payment ID
+    Memo,
+    /// The transaction has to contain exactly one payment operation
+    PaymentOperation,
+    /// The payment has to come from the user's wallet
+    Source,
+    /// The payment has to go to the app wallet
+    Destination,
+    Amount,
+    /// The payment has to be in the native Pi
+    Asset,
+}
+
+impl U2APaymentVerification {
+    /// Making every check from the already loaded data, it's not calling any API
+    ///
+    /// - payment: the payment from the Pi API
+    /// - transaction: the transaction loaded from Horizon by the txid
+    /// - operations: the payment operations of the transaction loaded from Horizon
+    /// - app_wallet: public key of the app wallet, which should receive the payment
+    /// - network: the network the app is configured for
+    pub fn new(
+        payment: &PaymentDTO,
+        transaction: &Transaction,
+        operations: &[Operation],
+        app_wallet: &str,
+        network: &NetworkPassphrase,
+    ) -> Self {
+        let mut checks = Vec::new();
+        let mut check = |kind, passed, expected: String, actual: String| {
+            checks.push(VerificationCheck {
+                kind,
+                passed,
+                expected,
+                actual,
+            })
+        };
+
+        check(
+            VerificationCheckKind::Direction,
+            matches!(payment.direction, Direction::UserToApp),
+            format!("{:?}", Direction::UserToApp),
+            format!("{:?}", payment.direction),
+        );
+        check(
+            VerificationCheckKind::Network,
+            payment.network == *network,
+            format!("{:?}", network),
+            format!("{:?}", payment.network),
+        );
+        if let Some(linked) = &payment.transaction {
+            check(
+                VerificationCheckKind::LinkedTransaction,
+                linked.txid == transaction.hash,
+                linked.txid.clone(),
+                transaction.hash.clone(),
+            );
+        }
+        check(
+            VerificationCheckKind::Successful,
+            transaction.successful,
+            true.to_string(),
+            transaction.successful.to_string(),
+        );
+        check(
+            VerificationCheckKind::Memo,
+            transaction.memo_type == "text"
+                && transaction.memo.as_deref() == Some(payment.identifier.as_str()),
+            payment.identifier.clone(),
+            transaction.memo.clone().unwrap_or_default(),
+        );
+
+        let payment_operations: Vec<&Operation> = operations
+            .iter()
+            .filter(|operation| operation.r#type == "payment")
+            .collect();
+        check(
+            VerificationCheckKind::PaymentOperation,
+            payment_operations.len() == 1,
+            1.to_string(),
+            payment_operations.len().to_string(),
+        );
+
+        if let [operation] = payment_operations.as_slice() {
+            let from = operation.from.clone().unwrap_or_default();
+            check(
+                VerificationCheckKind::Source,
+                from == payment.from_address,
+                payment.from_address.clone(),
+                from,
+            );
+
+            let to = operation.to.clone().unwrap_or_default();
+            check(
+                VerificationCheckKind::Destination,
+                to == payment.to_address && to == app_wallet,
+                app_wallet.to_string(),
+                to,
+            );
+
+            let amount = operation
+                .amount
+                .as_deref()
+                .and_then(|amount| amount.parse::<PiAmount>().ok());
+            check(
+                VerificationCheckKind::Amount,
+                amount == Some(payment.amount),
+                payment.amount.to_string(),
+                operation.amount.clone().unwrap_or_default(),
+            );
+
+            let asset_type = operation.asset_type.clone().unwrap_or_default();
+            check(
+                VerificationCheckKind::Asset,
+                asset_type == "native",
+                "native".to_string(),
+                asset_type,
+            );
+        }
+
+        U2APaymentVerification {
+            payment_id: payment.identifier.clone(),
+            txid: transaction.hash.clone(),
+            checks,
+        }
+    }
+
+    /// True if every check passed, the payment can be completed
+    pub fn is_valid(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    pub fn failed_checks(&self) -> impl Iterator<Item = &VerificationCheck> {
+        self.checks.iter().filter(|check| !check.passed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const APP_WALLET: &str = "GAUZUPTHOMSZEV65VNSRMUDAAE4VBMSRYYAX3UOWYU3BQUZ6OK65NOWM";
+    const USER_WALLET: &str = "GAP2TJNW7NL52MPB36DZ2PB6PSIBEUEJXDG325BJQKUNDQBPKX3E2DLV";
+    const TXID: &str = "3389e9f0f1a65f19736cacf544c2e825313e8447f569233bb8db39aa607c8889";
+
+    fn payment() -> PaymentDTO {
+        serde_json::from_value(json!({
+            "identifier": "payment_id",
+            "user_uid": "user_uid",
+            "amount": 3.25,
+            "memo": "Apple pie",
+            "metadata": { "productId": "apple-pie-1" },
+            "from_address": USER_WALLET,
+            "to_address": APP_WALLET,
+            "direction": "user_to_app",
+            "status": {
+                "developer_approved": true,
+                "transaction_verified": true,
+                "developer_completed": false,
+                "cancelled": false,
+                "user_cancelled": false
+            },
+            "transaction": { "txid": TXID, "verified": true, "_link": "link" },
+            "created_at": "2023-01-01T00:00:00.000Z",
+            "network": "Pi Testnet"
+        }))
+        .unwrap()
+    }
+
+    fn link() -> Value {
+        json!({ "href": "link" })
+    }
+
+    fn transaction() -> Transaction {
+        serde_json::from_value(json!({
+            "memo": "payment_id",
+            "_links": {},
+            "id": TXID,
+            "paging_token": "1",
+            "successful": true,
+            "hash": TXID,
+            "ledger": 1,
+            "created_at": "2023-01-01T00:00:05Z",
+            "source_account": USER_WALLET,
+            "source_account_sequence": "1",
+            "fee_account": USER_WALLET,
+            "fee_charged": "100000",
+            "max_fee": "100000",
+            "operation_count": 1,
+            "envelope_xdr": "",
+            "result_xdr": "",
+            "result_meta_xdr": "",
+            "fee_meta_xdr": "",
+            "memo_type": "text",
+            "signatures": []
+        }))
+        .unwrap()
+    }
+
+    fn operation() -> Operation {
+        serde_json::from_value(json!({
+            "_links": {
+                "self": link(),
+                "transaction": link(),
+                "effects": link(),
+                "succeeds": link(),
+                "precedes": link()
+            },
+            "id": "1",
+            "paging_token": "1",
+            "transaction_successful": true,
+            "source_account": USER_WALLET,
+            "type": "payment",
+            "type_i": 1,
+            "created_at": "2023-01-01T00:00:05Z",
+            "transaction_hash": TXID,
+            "asset_type": "native",
+            "from": USER_WALLET,
+            "to": APP_WALLET,
+            "amount": "3.2500000"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_valid_payment() {
+        let report = U2APaymentVerification::new(
+            &payment(),
+            &transaction(),
+            &[operation()],
+            APP_WALLET,
+            &NetworkPassphrase::PiTestnet,
+        );
+
+        assert!(report.is_valid());
+        assert_eq!(report.failed_checks().count(), 0);
+    }
+
+    #[test]
+    fn test_mismatches_are_reported() {
+        let mut transaction = transaction();
+        transaction.memo = Some("other_payment".to_string());
+        let mut operation = operation();
+        operation.amount = Some("3.1399999".to_string());
+
+        let report = U2APaymentVerification::new(
+            &payment(),
+            &transaction,
+            &[operation],
+            APP_WALLET,
+            &NetworkPassphrase::PiNetwork,
+        );
+
+        let failed: Vec<VerificationCheckKind> =
+            report.failed_checks().map(|check| check.kind).collect();
+        assert_eq!(
+            failed,
+            vec![
+                VerificationCheckKind::Network,
+                VerificationCheckKind::Memo,
+                VerificationCheckKind::Amount
+            ]
+        );
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn test_missing_payment_operation() {
+        let report = U2APaymentVerification::new(
+            &payment(),
+            &transaction(),
+            &[],
+            APP_WALLET,
+            &NetworkPassphrase::PiTestnet,
+        );
+
+        let failed: Vec<VerificationCheckKind> =
+            report.failed_checks().map(|check| check.kind).collect();
+        assert_eq!(failed, vec![VerificationCheckKind::PaymentOperation]);
+    }
+}
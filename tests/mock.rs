@@ -0,0 +1,1633 @@
+#![cfg(feature = "mock")]
+
+use pi_rust::mock::{MockError, MockPiApi};
+use pi_rust::stellar_sdk::keypair::Keypair;
+use pi_rust::{types::*, PiNetwork};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const API_KEY: &str = "mock_api_key";
+const USER_UID: &str = "user_uid";
+
+fn setup() -> (MockPiApi, PiNetwork, String) {
+    let mut app_wallet = Keypair::random().unwrap();
+    let user_wallet = Keypair::random().unwrap();
+
+    let mock = MockPiApi::start(API_KEY, &app_wallet.public_key()).unwrap();
+    let access_token = mock.add_user(USER_UID, Some("username"), &user_wallet.public_key());
+
+    let options = ReqwestClientOptions {
+        base_url: mock.url(),
+        retry_policy: RetryPolicy {
+            initial_backoff: Duration::from_millis(10),
+            ..RetryPolicy::default()
+        },
+        ..ReqwestClientOptions::default()
+    };
+    let pi = PiNetwork::new(
+        API_KEY.to_string(),
+        app_wallet.secret_key().unwrap(),
+        None,
+        Some(options),
+    )
+    .unwrap();
+
+    (mock, pi, access_token)
+}
+
+fn payment_args(amount: &str) -> PaymentArgs {
+    PaymentArgs {
+        amount: amount.parse().unwrap(),
+        memo: "Refund for apple pie".to_string(),
+        metadata: json!({"productId": "apple-pie-1"}),
+        uid: USER_UID.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_mock_a2u_lifecycle() {
+    let (_mock, pi, _) = setup();
+
+    let payment_id = pi.create_payment(payment_args("0.1")).await.unwrap();
+    let payment: PaymentDTO = pi.get_payment(payment_id.clone()).await.unwrap();
+    assert_eq!(payment.direction, Direction::AppToUser);
+    assert!(payment.status.developer_approved);
+    assert!(payment.transaction.is_none());
+    assert_eq!(payment.state().unwrap(), PaymentState::Approved);
+    assert!(!payment.is_older_than(Duration::from_secs(60)));
+
+    let err = pi.create_payment(payment_args("0.2")).await.unwrap_err();
+    assert_eq!(
+        err.api_error_kind(),
+        Some(PiApiErrorKind::OngoingPaymentExists)
+    );
+
+    let incomplete = pi.get_incomplete_server_payments().await.unwrap();
+    assert_eq!(incomplete.len(), 1);
+    assert_eq!(incomplete[0].identifier, payment_id);
+
+    let completed = pi
+        .complete_payment(payment_id.clone(), "txid".to_string())
+        .await
+        .unwrap();
+    assert!(completed.status.developer_completed);
+    assert_eq!(completed.state().unwrap(), PaymentState::Completed);
+    assert_eq!(completed.transaction.unwrap().txid, "txid");
+    assert!(pi
+        .get_incomplete_server_payments()
+        .await
+        .unwrap()
+        .is_empty());
+
+    let err = pi
+        .complete_payment(payment_id, "txid".to_string())
+        .await
+        .unwrap_err();
+    assert_eq!(err.api_error_kind(), Some(PiApiErrorKind::AlreadyCompleted));
+
+    let payment_id = pi.create_payment(payment_args("0.3")).await.unwrap();
+    let cancelled = pi.cancel_payment(payment_id).await.unwrap();
+    assert!(cancelled.status.cancelled);
+    assert_eq!(
+        cancelled.state().unwrap(),
+        PaymentState::CancelledByDeveloper
+    );
+    assert!(pi
+        .get_incomplete_server_payments()
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_mock_u2a_lifecycle() {
+    let (mock, pi, access_token) = setup();
+
+    let user = pi.authenticate_user(access_token).await.unwrap();
+    assert_eq!(user.uid, USER_UID);
+    match pi.authenticate_user("invalid".to_string()).await {
+        Err(PiError::InvalidAccessToken(msg)) => {
+            assert!(
+                msg.contains("Invalid access token") && !msg.contains("Ok("),
+                "{msg}"
+            )
+        }
+        other => panic!("Expected an invalid access token error, got {other:?}"),
+    }
+
+    let payment = mock.create_user_payment(payment_args("1")).unwrap();
+    assert_eq!(payment.direction, Direction::UserToApp);
+    assert!(pi
+        .complete_payment(payment.identifier.clone(), "txid".to_string())
+        .await
+        .is_err());
+
+    let approved = pi
+        .approve_payment(payment.identifier.clone())
+        .await
+        .unwrap();
+    assert!(approved.status.developer_approved);
+    assert!(pi
+        .approve_payment(payment.identifier.clone())
+        .await
+        .is_err());
+
+    mock.link_transaction(&payment.identifier, "user_txid")
+        .unwrap();
+    assert!(pi
+        .complete_payment(payment.identifier.clone(), "other_txid".to_string())
+        .await
+        .is_err());
+    let completed = pi
+        .complete_payment(payment.identifier.clone(), "user_txid".to_string())
+        .await
+        .unwrap();
+    assert!(completed.status.developer_completed);
+    assert!(
+        mock.payment(&payment.identifier)
+            .unwrap()
+            .status
+            .developer_completed
+    );
+}
+
+#[tokio::test]
+async fn test_mock_typed_metadata() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct OrderRef {
+        order_id: u64,
+        product_id: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Invoice {
+        #[allow(dead_code)]
+        invoice_number: String,
+    }
+
+    let (_mock, pi, _) = setup();
+    let order = OrderRef {
+        order_id: 42,
+        product_id: "apple-pie-1".to_string(),
+    };
+    let args = PaymentArgs::builder()
+        .amount("1".parse().unwrap())
+        .memo("Refund for apple pie")
+        .metadata(order)
+        .uid(USER_UID)
+        .build()
+        .unwrap();
+    let payment_id = pi.create_payment(args).await.unwrap();
+
+    let payment = pi
+        .get_payment::<OrderRef>(payment_id.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        payment.metadata,
+        OrderRef {
+            order_id: 42,
+            product_id: "apple-pie-1".to_string(),
+        }
+    );
+    let payment = pi.get_payment::<Value>(payment_id.clone()).await.unwrap();
+    assert_eq!(payment.metadata["orderId"], json!(42));
+
+    let err = pi
+        .get_payment::<Invoice>(payment_id.clone())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, PiError::MetadataMismatch { ref payment_id, .. } if *payment_id == payment.identifier)
+    );
+
+    // Metadata which isn't an object is rejected before it's sent
+    let err = PaymentArgs::builder()
+        .amount("1".parse().unwrap())
+        .metadata(42)
+        .uid(USER_UID)
+        .build()
+        .unwrap_err();
+    assert_eq!(err, PaymentArgsError::MetadataNotObject);
+}
+
+#[tokio::test]
+async fn test_mock_errors() {
+    let (mock, pi, _) = setup();
+
+    let err = pi
+        .get_payment::<Value>("unknown".to_string())
+        .await
+        .unwrap_err();
+    assert_eq!(err.api_error_kind(), Some(PiApiErrorKind::PaymentNotFound));
+
+    // Invalid arguments are rejected before they are sent
+    let err = pi.create_payment(payment_args("0")).await.unwrap_err();
+    assert!(matches!(
+        err,
+        PiError::PaymentArgs(PaymentArgsError::AmountNotPositive(_))
+    ));
+    assert_eq!(mock.request_count("POST /v2/payments"), 0);
+
+    let payment_id = pi.create_payment(payment_args("0.1")).await.unwrap();
+
+    // Transient failures of idempotent calls are retried
+    mock.inject_error(
+        MockError::new("GET /v2/payments/", 503, "unavailable", "Try later").times(2),
+    );
+    let payment: PaymentDTO = pi.get_payment(payment_id.clone()).await.unwrap();
+    assert_eq!(payment.identifier, payment_id);
+    assert_eq!(mock.request_count(&payment_id), 3);
+
+    // But not for the ones which could have been processed
+    mock.inject_error(MockError::new("/cancel", 503, "unavailable", "Try later"));
+    assert!(pi.cancel_payment(payment_id.clone()).await.is_err());
+    assert_eq!(mock.request_count("/cancel"), 1);
+
+    // Rate limited requests weren't processed, so they are retried
+    mock.inject_error(MockError::new("/cancel", 429, "rate_limited", "Slow down"));
+    let cancelled = pi.cancel_payment(payment_id.clone()).await.unwrap();
+    assert!(cancelled.status.cancelled);
+    assert_eq!(mock.request_count("/cancel"), 3);
+
+    // The first attempt completed the payment, only its response was lost
+    let completed_id = pi.create_payment(payment_args("0.2")).await.unwrap();
+    mock.inject_error(
+        MockError::new("/complete", 503, "unavailable", "Try later").after_processing(),
+    );
+    let completed = pi
+        .complete_payment(completed_id, "txid".to_string())
+        .await
+        .unwrap();
+    assert!(completed.status.developer_completed);
+    assert_eq!(mock.request_count("/complete"), 2);
+
+    mock.inject_error(
+        MockError::new("GET /v2/payments/", 429, "rate_limited", "Slow down").times(3),
+    );
+    let err = pi
+        .get_payment::<Value>(payment_id.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(err.api_error_kind(), Some(PiApiErrorKind::RateLimited));
+
+    // A day long Retry-After isn't waited for, the rate limit is returned at once
+    let mut error = MockError::new("GET /v2/payments/", 429, "rate_limited", "Slow down");
+    error.response = error.response.with_header("Retry-After", "86400");
+    mock.inject_error(error);
+    let requests = mock.request_count(&payment_id);
+    let err = tokio::time::timeout(
+        Duration::from_secs(5),
+        pi.get_payment::<Value>(payment_id.clone()),
+    )
+    .await
+    .unwrap()
+    .unwrap_err();
+    assert_eq!(err.api_error_kind(), Some(PiApiErrorKind::RateLimited));
+    assert_eq!(mock.request_count(&payment_id), requests + 1);
+
+    let wrong_key = PiNetwork::new(
+        "wrong_key".to_string(),
+        Keypair::random().unwrap().secret_key().unwrap(),
+        None,
+        Some(ReqwestClientOptions {
+            base_url: mock.url(),
+            ..ReqwestClientOptions::default()
+        }),
+    )
+    .unwrap();
+    let err = wrong_key
+        .get_incomplete_server_payments()
+        .await
+        .unwrap_err();
+    assert_eq!(err.api_error_kind(), Some(PiApiErrorKind::Unauthorized));
+}
+
+mod horizon {
+    use pi_rust::channels::ChannelPool;
+    use pi_rust::flow::{PaymentFlow, PaymentFlowState};
+    use pi_rust::mock::{MockError, MockHorizon, MockPiApi};
+    use pi_rust::resolution::{
+        IncompletePaymentAction, IncompletePaymentKind, IncompletePaymentPolicy,
+    };
+    use pi_rust::stellar_sdk::{
+        endpoints::{CallBuilder, Server, ServerOptions},
+        keypair::Keypair,
+        types::HorizonError,
+        utils::Endpoint,
+    };
+    use pi_rust::store::{MemoryPaymentStore, PaymentStore};
+    use pi_rust::{types::*, PiNetwork};
+    use serde_json::json;
+    use std::str::FromStr;
+    use std::time::Duration;
+    use stellar_base::{
+        amount::{Amount, Stroops},
+        asset::Asset,
+        crypto::SodiumKeyPair,
+        memo::Memo,
+        operations::Operation,
+        transaction::Transaction,
+        Network,
+    };
+
+    const PASSPHRASE: &str = "Pi Testnet";
+
+    fn server(horizon: &MockHorizon) -> Server {
+        let options = ServerOptions {
+            allow_http: Some(true),
+            network_passphrase: Some(PASSPHRASE.to_string()),
+            ..ServerOptions::default()
+        };
+        Server::new(horizon.url(), Some(options)).unwrap()
+    }
+
+    fn payment(
+        source: &SodiumKeyPair,
+        signer: &SodiumKeyPair,
+        destination: &SodiumKeyPair,
+        sequence: i64,
+        amount: &str,
+    ) -> Transaction {
+        let operation = Operation::new_payment()
+            .with_destination(destination.public_key())
+            .with_amount(Amount::from_str(amount).unwrap())
+            .unwrap()
+            .with_asset(Asset::new_native())
+            .build()
+            .unwrap();
+        let mut transaction =
+            Transaction::builder(source.public_key(), sequence, Stroops::new(100))
+                .with_memo(Memo::Text("mock".to_string()))
+                .add_operation(operation)
+                .into_transaction()
+                .unwrap();
+        transaction
+            .sign(signer.as_ref(), &Network::new(PASSPHRASE.to_string()))
+            .unwrap();
+        transaction
+    }
+
+    fn result_code(err: anyhow::Error) -> (String, Vec<String>) {
+        let err = err.downcast::<HorizonError>().unwrap();
+        (
+            err.transaction_result_code().unwrap().to_string(),
+            err.operation_result_codes().to_vec(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_mock_horizon_submit() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        let s = server(&horizon);
+        let alice = SodiumKeyPair::random().unwrap();
+        let bob = SodiumKeyPair::random().unwrap();
+        let alice_id = alice.public_key().account_id();
+        let bob_id = bob.public_key().account_id();
+        horizon.create_account(&alice_id, "10").unwrap();
+        horizon.create_account(&bob_id, "1").unwrap();
+
+        let account = s.load_account(&alice_id).await.unwrap();
+        assert_eq!(account.balances[0].balance, "10.0000000");
+        assert_eq!(s.fetch_base_fee().await.unwrap(), "100");
+        let sequence = account.sequence.parse::<i64>().unwrap();
+
+        let response = s
+            .submit_transaction(payment(&alice, &alice, &bob, sequence + 1, "2.5"))
+            .await
+            .unwrap();
+        assert!(response.successful);
+        assert_eq!(response.memo.as_deref(), Some("mock"));
+        assert_eq!(horizon.balance(&alice_id).unwrap(), "7.4999900");
+        assert_eq!(horizon.balance(&bob_id).unwrap(), "3.5000000");
+        assert_eq!(horizon.sequence(&alice_id), Some(sequence + 1));
+
+        // The same envelope is answered with the transaction already in the ledger
+        let again = s
+            .submit_transaction_envelope(&response.envelope_xdr)
+            .await
+            .unwrap();
+        assert_eq!(again.hash, response.hash);
+        assert_eq!(horizon.balance(&bob_id).unwrap(), "3.5000000");
+
+        let transaction = s.load_transaction(&response.hash).await.unwrap();
+        assert_eq!(transaction.ledger, horizon.latest_ledger() as u64);
+        let ledger = s.load_ledger(horizon.latest_ledger() as u64).await.unwrap();
+        assert_eq!(ledger.successful_transaction_count, 1);
+        let payments = s
+            .payments()
+            .for_endpoint(Endpoint::Accounts(bob_id.clone()))
+            .call()
+            .await
+            .unwrap();
+        assert_eq!(payments._embedded.records.len(), 1);
+        assert_eq!(
+            payments._embedded.records[0].amount.as_deref(),
+            Some("2.5000000")
+        );
+        let operation = s
+            .load_operation(&payments._embedded.records[0].id)
+            .await
+            .unwrap();
+        assert_eq!(operation.transaction_hash, response.hash);
+
+        let err = s
+            .submit_transaction(payment(&alice, &alice, &bob, sequence + 1, "1"))
+            .await
+            .unwrap_err();
+        assert_eq!(result_code(err).0, "tx_bad_seq");
+        let err = s
+            .submit_transaction(payment(&alice, &bob, &bob, sequence + 2, "1"))
+            .await
+            .unwrap_err();
+        assert_eq!(result_code(err).0, "tx_bad_auth");
+
+        // Failed operations still get into the ledger, the fee is charged and the sequence is used
+        let err = s
+            .submit_transaction(payment(&alice, &alice, &bob, sequence + 2, "100"))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            result_code(err),
+            ("tx_failed".to_string(), vec!["op_underfunded".to_string()])
+        );
+        assert_eq!(horizon.sequence(&alice_id), Some(sequence + 2));
+        assert_eq!(horizon.balance(&alice_id).unwrap(), "7.4999800");
+        let transactions = s
+            .transactions()
+            .for_endpoint(Endpoint::Accounts(alice_id.clone()))
+            .include_failed(true)
+            .call()
+            .await
+            .unwrap();
+        assert_eq!(transactions._embedded.records.len(), 2);
+        assert!(!transactions._embedded.records[1].successful);
+
+        let stranger = SodiumKeyPair::random().unwrap();
+        let err = s
+            .submit_transaction(payment(&alice, &alice, &stranger, sequence + 3, "1"))
+            .await
+            .unwrap_err();
+        assert_eq!(result_code(err).1, vec!["op_no_destination".to_string()]);
+        assert!(s
+            .load_account(&stranger.public_key().account_id())
+            .await
+            .is_err());
+    }
+
+    #[cfg(feature = "ureq")]
+    #[test]
+    fn test_mock_horizon_blocking() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        let s = pi_rust::stellar_sdk::blocking::Server::from(server(&horizon));
+        let alice = SodiumKeyPair::random().unwrap();
+        let bob = SodiumKeyPair::random().unwrap();
+        let alice_id = alice.public_key().account_id();
+        horizon.create_account(&alice_id, "10").unwrap();
+        horizon
+            .create_account(&bob.public_key().account_id(), "1")
+            .unwrap();
+
+        let sequence = s
+            .load_account(&alice_id)
+            .unwrap()
+            .sequence
+            .parse::<i64>()
+            .unwrap();
+        let response = s
+            .submit_transaction(payment(&alice, &alice, &bob, sequence + 1, "1"))
+            .unwrap();
+        assert!(response.successful);
+        let transactions = s
+            .transactions()
+            .for_endpoint(Endpoint::Accounts(alice_id.clone()))
+            .call_blocking()
+            .unwrap();
+        assert_eq!(transactions._embedded.records[0].hash, response.hash);
+    }
+
+    #[tokio::test]
+    async fn test_mock_horizon_lost_response() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        let s = server(&horizon);
+        let alice = SodiumKeyPair::random().unwrap();
+        let bob = SodiumKeyPair::random().unwrap();
+        let alice_id = alice.public_key().account_id();
+        horizon.create_account(&alice_id, "10").unwrap();
+        horizon
+            .create_account(&bob.public_key().account_id(), "1")
+            .unwrap();
+        let sequence = horizon.sequence(&alice_id).unwrap();
+
+        // The transaction is applied but the response is lost, it's found by its hash instead of paying twice
+        horizon.inject_error(
+            MockError::response(
+                "POST /transactions",
+                pi_rust::mock::MockResponse::json(504, &json!({})),
+            )
+            .after_processing(),
+        );
+        let response = s
+            .submit_transaction(payment(&alice, &alice, &bob, sequence + 1, "1"))
+            .await
+            .unwrap();
+        assert!(response.successful);
+        assert_eq!(horizon.request_count("POST /transactions"), 1);
+        assert_eq!(horizon.balance(&alice_id).unwrap(), "8.9999900");
+    }
+
+    #[tokio::test]
+    async fn test_mock_horizon_gateway_timeout() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        horizon.set_ledger_interval(Duration::from_secs(1));
+        let timeout = || {
+            MockError::response(
+                "POST /transactions",
+                pi_rust::mock::MockResponse::json(
+                    504,
+                    &json!({
+                        "type": "https://stellar.org/horizon-errors/timeout",
+                        "title": "Timeout",
+                        "status": 504,
+                        "detail": "Your request timed out before completing.",
+                    }),
+                ),
+            )
+            .after_processing()
+        };
+
+        // The problem of the 504 is kept, and it's still an unknown outcome
+        let alice = SodiumKeyPair::random().unwrap();
+        let bob = SodiumKeyPair::random().unwrap();
+        let alice_id = alice.public_key().account_id();
+        horizon.create_account(&alice_id, "10").unwrap();
+        horizon
+            .create_account(&bob.public_key().account_id(), "1")
+            .unwrap();
+        let sequence = horizon.sequence(&alice_id).unwrap();
+        let options = ServerOptions {
+            allow_http: Some(true),
+            retry_policy: Some(RetryPolicy {
+                max_attempts: 1,
+                ..RetryPolicy::default()
+            }),
+            ..ServerOptions::default()
+        };
+        let s = Server::new(horizon.url(), Some(options)).unwrap();
+        horizon.inject_error(timeout());
+        let err: PiError = s
+            .submit_transaction(payment(&alice, &alice, &bob, sequence + 1, "1"))
+            .await
+            .unwrap_err()
+            .into();
+        match &err {
+            PiError::Horizon(horizon_error) => assert_eq!(horizon_error.status, 504),
+            other => panic!("Expected Horizon error, got {:?}", other),
+        }
+        assert!(err.is_unknown_submission_outcome());
+
+        // The payment isn't failed, its transaction is found once the outcome is known
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        let user_id = Keypair::random().unwrap().public_key();
+        horizon.create_account(&app_id, "100").unwrap();
+        horizon.create_account(&user_id, "1").unwrap();
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        pi_api.add_user(super::USER_UID, None, &user_id);
+        let options = ReqwestClientOptions {
+            base_url: pi_api.url(),
+            horizon_url: Some(horizon.url()),
+            retry_policy: RetryPolicy {
+                max_attempts: 1,
+                ..RetryPolicy::default()
+            },
+            transaction_validity: Some(Duration::from_secs(1)),
+            ..ReqwestClientOptions::default()
+        };
+        let pi = PiNetwork::new(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            None,
+            Some(options),
+        )
+        .unwrap();
+        let payment_id = pi.create_payment(super::payment_args("1")).await.unwrap();
+        horizon.inject_error(timeout());
+        let txid = pi.submit_payment(payment_id).await.unwrap();
+        assert!(horizon.transaction(&txid).unwrap().successful);
+        assert_eq!(horizon.balance(&user_id).unwrap(), "2.0000000");
+    }
+
+    #[tokio::test]
+    async fn test_mock_submit_payment() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        let user_id = Keypair::random().unwrap().public_key();
+        horizon.create_account(&app_id, "100").unwrap();
+        horizon.create_account(&user_id, "1").unwrap();
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        pi_api.add_user(super::USER_UID, None, &user_id);
+        let options = ReqwestClientOptions {
+            base_url: pi_api.url(),
+            horizon_url: Some(horizon.url()),
+            ..ReqwestClientOptions::default()
+        };
+        let pi = PiNetwork::new(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            None,
+            Some(options),
+        )
+        .unwrap();
+
+        let payment_id = pi.create_payment(super::payment_args("2.5")).await.unwrap();
+        // The submission is an ordinary task of the runtime, nothing in it blocks the thread
+        let txid = tokio::spawn({
+            let pi = pi.clone();
+            let payment_id = payment_id.clone();
+            async move { pi.submit_payment(payment_id).await }
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        let completed = pi
+            .complete_payment(payment_id.clone(), txid.clone())
+            .await
+            .unwrap();
+        assert!(completed.status.developer_completed);
+
+        let transaction = horizon.transaction(&txid).unwrap();
+        assert_eq!(transaction.memo.as_deref(), Some(payment_id.as_str()));
+        assert_eq!(horizon.balance(&user_id).unwrap(), "3.5000000");
+        assert_eq!(horizon.balance(&app_id).unwrap(), "97.4999900");
+    }
+
+    #[tokio::test]
+    async fn test_mock_flow_stores_created_payment() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        let user_id = Keypair::random().unwrap().public_key();
+        horizon.create_account(&app_id, "100").unwrap();
+        horizon.create_account(&user_id, "1").unwrap();
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        pi_api.add_user(super::USER_UID, None, &user_id);
+        let options = ReqwestClientOptions {
+            base_url: pi_api.url(),
+            horizon_url: Some(horizon.url()),
+            retry_policy: RetryPolicy {
+                max_attempts: 1,
+                ..RetryPolicy::default()
+            },
+            ..ReqwestClientOptions::default()
+        };
+        let pi = PiNetwork::new(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            None,
+            Some(options),
+        )
+        .unwrap();
+        let flow = PaymentFlow::new(pi, MemoryPaymentStore::default());
+
+        // The payment is created, but its first read fails
+        pi_api.inject_error(MockError::new(
+            "GET /v2/payments/",
+            503,
+            "unavailable",
+            "Try later",
+        ));
+        assert!(flow.start(super::payment_args("2.5")).await.is_err());
+        let record = flow.store().find_by_uid(super::USER_UID).unwrap().remove(0);
+        assert_eq!(record.state, PaymentFlowState::Created);
+        assert_eq!(record.amount, "2.5".parse().unwrap());
+        assert!(pi_api.payment(&record.payment_id).is_some());
+
+        let record = flow.resume(&record.payment_id).await.unwrap();
+        assert_eq!(record.state, PaymentFlowState::Completed);
+        assert_eq!(horizon.balance(&user_id).unwrap(), "3.5000000");
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_mock_blocking_pi_network() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        let user_id = Keypair::random().unwrap().public_key();
+        horizon.create_account(&app_id, "100").unwrap();
+        horizon.create_account(&user_id, "1").unwrap();
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        pi_api.add_user(super::USER_UID, None, &user_id);
+        let options = ReqwestClientOptions {
+            base_url: pi_api.url(),
+            horizon_url: Some(horizon.url()),
+            ..ReqwestClientOptions::default()
+        };
+        // No runtime here, the blocking PiNetwork brings its own
+        let pi = pi_rust::blocking::PiNetwork::new(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            None,
+            Some(options),
+        )
+        .unwrap();
+
+        let payment_id = pi.create_payment(super::payment_args("1.5")).unwrap();
+        let txid = pi.submit_payment(payment_id.clone()).unwrap();
+        let completed = pi.complete_payment(payment_id.clone(), txid).unwrap();
+        assert_eq!(completed.state().unwrap(), PaymentState::Completed);
+        let payment: PaymentDTO = pi.get_payment(payment_id).unwrap();
+        assert!(payment.status.developer_completed);
+        assert_eq!(horizon.balance(&user_id).unwrap(), "2.5000000");
+
+        let err = pi.get_payment::<serde_json::Value>("unknown".to_string());
+        assert!(err.unwrap_err().api_error_kind().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_mock_network_profile() {
+        // A local stand-in network with its own passphrase
+        let horizon = MockHorizon::start("Local Stand-in").unwrap();
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        let user_id = Keypair::random().unwrap().public_key();
+        horizon.create_account(&app_id, "100").unwrap();
+        horizon.create_account(&user_id, "1").unwrap();
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        pi_api.add_user(super::USER_UID, None, &user_id);
+        let profile = NetworkProfile::testnet()
+            .with_passphrase("Local Stand-in")
+            .with_horizon_url(horizon.url())
+            .with_pi_api_url(pi_api.url());
+        let pi = PiNetwork::with_profile(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            profile,
+            None,
+        )
+        .unwrap();
+
+        let payment_id = pi.create_payment(super::payment_args("2.5")).await.unwrap();
+        let txid = pi.submit_payment(payment_id.clone()).await.unwrap();
+        assert!(horizon.transaction(&txid).unwrap().successful);
+        assert_eq!(horizon.balance(&user_id).unwrap(), "3.5000000");
+        pi.complete_payment(payment_id, txid).await.unwrap();
+
+        // The payments of another network are not signed for this one
+        pi_api.set_network(NetworkPassphrase::PiNetwork);
+        let payment_id = pi.create_payment(super::payment_args("2.5")).await.unwrap();
+        let err = pi.submit_payment(payment_id.clone()).await.unwrap_err();
+        assert!(err.to_string().contains("Pi Network"));
+        assert_eq!(horizon.request_count("POST /transactions"), 1);
+
+        // A profile with the name of the stand-in as its network accepts the payments reported with it
+        pi.cancel_payment(payment_id).await.unwrap();
+        let network = NetworkPassphrase::Custom("Local Stand-in".to_string());
+        pi_api.set_network(network.clone());
+        let stand_in = PiNetwork::with_profile(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            NetworkProfile::from(network)
+                .with_horizon_url(horizon.url())
+                .with_pi_api_url(pi_api.url()),
+            None,
+        )
+        .unwrap();
+        let payment_id = stand_in
+            .create_payment(super::payment_args("1"))
+            .await
+            .unwrap();
+        assert!(pi.submit_payment(payment_id.clone()).await.is_err());
+        let txid = stand_in.submit_payment(payment_id).await.unwrap();
+        assert!(horizon.transaction(&txid).unwrap().successful);
+        assert_eq!(horizon.balance(&user_id).unwrap(), "4.5000000");
+
+        // And the channel accounts have to hold the minimum balance
+        let pool = ChannelPool::generate(1).unwrap();
+        assert!(pi
+            .create_channel_accounts(&pool, "0.5".parse().unwrap())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mock_sequence_resync() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        horizon.create_account(&app_id, "100").unwrap();
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        let options = ReqwestClientOptions {
+            base_url: pi_api.url(),
+            horizon_url: Some(horizon.url()),
+            ..ReqwestClientOptions::default()
+        };
+        let pi = PiNetwork::new(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            None,
+            Some(options),
+        )
+        .unwrap();
+        let load_account = format!("GET /accounts/{}", app_id);
+        let pay = |uid: &'static str| {
+            let wallet = Keypair::random().unwrap().public_key();
+            horizon.create_account(&wallet, "1").unwrap();
+            pi_api.add_user(uid, None, &wallet);
+            let args = PaymentArgs {
+                uid: uid.to_string(),
+                ..super::payment_args("1")
+            };
+            let pi = &pi;
+            async move {
+                let payment_id = pi.create_payment(args).await.unwrap();
+                pi.submit_payment(payment_id).await
+            }
+        };
+
+        // The account is only loaded for the first transaction
+        pay("alice").await.unwrap();
+        pay("bob").await.unwrap();
+        assert_eq!(horizon.request_count(&load_account), 1);
+        let sequence = horizon.sequence(&app_id).unwrap();
+        assert_eq!(pi.sequence_manager().last_used(&app_id), Some(sequence));
+
+        // Someone else used the wallet, the payment is rejected with tx_bad_seq, then built again after the resync
+        horizon.set_sequence(&app_id, sequence + 5).unwrap();
+        pay("carol").await.unwrap();
+        assert_eq!(horizon.request_count(&load_account), 2);
+        assert_eq!(horizon.sequence(&app_id), Some(sequence + 6));
+        assert_eq!(horizon.request_count("POST /transactions"), 4);
+    }
+
+    #[tokio::test]
+    async fn test_mock_resubmit_payment() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        horizon.set_ledger_interval(Duration::from_secs(1));
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        horizon.create_account(&app_id, "100").unwrap();
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        let options = ReqwestClientOptions {
+            base_url: pi_api.url(),
+            horizon_url: Some(horizon.url()),
+            retry_policy: RetryPolicy {
+                max_attempts: 1,
+                ..RetryPolicy::default()
+            },
+            transaction_validity: Some(Duration::from_secs(1)),
+            ..ReqwestClientOptions::default()
+        };
+        let pi = PiNetwork::new(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            None,
+            Some(options),
+        )
+        .unwrap();
+        let flow = PaymentFlow::new(pi.clone(), MemoryPaymentStore::default());
+        let lost = |uid: &'static str, processed: bool| {
+            let wallet = Keypair::random().unwrap().public_key();
+            horizon.create_account(&wallet, "1").unwrap();
+            pi_api.add_user(uid, None, &wallet);
+            let error = MockError::response(
+                "POST /transactions",
+                pi_rust::mock::MockResponse::json(504, &json!({})),
+            );
+            horizon.inject_error(if processed {
+                error.after_processing()
+            } else {
+                error
+            });
+            let args = PaymentArgs {
+                uid: uid.to_string(),
+                ..super::payment_args("1")
+            };
+            let flow = &flow;
+            async move {
+                assert!(flow.start(args).await.is_err());
+                let record = flow.store().find_by_uid(uid).unwrap().remove(0);
+                assert_eq!(record.state, PaymentFlowState::Submitted);
+                (wallet, record)
+            }
+        };
+
+        // The submission never reached Horizon, a new transaction is made after the first one expired
+        let (wallet, record) = lost("alice", false).await;
+        let txid = pi
+            .resubmit_payment(record.payment_id, record.envelope_xdr.as_deref().unwrap())
+            .await
+            .unwrap();
+        assert_ne!(Some(&txid), record.txid.as_ref());
+        assert!(horizon
+            .transaction(record.txid.as_deref().unwrap())
+            .is_none());
+        assert_eq!(horizon.balance(&wallet).unwrap(), "2.0000000");
+        assert_eq!(horizon.request_count("POST /transactions"), 2);
+
+        // The first transaction was applied, it's found instead of paying again
+        let (wallet, record) = lost("bob", true).await;
+        let txid = pi
+            .resubmit_payment(record.payment_id, record.envelope_xdr.as_deref().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(Some(&txid), record.txid.as_ref());
+        assert_eq!(horizon.balance(&wallet).unwrap(), "2.0000000");
+        assert_eq!(horizon.request_count("POST /transactions"), 3);
+
+        // Without time bounds it can't be known whether the first transaction lands later
+        let unbounded = PiNetwork::new(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            None,
+            Some(ReqwestClientOptions {
+                base_url: pi_api.url(),
+                horizon_url: Some(horizon.url()),
+                transaction_validity: None,
+                ..ReqwestClientOptions::default()
+            }),
+        )
+        .unwrap();
+        let flow = PaymentFlow::new(unbounded.clone(), MemoryPaymentStore::default());
+        let wallet = Keypair::random().unwrap().public_key();
+        horizon.create_account(&wallet, "1").unwrap();
+        pi_api.add_user("carol", None, &wallet);
+        horizon.inject_error(
+            MockError::response(
+                "POST /transactions",
+                pi_rust::mock::MockResponse::json(504, &json!({})),
+            )
+            .times(3),
+        );
+        let args = PaymentArgs {
+            uid: "carol".to_string(),
+            ..super::payment_args("1")
+        };
+        assert!(flow.start(args).await.is_err());
+        let record = flow.store().find_by_uid("carol").unwrap().remove(0);
+        assert!(unbounded
+            .resubmit_payment(record.payment_id, record.envelope_xdr.as_deref().unwrap())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mock_resubmit_bad_ledger() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        horizon.set_ledger_interval(Duration::from_secs(1));
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        let user_id = Keypair::random().unwrap().public_key();
+        horizon.create_account(&app_id, "100").unwrap();
+        horizon.create_account(&user_id, "1").unwrap();
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        pi_api.add_user(super::USER_UID, None, &user_id);
+        let options = ReqwestClientOptions {
+            base_url: pi_api.url(),
+            horizon_url: Some(horizon.url()),
+            retry_policy: RetryPolicy {
+                max_attempts: 1,
+                ..RetryPolicy::default()
+            },
+            transaction_validity: Some(Duration::from_secs(1)),
+            ..ReqwestClientOptions::default()
+        };
+        let pi = PiNetwork::new(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            None,
+            Some(options),
+        )
+        .unwrap();
+        let flow = PaymentFlow::new(pi.clone(), MemoryPaymentStore::default());
+        horizon.inject_error(MockError::response(
+            "POST /transactions",
+            pi_rust::mock::MockResponse::json(504, &json!({})),
+        ));
+        assert!(flow.start(super::payment_args("1")).await.is_err());
+        let record = flow.store().find_by_uid(super::USER_UID).unwrap().remove(0);
+
+        // A ledger whose close time can't be read is an error, not a reason to wait forever
+        let url = format!("{}/ledgers?order=desc&limit=1", horizon.url());
+        let mut page: serde_json::Value = reqwest::get(url).await.unwrap().json().await.unwrap();
+        page["_embedded"]["records"][0]["closed_at"] = json!("yesterday");
+        horizon.inject_error(MockError::response(
+            "GET /ledgers",
+            pi_rust::mock::MockResponse::json(200, &page),
+        ));
+        let err = pi
+            .resubmit_payment(
+                record.payment_id.clone(),
+                record.envelope_xdr.as_deref().unwrap(),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("yesterday"), "{}", err);
+
+        pi.resubmit_payment(record.payment_id, record.envelope_xdr.as_deref().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(horizon.balance(&user_id).unwrap(), "2.0000000");
+    }
+
+    #[tokio::test]
+    async fn test_mock_submit_payment_unknown_outcome() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        horizon.set_ledger_interval(Duration::from_secs(1));
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        horizon.create_account(&app_id, "100").unwrap();
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        let options = ReqwestClientOptions {
+            base_url: pi_api.url(),
+            horizon_url: Some(horizon.url()),
+            retry_policy: RetryPolicy {
+                max_attempts: 1,
+                ..RetryPolicy::default()
+            },
+            transaction_validity: Some(Duration::from_secs(1)),
+            ..ReqwestClientOptions::default()
+        };
+        let pi = PiNetwork::new(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            None,
+            Some(options),
+        )
+        .unwrap();
+        let lost = |uid: &'static str, processed: bool| {
+            let wallet = Keypair::random().unwrap().public_key();
+            horizon.create_account(&wallet, "1").unwrap();
+            pi_api.add_user(uid, None, &wallet);
+            let error = MockError::response(
+                "POST /transactions",
+                pi_rust::mock::MockResponse::json(504, &json!({})),
+            );
+            horizon.inject_error(if processed {
+                error.after_processing()
+            } else {
+                error
+            });
+            let args = PaymentArgs {
+                uid: uid.to_string(),
+                ..super::payment_args("1")
+            };
+            let pi = &pi;
+            async move {
+                let payment_id = pi.create_payment(args).await.unwrap();
+                (wallet, pi.submit_payment(payment_id).await.unwrap())
+            }
+        };
+
+        // The transaction was applied, its txid is returned without submitting it again
+        let (wallet, txid) = lost("alice", true).await;
+        assert!(horizon.transaction(&txid).unwrap().successful);
+        assert_eq!(horizon.balance(&wallet).unwrap(), "2.0000000");
+        assert_eq!(horizon.request_count("POST /transactions"), 1);
+
+        // The submission never reached Horizon, a new transaction is made once the first one expired
+        let (wallet, txid) = lost("bob", false).await;
+        assert!(horizon.transaction(&txid).unwrap().successful);
+        assert_eq!(horizon.balance(&wallet).unwrap(), "2.0000000");
+        assert_eq!(horizon.request_count("POST /transactions"), 3);
+    }
+
+    #[tokio::test]
+    async fn test_mock_resolve_in_flight_payment() {
+        resolve_in_flight_payment(IncompletePaymentPolicy::Submit).await;
+    }
+
+    #[tokio::test]
+    async fn test_mock_cancel_in_flight_payment() {
+        resolve_in_flight_payment(IncompletePaymentPolicy::Cancel).await;
+    }
+
+    // Neither submitted again nor cancelled, the payment is completed with the transaction which got in meanwhile
+    async fn resolve_in_flight_payment(policy: IncompletePaymentPolicy) {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        horizon.set_ledger_interval(Duration::from_secs(1));
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        horizon.create_account(&app_id, "100").unwrap();
+        let wallet = Keypair::random().unwrap();
+        horizon.create_account(&wallet.public_key(), "1").unwrap();
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        pi_api.add_user("alice", None, &wallet.public_key());
+        let options = ReqwestClientOptions {
+            base_url: pi_api.url(),
+            horizon_url: Some(horizon.url()),
+            transaction_validity: Some(Duration::from_secs(1)),
+            ..ReqwestClientOptions::default()
+        };
+        let pi = PiNetwork::new(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            None,
+            Some(options),
+        )
+        .unwrap();
+        let args = PaymentArgs {
+            uid: "alice".to_string(),
+            ..super::payment_args("1")
+        };
+        let payment_id = pi.create_payment(args).await.unwrap();
+
+        // The transaction of a crashed run gets into a ledger while the resolver waits for it to expire
+        let app = SodiumKeyPair::from(app_wallet.clone());
+        let mut transaction = payment(
+            &app,
+            &app,
+            &SodiumKeyPair::from(wallet),
+            horizon.sequence(&app_id).unwrap() + 1,
+            "1",
+        );
+        *transaction.memo_mut() = Memo::Text(payment_id.clone());
+        transaction.signatures_mut().clear();
+        transaction
+            .sign(app.as_ref(), &Network::new(PASSPHRASE.to_string()))
+            .unwrap();
+        let pi_horizon = server(&horizon);
+        let in_flight = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            pi_horizon.submit_transaction(transaction).await.unwrap().id
+        });
+
+        let resolutions = pi.resolve_incomplete_payments(policy).await.unwrap();
+        let txid = in_flight.await.unwrap();
+        assert!(resolutions[0].error.is_none());
+        assert_eq!(resolutions[0].kind, IncompletePaymentKind::MemoTransaction);
+        assert_eq!(resolutions[0].action, IncompletePaymentAction::Complete);
+        assert_eq!(resolutions[0].txid, Some(txid));
+        assert_eq!(horizon.request_count("POST /transactions"), 1);
+        let status = pi_api.payment(&payment_id).unwrap().status;
+        assert!(status.developer_completed);
+        assert!(!status.cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_mock_fee_surge() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        let app_seed = app_wallet.secret_key().unwrap();
+        horizon.create_account(&app_id, "100").unwrap();
+        // The ledgers are full, the last base fee of the stats isn't enough anymore
+        horizon.set_base_fee(350);
+        let distribution = |p90: &str| {
+            json!({
+                "max": "500", "min": "100", "mode": "100", "p10": "100", "p20": "100", "p30": "100",
+                "p40": "100", "p50": "100", "p60": "200", "p70": "200", "p80": "300", "p90": p90,
+                "p95": "500", "p99": "500",
+            })
+        };
+        horizon.set_fee_stats(
+            serde_json::from_value(json!({
+                "last_ledger": "1",
+                "last_ledger_base_fee": "100",
+                "ledger_capacity_usage": "1.0",
+                "fee_charged": distribution("400"),
+                "max_fee": distribution("400"),
+            }))
+            .unwrap(),
+        );
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        let pay = |uid: &'static str, fee_policy: FeePolicy| {
+            let wallet = Keypair::random().unwrap().public_key();
+            horizon.create_account(&wallet, "1").unwrap();
+            pi_api.add_user(uid, None, &wallet);
+            let options = ReqwestClientOptions {
+                base_url: pi_api.url(),
+                horizon_url: Some(horizon.url()),
+                fee_policy,
+                ..ReqwestClientOptions::default()
+            };
+            let pi = PiNetwork::new(
+                super::API_KEY.to_string(),
+                app_seed.clone(),
+                None,
+                Some(options),
+            )
+            .unwrap();
+            let args = PaymentArgs {
+                uid: uid.to_string(),
+                ..super::payment_args("1")
+            };
+            async move {
+                let payment_id = pi.create_payment(args).await.unwrap();
+                pi.submit_payment(payment_id).await
+            }
+        };
+
+        let err = pay("alice", FeePolicy::default()).await.unwrap_err();
+        assert_eq!(err.transaction_result_code(), Some("tx_insufficient_fee"));
+
+        // Signed again with 200, then with 400 stroops
+        let raising = FeePolicy {
+            max_retry_fee: Some(1000),
+            ..FeePolicy::default()
+        };
+        let txid = pay("bob", raising).await.unwrap();
+        assert_eq!(horizon.transaction(&txid).unwrap().max_fee, "400");
+        assert_eq!(horizon.request_count("POST /transactions"), 4);
+
+        let percentile = FeePolicy {
+            strategy: FeeStrategy::Percentile(90),
+            max_retry_fee: None,
+        };
+        pay("carol", percentile).await.unwrap();
+        assert_eq!(horizon.request_count("POST /transactions"), 5);
+    }
+}
+
+mod channels {
+    use pi_rust::channels::ChannelPool;
+    use pi_rust::mock::{MockError, MockHorizon, MockPiApi};
+    use pi_rust::retry::RetryPolicy;
+    use pi_rust::stellar_sdk::keypair::Keypair;
+    use pi_rust::{types::*, PiNetwork};
+    use serde_json::json;
+    use std::time::Duration;
+
+    const PASSPHRASE: &str = "Pi Testnet";
+
+    #[tokio::test]
+    async fn test_channel_pool_leases() {
+        let seeds = (0..2)
+            .map(|_| Keypair::random().unwrap().secret_key().unwrap().to_string())
+            .collect();
+        let pool = ChannelPool::new(seeds).unwrap();
+        assert_eq!(pool.len(), 2);
+
+        let first = pool.acquire().await.unwrap();
+        let second = pool.acquire().await.unwrap();
+        assert_ne!(first.public_key(), second.public_key());
+        assert_eq!(pool.available(), 0);
+
+        drop(first);
+        assert_eq!(pool.available(), 1);
+        let third = pool.acquire().await.unwrap();
+        assert!(pool.public_keys().contains(&third.public_key()));
+
+        assert!(ChannelPool::new(vec!["not a seed".to_string()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_channel_accounts_lifecycle() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        horizon.create_account(&app_id, "1000").unwrap();
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        let options = ReqwestClientOptions {
+            base_url: pi_api.url(),
+            horizon_url: Some(horizon.url()),
+            ..ReqwestClientOptions::default()
+        };
+        let pi = PiNetwork::new(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            None,
+            Some(options),
+        )
+        .unwrap();
+
+        // The seeds are there before any account is created, a failed creation can be repeated with them
+        let pool = ChannelPool::generate(3).unwrap();
+        assert_eq!(pool.secret_seeds().len(), 3);
+        horizon.inject_error(MockError::response(
+            "POST /transactions",
+            pi_rust::mock::MockResponse::json(
+                400,
+                &json!({
+                    "type": "https://stellar.org/horizon-errors/transaction_failed",
+                    "title": "Transaction Failed",
+                    "status": 400,
+                    "extras": { "result_codes": { "transaction": "tx_insufficient_balance" }, "result_xdr": "" }
+                }),
+            ),
+        ));
+        assert!(pi
+            .create_channel_accounts(&pool, "5".parse().unwrap())
+            .await
+            .is_err());
+        let txids = pi
+            .create_channel_accounts(&pool, "5".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(txids.len(), 1);
+        // The existing accounts are skipped
+        assert!(pi
+            .create_channel_accounts(&pool, "5".parse().unwrap())
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(!format!("{:?}", pool).contains(pool.secret_seeds()[0].as_str()));
+        for channel in pool.public_keys() {
+            assert_eq!(horizon.balance(channel).unwrap(), "5.0000000");
+        }
+        let channels = pool.public_keys().to_vec();
+        // The clones share the pool
+        let clone = pi.clone();
+        clone.set_channel_pool(pool);
+
+        // Payments of different users are submitted at the same time through the channels
+        let mut payment_ids = Vec::new();
+        for uid in ["alice", "bob", "carol"] {
+            let wallet = Keypair::random().unwrap().public_key();
+            horizon.create_account(&wallet, "1").unwrap();
+            pi_api.add_user(uid, None, &wallet);
+            let args = PaymentArgs {
+                uid: uid.to_string(),
+                ..super::payment_args("2.5")
+            };
+            payment_ids.push(pi.create_payment(args).await.unwrap());
+        }
+        let (first, second, third) = tokio::join!(
+            pi.submit_payment(payment_ids[0].clone()),
+            pi.submit_payment(payment_ids[1].clone()),
+            pi.submit_payment(payment_ids[2].clone()),
+        );
+        for (payment_id, txid) in payment_ids.iter().zip([first, second, third]) {
+            let transaction = horizon.transaction(&txid.unwrap()).unwrap();
+            assert!(channels.contains(&transaction.source_account));
+            assert_eq!(transaction.memo.as_deref(), Some(payment_id.as_str()));
+        }
+        // The channels paid the fees, the app wallet only the payments
+        assert_eq!(horizon.balance(&app_id).unwrap(), "977.4999700");
+        assert_eq!(pi.channel_pool().unwrap().available(), 3);
+
+        let funding = pi
+            .fund_channel_accounts("1".parse().unwrap())
+            .await
+            .unwrap();
+        assert!(funding.is_complete());
+        assert_eq!(funding.txids.len(), 1);
+        assert_eq!(funding.funded, channels);
+        // Each channel got 1 Pi, together they paid the fees of the 3 payments
+        let stroops = |channel: &String| {
+            let balance = horizon.balance(channel).unwrap();
+            balance.replace('.', "").parse::<i64>().unwrap()
+        };
+        assert_eq!(channels.iter().map(stroops).sum::<i64>(), 179_999_700);
+
+        pi.retire_channel_accounts().await.unwrap();
+        assert!(pi.channel_pool().is_err());
+        for channel in &channels {
+            assert!(horizon.balance(channel).is_none());
+        }
+        assert_eq!(horizon.balance(&app_id).unwrap(), "992.4998800");
+
+        // The clone pays from the app wallet after the pool is retired
+        assert!(clone.channel_pool().is_err());
+        let wallet = Keypair::random().unwrap().public_key();
+        horizon.create_account(&wallet, "1").unwrap();
+        pi_api.add_user("dave", None, &wallet);
+        let args = PaymentArgs {
+            uid: "dave".to_string(),
+            ..super::payment_args("2.5")
+        };
+        let payment_id = clone.create_payment(args).await.unwrap();
+        let txid = clone.submit_payment(payment_id).await.unwrap();
+        assert_eq!(horizon.transaction(&txid).unwrap().source_account, app_id);
+    }
+
+    #[tokio::test]
+    async fn test_channel_funding_partial_failure() {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        horizon.set_ledger_interval(Duration::from_secs(1));
+        let mut app_wallet = Keypair::random().unwrap();
+        let app_id = app_wallet.public_key();
+        horizon.create_account(&app_id, "1000").unwrap();
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        let options = ReqwestClientOptions {
+            base_url: pi_api.url(),
+            horizon_url: Some(horizon.url()),
+            retry_policy: RetryPolicy {
+                max_attempts: 1,
+                ..RetryPolicy::default()
+            },
+            transaction_validity: Some(Duration::from_secs(1)),
+            ..ReqwestClientOptions::default()
+        };
+        let pi = PiNetwork::new(
+            super::API_KEY.to_string(),
+            app_wallet.secret_key().unwrap(),
+            None,
+            Some(options),
+        )
+        .unwrap();
+
+        // 101 channels are funded by two transactions
+        let pool = ChannelPool::generate(101).unwrap();
+        let txids = pi
+            .create_channel_accounts(&pool, "1".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(txids.len(), 2);
+        let channels = pool.public_keys().to_vec();
+        pi.set_channel_pool(pool);
+
+        // The first transaction gets in without an answer, it's found in the ledgers instead of being sent again.
+        // The app wallet can't pay the second one.
+        horizon.inject_error(
+            MockError::response(
+                "POST /transactions",
+                pi_rust::mock::MockResponse::json(504, &json!({})),
+            )
+            .after_processing(),
+        );
+        let funding = pi
+            .fund_channel_accounts("8.9".parse().unwrap())
+            .await
+            .unwrap();
+        assert!(!funding.is_complete());
+        assert!(funding.error.is_some());
+        assert_eq!(funding.txids.len(), 1);
+        assert!(horizon.transaction(&funding.txids[0]).is_some());
+        assert_eq!(funding.funded, channels[..100]);
+        assert_eq!(funding.unfunded, channels[100..]);
+        assert_eq!(horizon.request_count("POST /transactions"), 4);
+        for channel in &funding.funded {
+            assert_eq!(horizon.balance(channel).unwrap(), "9.9000000");
+        }
+
+        // Only the unfunded channel is paid again
+        let funding = pi
+            .fund_channels(&funding.unfunded, "1".parse().unwrap())
+            .await
+            .unwrap();
+        assert!(funding.is_complete());
+        assert_eq!(horizon.balance(&channels[100]).unwrap(), "2.0000000");
+        assert_eq!(horizon.balance(&channels[0]).unwrap(), "9.9000000");
+
+        let stranger = Keypair::random().unwrap().public_key();
+        assert!(pi
+            .fund_channels(&[stranger], "1".parse().unwrap())
+            .await
+            .is_err());
+    }
+}
+
+mod signer {
+    use pi_rust::channels::ChannelPool;
+    use pi_rust::mock::{MockHorizon, MockPiApi, MockSigner};
+    use pi_rust::signer::{RemoteSigner, TransactionSigner};
+    use pi_rust::stellar_sdk::keypair::Keypair;
+    use pi_rust::{types::*, PiNetwork};
+
+    const PASSPHRASE: &str = "Pi Testnet";
+
+    // The seed of the app wallet is only held by the mock signer
+    fn setup(signer: &MockSigner) -> (MockHorizon, MockPiApi, String) {
+        let horizon = MockHorizon::start(PASSPHRASE).unwrap();
+        let app_id = signer.public_key();
+        let user_id = Keypair::random().unwrap().public_key();
+        horizon.create_account(&app_id, "1000").unwrap();
+        horizon.create_account(&user_id, "1").unwrap();
+
+        let pi_api = MockPiApi::start(super::API_KEY, &app_id).unwrap();
+        pi_api.add_user(super::USER_UID, None, &user_id);
+        (horizon, pi_api, user_id)
+    }
+
+    fn pi_network(remote: RemoteSigner, horizon: &MockHorizon, pi_api: &MockPiApi) -> PiNetwork {
+        let options = ReqwestClientOptions {
+            base_url: pi_api.url(),
+            horizon_url: Some(horizon.url()),
+            ..ReqwestClientOptions::default()
+        };
+        PiNetwork::new(super::API_KEY.to_string(), remote, None, Some(options)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_http() {
+        let signer = MockSigner::start_http(Keypair::random().unwrap()).unwrap();
+        let (horizon, pi_api, user_id) = setup(&signer);
+        let remote = RemoteSigner::http(&signer.url().unwrap(), &signer.public_key()).unwrap();
+        let pi = pi_network(remote, &horizon, &pi_api);
+        assert_eq!(pi.signer().public_key(), signer.public_key());
+
+        let payment_id = pi.create_payment(super::payment_args("2.5")).await.unwrap();
+        let txid = pi.submit_payment(payment_id.clone()).await.unwrap();
+        assert!(horizon.transaction(&txid).unwrap().successful);
+        assert_eq!(horizon.balance(&user_id).unwrap(), "3.5000000");
+        pi.complete_payment(payment_id, txid).await.unwrap();
+
+        let requests = signer.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].network_passphrase, PASSPHRASE);
+        assert_eq!(requests[0].public_key, signer.public_key());
+
+        // The transactions of the channel accounts are signed by the app wallet too
+        let pool = ChannelPool::generate(2).unwrap();
+        pi.create_channel_accounts(&pool, "5".parse().unwrap())
+            .await
+            .unwrap();
+        pi.set_channel_pool(pool);
+        let payment_id = pi.create_payment(super::payment_args("1")).await.unwrap();
+        let txid = pi.submit_payment(payment_id).await.unwrap();
+        assert!(horizon.transaction(&txid).unwrap().successful);
+        pi.retire_channel_accounts().await.unwrap();
+        assert_eq!(signer.requests().len(), 4);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_remote_signer_unix() {
+        let path = std::env::temp_dir().join(format!("pi-rust-signer-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let signer = MockSigner::start_unix(Keypair::random().unwrap(), &path).unwrap();
+        let (horizon, pi_api, user_id) = setup(&signer);
+        let remote =
+            RemoteSigner::unix(signer.socket_path().unwrap(), &signer.public_key()).unwrap();
+        let pi = pi_network(remote, &horizon, &pi_api);
+
+        let payment_id = pi.create_payment(super::payment_args("2.5")).await.unwrap();
+        let txid = pi.submit_payment(payment_id).await.unwrap();
+        assert!(horizon.transaction(&txid).unwrap().successful);
+        assert_eq!(horizon.balance(&user_id).unwrap(), "3.5000000");
+        assert_eq!(signer.requests().len(), 1);
+
+        drop(signer);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_remote_signer_unix_long_response() {
+        use std::io::{Read, Write};
+
+        let path = std::env::temp_dir().join(format!("pi-rust-long-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 256];
+            let _ = stream.read(&mut request);
+            // A line that never ends
+            let _ = stream.write_all(&vec![b'a'; 128 * 1024]);
+        });
+
+        let public_key = Keypair::random().unwrap().public_key();
+        let remote = RemoteSigner::unix(&path, &public_key).unwrap();
+        let err = remote
+            .sign_transaction_hash(&[0; 32], PASSPHRASE)
+            .await
+            .unwrap_err();
+        assert!(matches!(&err, PiError::Signer(msg) if msg.contains("longer than")));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_refusal() {
+        let signer = MockSigner::start_http(Keypair::random().unwrap()).unwrap();
+        let (horizon, pi_api, _) = setup(&signer);
+        let remote = RemoteSigner::http(&signer.url().unwrap(), &signer.public_key()).unwrap();
+        let pi = pi_network(remote, &horizon, &pi_api);
+        let payment_id = pi.create_payment(super::payment_args("2.5")).await.unwrap();
+
+        // Nothing is submitted without the signature, and it's not mistaken for a lost submission
+        signer.set_network("Pi Network");
+        let err = pi.submit_payment(payment_id.clone()).await.unwrap_err();
+        assert!(matches!(&err, PiError::Signer(msg) if msg.contains("Not signing")));
+        assert!(!err.is_unknown_submission_outcome());
+
+        signer.set_network(PASSPHRASE);
+        signer.set_refusal(Some("The signer is locked"));
+        let err = pi.submit_payment(payment_id.clone()).await.unwrap_err();
+        assert!(err.to_string().contains("The signer is locked"));
+        assert_eq!(horizon.request_count("POST /transactions"), 0);
+
+        // The sequence numbers taken for the refused transactions are not skipped
+        signer.set_refusal(None);
+        let txid = pi.submit_payment(payment_id).await.unwrap();
+        assert!(horizon.transaction(&txid).unwrap().successful);
+        assert_eq!(horizon.request_count("POST /transactions"), 1);
+
+        // A signer of another key is refused by the signing process
+        let other = Keypair::random().unwrap().public_key();
+        let remote = RemoteSigner::http(&signer.url().unwrap(), &other).unwrap();
+        let err = remote
+            .sign_transaction_hash(&[0; 32], PASSPHRASE)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Unknown key"));
+        assert!(RemoteSigner::http(&signer.url().unwrap(), "not a key").is_err());
+    }
+}
@@ -14,13 +14,13 @@ mod tests {
 
     #[tokio::test]
     async fn test_combined() {
-        let mut pi = setup();
+        let pi = setup();
 
         let user_uid = env::var("USER_ID").expect("USER_ID must be set");
 
         println!("Complete payment test");
         let payment_data = PaymentArgs {
-            amount: 0.1,
+            amount: "0.1".parse().unwrap(),
             memo: "Refund for apple pie".to_string(),
             metadata: json!({"productId": "apple-pie-1"}),
             uid: user_uid.clone(),
@@ -38,7 +38,7 @@ mod tests {
         println!("Cancel payment test");
         let payment_id2 = pi.create_payment(payment_data.clone()).await.unwrap();
 
-        let get_payment2 = pi
+        let get_payment2: PaymentDTO = pi
             .get_payment(payment_id2.clone().to_string())
             .await
             .unwrap();
@@ -48,7 +48,7 @@ mod tests {
 
         println!("Looking for incomplete payment");
         let payment_data2 = PaymentArgs {
-            amount: 2.1,
+            amount: "2.1".parse().unwrap(),
             memo: "Refund for apple pie".to_string(),
             metadata: json!({"productId": "apple-pie-1"}),
             uid: user_uid.clone(),
@@ -66,6 +66,52 @@ mod tests {
         assert_ne!(have_incomplete_vec, no_incomplete_vec);
     }
 
+    #[tokio::test]
+    async fn test_authenticate_user_invalid_token() {
+        let pi = setup();
+
+        let result = pi
+            .authenticate_user("invalid_access_token".to_string())
+            .await;
+
+        assert!(matches!(result, Err(PiError::InvalidAccessToken(_))));
+    }
+
+    #[test]
+    fn test_pi_network_is_shareable() {
+        fn assert_shareable<T: Send + Sync + Clone + 'static>() {}
+        fn assert_send<T: Send>(_: &T) {}
+        assert_shareable::<PiNetwork>();
+
+        // The futures have to be Send too, otherwise they can't be spawned on a multi-threaded runtime
+        let pi = PiNetwork::new(
+            "api_key".to_string(),
+            "SAZ443I6BNR2MD3G27C4EZIEEFMKOPT4SR6IHZDLXPODEHR2GRQVIC7R".to_string(),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_send(&pi.submit_payment("payment_id".to_string()));
+        assert_send(&pi.complete_payment("payment_id".to_string(), "txid".to_string()));
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let seed = "SAZ443I6BNR2MD3G27C4EZIEEFMKOPT4SR6IHZDLXPODEHR2GRQVIC7R";
+        let pi =
+            PiNetwork::new("secret_api_key".to_string(), seed.to_string(), None, None).unwrap();
+        assert_eq!(pi.api_key(), "secret_api_key");
+        assert_eq!(
+            pi.signer().public_key(),
+            "GACAMF2WHKKQTYVHVA3CRMVUHN6GUBLTB7PBJQF73N7ATCIYAIFUCT6B"
+        );
+
+        let debug = format!("{:?}", pi);
+        assert!(debug.contains("[REDACTED]"));
+        assert!(!debug.contains("secret_api_key"));
+        assert!(!debug.contains(seed));
+    }
+
     #[test]
     fn test_validate_seed_format() {
         let seed_valid = "SAFPHSUDCR3UUQX36MMRXJZBVZNKFP5OFOZSOLUWTT76QQUPKUUFNRNW";